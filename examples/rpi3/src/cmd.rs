@@ -2,6 +2,11 @@ use core::error::Error;
 use core::time::Duration;
 
 use grow::zone::light::LampState;
+use grow::zone::arm::STEP_TIMEOUT;
+use grow::zone::ZoneKind;
+use grow::zone::lease::{Lease, LeaseHolder, MANUAL_LEASE};
+use grow::command::Command;
+use grow::ops::LogLevel;
 
 
 
@@ -14,16 +19,44 @@ use tokio::task::JoinHandle;
 
 
 
+/// Lease an Arm or Pump for a console command, refused while e.g. the RC session holds it
+async fn console_lease(house: &HouseMutex, kind: ZoneKind, id: u8) -> Option<Lease> {
+    let leased = house.lock().await.leases().acquire(kind, id, LeaseHolder::Console, MANUAL_LEASE);
+    match leased {
+        Ok(lease) => Some(lease),
+        Err(e) => {
+            println!("\t{}", e);
+            None
+        }
+    }
+}
+
 pub fn list_cmds() {
     let general_list = vec![
         ("board", "Show status board"),
         ("status", "Toggle output on status change"),
         ("log", "Toggle output on zone log event"),
+        ("loglevel", "Set log level for a source"),
         ("pset", "Set water position with RC"),
+        ("menu", "Open display menu, operated with RC or buttons"),
         ("pshow", "Show settings for Water zone"),
         ("pconfirm", "Confirm arm positioned for Water zone"),
         ("pgoto", "Go to position for Water zone"),
+        ("pteach", "Teach named Arm position with RC"),
+        ("plist", "List named Arm positions"),
+        ("pmove", "Move Arm to named position"),
+        ("pforget", "Remove named Arm position"),
+        ("proute", "Set named positions for Water zone"),
         ("calib", "Calibrate Arm zero-position"),
+        ("armhome", "Home Arm and move to home position"),
+        ("alerts", "Show active alerts"),
+        ("ack", "Acknowledge alert for zone"),
+        ("snooze", "Snooze alert for zone"),
+        ("tasks", "Show task health and stalled loops"),
+        ("leases", "Show Arm and Pump leases"),
+        ("rules", "Show automation rules"),
+        ("stage", "Show grow stage"),
+        ("profile", "Activate grow profile"),
     ];
     let debug_list = vec![
         ("armpos", "Show current Arm position"),
@@ -32,7 +65,9 @@ pub fn list_cmds() {
         ("arm1y", "Move Arm 1 y-axis"),
         ("pump1", "Run Pump 1 for 3 seconds"),
         ("pump1run", "Run Pump 1 until stopped"),
+        ("pumpspeed", "Set Pump speed"),
         ("ps", "Stop Pump 1"),
+        ("refill", "Start or stop refilling Tank zone"),
         ("fan1dc", "Set fan duty cycle for Air zone 1"),
     ];
     let sensor_list = vec![
@@ -40,6 +75,7 @@ pub fn list_cmds() {
         ("light1", "Take brightness reading from Light zone 1"),
         ("temp1", "Take temp reading from Air zone 1"),
         ("tank1", "Take level reading from Tank zone 1"),
+        ("tankinfo", "Show level and forecast for Tank zone"),
         ("fan1", "Take fan speed reading from Air zone 1"),
     ];
     for cmd in general_list {
@@ -82,8 +118,26 @@ pub fn manual_cmds(
             }
         }
     };
+    let getkind = || -> ( bool, ZoneKind ) {
+        let _line: String = read!("{}\n");
+        match _line.trim().to_lowercase().as_str() {
+            "air" => (true, ZoneKind::Air),
+            "aux" => (true, ZoneKind::Aux),
+            "light" => (true, ZoneKind::Light),
+            "water" => (true, ZoneKind::Water),
+            "arm" => (true, ZoneKind::Arm),
+            "pump" => (true, ZoneKind::Pump),
+            "tank" => (true, ZoneKind::Tank),
+            other => {
+                eprintln!("Unknown zone kind {:?}. Try again.", other);
+                (false, ZoneKind::Air)
+            }
+        }
+    };
     Ok(tokio::spawn(async move {
         tokio::time::sleep(Duration::from_millis(500)).await;
+        // Board reads don't wait for the house, e.g. during watering
+        let view = house.lock().await.view();
         tokio::task::yield_now().await;
         loop {
             print!("(l)ist cmds, or (q)uit >");
@@ -95,7 +149,7 @@ pub fn manual_cmds(
             match line {
                 // Operations commands
                 _line if _line.contains("board") => {
-                    let mut board = house.lock().await.collect_display_status();
+                    let mut board = view.collect_display_status();
                     board.sort();
                     for z in board {
                         println!("{}", &z);
@@ -111,16 +165,128 @@ pub fn manual_cmds(
                     let _ = manager.lock().await.blink().await;
                     tokio::task::yield_now().await;
                 }
+                _line if _line.contains("menu") => {
+                    match manager.lock().await.open_menu().await {
+                        Ok(()) => println!("Menu opened on display"),
+                        Err(e) => println!("Error: {}", e),
+                    }
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("loglevel") => {
+                    print!("Log source, e.g. water/1 or zonelog > ");
+                    let source: String = read!("{}\n");
+                    print!("Level (trace/debug/info/notice/warning/error/off) > ");
+                    let level: String = read!("{}\n");
+                    let level = match level.trim() {
+                        "trace" => Some(LogLevel::Trace),
+                        "debug" => Some(LogLevel::Debug),
+                        "info" => Some(LogLevel::Info),
+                        "notice" => Some(LogLevel::Notice),
+                        "warning" => Some(LogLevel::Warning),
+                        "error" => Some(LogLevel::Error),
+                        "off" => None,
+                        _ => {
+                            println!("Unknown level");
+                            continue;
+                        }
+                    };
+                    match manager.lock().await.set_log_source(source.trim(), level) {
+                        Ok(()) => println!("Log level for {} set to {:?}", source.trim(), level),
+                        Err(e) => println!("Error: {}", e),
+                    }
+                    tokio::task::yield_now().await;
+                }
                 _line if _line.contains("log") => {
-                    let set_to = manager.lock().await.zonelog_toggle().unwrap();
+                    let set_to = manager.lock().await.toggle_log_source("zonelog", LogLevel::Debug);
                     println!("Output zone log: {:?}", set_to);
                     tokio::task::yield_now().await;
                 }
                 _line if _line.contains("status") => {
-                    let set_to = manager.lock().await.statuslog_toggle().unwrap();
+                    let set_to = manager.lock().await.toggle_log_source("zonestatus", LogLevel::Info);
                     println!("Output status log: {:?}", set_to);
                     tokio::task::yield_now().await;
                 }
+                _line if _line.contains("alerts") => {
+                    match manager.lock().await.alerts() {
+                        Some(alerts) => {
+                            for a in alerts {
+                                println!("\t{:?} {} {:?} since {} notified:{} escalated:{} ack:{} snoozed:{} {:?}",
+                                    a.kind, a.id, a.indicator, a.since, a.notified, a.escalated, a.acknowledged, a.snoozed, a.msg);
+                            }
+                        }
+                        None => println!("Alerter not running"),
+                    }
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("tasks") => {
+                    for t in manager.lock().await.task_health() {
+                        println!("\t{} {:?} restarts:{} last failure:{:?}", t.name, t.state, t.restarts, t.last_failure);
+                    }
+                    match manager.lock().await.controller_health() {
+                        Some(health) => println!("\tStalled:{:?} House lock held:{:?}", health.stalled, health.lock_held),
+                        None => println!("\tHeartbeat monitor not running"),
+                    }
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("leases") => {
+                    let now = std::time::Instant::now();
+                    for l in house.lock().await.leases().held() {
+                        println!("\t{:?} {} held by {}, {:?} left", l.kind, l.id, l.holder, l.until.saturating_duration_since(now));
+                    }
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("rules") => {
+                    match manager.lock().await.rules() {
+                        Some(rules) => {
+                            for r in rules {
+                                println!("\t{} active:{} since {:?}", r.name, r.active, r.since);
+                            }
+                        }
+                        None => println!("Rules engine not running"),
+                    }
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("stage") => {
+                    match house.lock().await.stage() {
+                        Some(stage) => println!("\t{} since {}", stage, stage.since),
+                        None => println!("\tNo stage"),
+                    }
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("profile") => {
+                    print!("Profile name (empty for calendar) > ");
+                    let name: String = read!("{}\n");
+                    let name = Some(name.trim().to_string()).filter(|name| !name.is_empty());
+                    let result = house.lock().await.activate_profile(name.clone()).await;
+                    println!("\tProfile {:?}: {:?}", &name, &result);
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("ack") => {
+                    print!("Acknowledge alert, zone kind > ");
+                    let kind = getkind();
+                    if !kind.0 {continue;}
+                    print!("Zone id > ");
+                    let zid = getnum_u8();
+                    if !zid.0 {continue;}
+                    let result = manager.lock().await.alert_ack(kind.1, zid.1).await;
+                    println!("\tAcknowledge {:?} {}: {:?}", &kind.1, &zid.1, &result);
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("snooze") => {
+                    print!("Snooze alert, zone kind > ");
+                    let kind = getkind();
+                    if !kind.0 {continue;}
+                    print!("Zone id > ");
+                    let zid = getnum_u8();
+                    if !zid.0 {continue;}
+                    print!("Minutes > ");
+                    let mins = getnum_u8();
+                    if !mins.0 {continue;}
+                    let result = manager.lock().await
+                        .alert_snooze(kind.1, zid.1, Duration::from_secs(mins.1 as u64 * 60)).await;
+                    println!("\tSnooze {:?} {}: {:?}", &kind.1, &zid.1, &result);
+                    tokio::task::yield_now().await;
+                }
                 _line if _line.contains("pset") => {
                     // Connect to remote
                     print!("Set position for Water zone > ");
@@ -145,17 +311,30 @@ pub fn manual_cmds(
                     print!("Move Arm to Water position > ");
                     let zid = getnum_u8();
                     if !zid.0 {continue;}
-                    {
+                    let planned = {
                         let mut lock = house.lock().await;
                         let response = lock.get_water_settings(zid.1);
                         println!("\tWater zone {} settings: {:#?}", &zid.1, &response);
-                        let movement = response.unwrap().position;
-                        let _ = lock.arm_goto(
-                            movement.arm_id,
-                            movement.x,
-                            movement.y,
-                            movement.z,
-                        );
+                        match response {
+                            Some(ws) => lock.arm_plan(
+                                ws.position.arm_id,
+                                ws.position.x,
+                                ws.position.y,
+                                ws.position.z,
+                            ),
+                            None => {
+                                println!("Water zone not found");
+                                continue;
+                            }
+                        }
+                    };
+                    match planned {
+                        Ok(arm_move) => {
+                            println!("\tSteps: {:?}", &arm_move.steps);
+                            let result = arm_move.run(STEP_TIMEOUT).await;
+                            println!("\tMove result: {:?}", result);
+                        }
+                        Err(e) => println!("\tMove rejected: {}", e),
                     }
                     tokio::task::yield_now().await;
                 }
@@ -170,6 +349,72 @@ pub fn manual_cmds(
                     }
                     tokio::task::yield_now().await;
                 }
+                _line if _line.contains("pteach") => {
+                    print!("Teach position for Arm > ");
+                    let zid = getnum_u8();
+                    if !zid.0 {continue;}
+                    print!("Position name > ");
+                    let name: String = read!("{}\n");
+                    let taught = manager.lock().await.teach_from_rc(zid.1, name.trim()).await;
+                    println!("\tTeach {} on Arm {}: {:?}", name.trim(), &zid.1, &taught);
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("plist") => {
+                    print!("List positions for Arm > ");
+                    let zid = getnum_u8();
+                    if !zid.0 {continue;}
+                    match house.lock().await.arm_positions(zid.1) {
+                        Some(positions) => {
+                            for (name, point) in positions {
+                                println!("{:>12}\t{}, {}, {}", name, point.x, point.y, point.z);
+                            }
+                        }
+                        None => println!("\tArm {} not found", &zid.1),
+                    }
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("pmove") => {
+                    print!("Move Arm > ");
+                    let zid = getnum_u8();
+                    if !zid.0 {continue;}
+                    print!("To position > ");
+                    let name: String = read!("{}\n");
+                    let planned = house.lock().await.arm_plan_named(zid.1, name.trim());
+                    match planned {
+                        Ok(arm_move) => {
+                            println!("\tSteps: {:?}", &arm_move.steps);
+                            let result = arm_move.run(STEP_TIMEOUT).await;
+                            println!("\tMove result: {:?}", result);
+                        }
+                        Err(e) => println!("\tMove rejected: {}", e),
+                    }
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("pforget") => {
+                    print!("Remove position from Arm > ");
+                    let zid = getnum_u8();
+                    if !zid.0 {continue;}
+                    print!("Position name > ");
+                    let name: String = read!("{}\n");
+                    let result = house.lock().await.forget_position(zid.1, name.trim());
+                    println!("\tRemove {}: {:?}", name.trim(), &result);
+                    tokio::task::yield_now().await;
+                }
+                _line if _line.contains("proute") => {
+                    print!("Set positions for Water zone > ");
+                    let zid = getnum_u8();
+                    if !zid.0 {continue;}
+                    print!("Position names, comma separated (empty for single position) > ");
+                    let names_line: String = read!("{}\n");
+                    let names: Vec<String> = names_line
+                        .split(',')
+                        .map(|n| n.trim().to_string())
+                        .filter(|n| !n.is_empty())
+                        .collect();
+                    let result = house.lock().await.set_water_positions(zid.1, names);
+                    println!("\tWater zone {} positions: {:?}", &zid.1, &result);
+                    tokio::task::yield_now().await;
+                }
                 _line if _line.contains("load") => {
                     println!("Load settings...");
                     let _ = house.lock().await.load_settings();
@@ -189,24 +434,37 @@ pub fn manual_cmds(
                     let zid = getnum_u8();
                     if !zid.0 {continue}
                     let mut lock = house.lock().await;
-                    let response = lock.read_moisture_value(zid.1);
+                    let response = lock.execute(Command::ReadMoisture { id: zid.1 }).await;
                     println!("\tWater zone {} moisture: {:?}", &zid.1, &response);
                 }
                 _line if _line.contains("light1") => {
                     let mut lock = house.lock().await;
-                    let response = lock.read_light_value(1u8);
+                    let response = lock.execute(Command::ReadLight { id: 1 }).await;
                     println!("\tLight zone {} brightness: {:?}", 1, &response);
                 }
                 _line if _line.contains("temp1") => {
                     let mut lock = house.lock().await;
-                    let response = lock.read_temperature_value(1u8);
+                    let response = lock.execute(Command::ReadTemperature { id: 1 }).await;
                     println!("\tAir zone {} temperature: {:?}", 1, &response);
                 }
                 _line if _line.contains("fan1") => {
                     let mut lock = house.lock().await;
-                    let response = lock.read_fan_speed(1u8);
+                    let response = lock.execute(Command::ReadFanSpeed { id: 1 }).await;
                     println!("\tAir zone {} fan speed: {:?}", 1, &response);
                 }
+                _line if _line.contains("tankinfo") => {
+                    print!("Show level for Tank zone > ");
+                    let zid = getnum_u8();
+                    if !zid.0 {continue;}
+                    match house.lock().await.tank_status(zid.1) {
+                        Some(status) => println!(
+                            "\tTank {}: {:?}, {:?}%, {:?} l, empty in {:?} days",
+                            &zid.1, status.level, status.percent, status.volume, status.days_left
+                        ),
+                        None => println!("\tTank {} not found", &zid.1),
+                    }
+                    tokio::task::yield_now().await;
+                }
                 _line if _line.contains("tank1") => {
                     let mut lock = house.lock().await;
                     let response = lock.read_tank_level(1u8);
@@ -226,24 +484,45 @@ pub fn manual_cmds(
                     let input = _line.trim().parse::<f64>().unwrap();
                     let _ = house.lock().await.set_fan_duty_cycle(1, input);
                 }
+                _line if _line.contains("refill") => {
+                    print!("Refill Tank zone > ");
+                    let zid = getnum_u8();
+                    if !zid.0 {continue;}
+                    print!("1 start, 0 stop > ");
+                    let on = getnum_u8();
+                    if !on.0 {continue;}
+                    let result = house.lock().await.tank_refill(zid.1, on.1 != 0);
+                    println!("\tTank {} refill: {:?}", &zid.1, &result);
+                    tokio::task::yield_now().await;
+                }
 
                 // Pump actions
+                _line if _line.contains("pumpspeed") => {
+                    print!("Set speed for Pump > ");
+                    let zid = getnum_u8();
+                    if !zid.0 {continue;}
+                    print!("Speed, -100..100 > ");
+                    let speed = getnum_i32();
+                    if !speed.0 {continue;}
+                    let result = house.lock().await.pump_set_speed(zid.1, speed.1.clamp(-100, 100) as i8).await;
+                    println!("\tPump {} speed {}: {:?}", &zid.1, &speed.1, &result);
+                    tokio::task::yield_now().await;
+                }
                 _line if _line.contains("pump1run") => {
                     let m = house.clone();
                     tokio::spawn(async move {
-                        let _ = m.lock().await.pump_run(1u8).await;
+                        // Leased to the console by the house
+                        if let Err(e) = m.lock().await.pump_run(1u8).await {
+                            println!("\tPump 1 run: {}", e);
+                        }
                     });
                     tokio::task::yield_now().await;
                 }
                 _line if _line.contains("pump1") => {
                     let m = house.clone();
                     tokio::spawn(async move {
-                        {
-                            let _ = m.lock().await.pump_run(1u8).await;
-                        }
-                        tokio::time::sleep(Duration::from_secs(3)).await;
-                        {
-                            let _ = m.lock().await.pump_stop(1u8).await;
+                        if let Err(e) = m.lock().await.pump_run_for_secs(1u8, 3).await {
+                            println!("\tPump 1 run: {}", e);
                         }
                     });
                     tokio::task::yield_now().await;
@@ -260,14 +539,33 @@ pub fn manual_cmds(
                     print!("Arm 1 goto X > ");
                     let pos_x = getnum_i32();
                     if !pos_x.0 {continue;}
-                    let _ = house.lock().await.arm_goto_x(1u8, pos_x.1).await;
+                    let lease = console_lease(&house, ZoneKind::Arm, 1u8).await;
+                    if lease.is_none() {continue;}
+                    // Planned under the lock, moved without it
+                    let planned = {
+                        let mut lock = house.lock().await;
+                        lock.arm_position(1u8).and_then(|(_, y, z)| lock.arm_plan(1u8, pos_x.1, y, z))
+                    };
+                    match planned {
+                        Ok(arm_move) => println!("Arm moved: {:?}", arm_move.run(STEP_TIMEOUT).await),
+                        Err(e) => println!("Arm move error: {}", e),
+                    }
                     tokio::task::yield_now().await;
                 }
                 _line if _line.contains("arm1y") => {
                     print!("Arm 1 goto Y > ");
                     let pos_y = getnum_i32();
                     if !pos_y.0 {continue;}
-                    let _ = house.lock().await.arm_goto_y(1u8, pos_y.1).await;
+                    let lease = console_lease(&house, ZoneKind::Arm, 1u8).await;
+                    if lease.is_none() {continue;}
+                    let planned = {
+                        let mut lock = house.lock().await;
+                        lock.arm_position(1u8).and_then(|(x, _, z)| lock.arm_plan(1u8, x, pos_y.1, z))
+                    };
+                    match planned {
+                        Ok(arm_move) => println!("Arm moved: {:?}", arm_move.run(STEP_TIMEOUT).await),
+                        Err(e) => println!("Arm move error: {}", e),
+                    }
                     tokio::task::yield_now().await;
                 }
                 _line if _line.contains("arm1") => {
@@ -277,7 +575,13 @@ pub fn manual_cmds(
                     print!("Arm 1 goto Y > ");
                     let pos_y = getnum_i32();
                     if !pos_y.0 {continue;}
-                    let _ = house.lock().await.arm_goto(1u8, pos_x.1, pos_y.1, 0).await;
+                    let lease = console_lease(&house, ZoneKind::Arm, 1u8).await;
+                    if lease.is_none() {continue;}
+                    let planned = house.lock().await.arm_plan(1u8, pos_x.1, pos_y.1, 0);
+                    match planned {
+                        Ok(arm_move) => println!("Arm moved: {:?}", arm_move.run(STEP_TIMEOUT).await),
+                        Err(e) => println!("Arm move error: {}", e),
+                    }
                     tokio::task::yield_now().await;
                 }
                 _line if _line.contains("armupdate") => {
@@ -292,6 +596,17 @@ pub fn manual_cmds(
                     }
                     tokio::task::yield_now().await;
                 }
+                _line if _line.contains("armhome") => {
+                    let homing = house.lock().await.arm_home(1);
+                    match homing {
+                        Ok(homing) => {
+                            let result = homing.run(STEP_TIMEOUT).await;
+                            println!("Arm homing: {:?}", result);
+                        }
+                        Err(e) => println!("Arm homing error: {}", e),
+                    }
+                    tokio::task::yield_now().await;
+                }
                 _line if _line.contains("calib") => {
                     {
                         let mut lock = house.lock().await;
@@ -303,7 +618,7 @@ pub fn manual_cmds(
 
                 // Special commands
                 _line if _line.contains("b") => {
-                    let mut board = house.lock().await.collect_display_status();
+                    let mut board = view.collect_display_status();
                     board.sort();
                     for z in board {
                         println!("{}", &z);
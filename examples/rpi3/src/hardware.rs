@@ -22,6 +22,12 @@ pub mod pushbuttons;
 // Dummy sensors
 pub mod dummy;
 
+/// Tank refill valve (direct GPIO, relay)
+pub mod refill;
+
+/// Driver registry, bound to zones in the config
+pub mod drivers;
+
 /// Hardware config
 pub use conf::*;
 pub mod conf {
@@ -38,6 +44,7 @@ pub mod conf {
     pub const INDICATORS_SR_LATCH: u8 = 13; // 19p35
     pub const INDICATORS_SR_RESET: u8 = 5; // 26p37
     pub const HELPER_PUMP_PIN: u8 = 4; //4p7
+    pub const REFILL_VALVE_PIN: u8 = 17; //17p11
 
     // Fan
     pub const PIN_FAN_1_RPM: u8 = 23; //BCM 26 phys 16
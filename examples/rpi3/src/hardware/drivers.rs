@@ -0,0 +1,178 @@
+use core::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+use lego_powered_up::HubMutex;
+use ::pcf8591::Pin;
+
+use grow::driver::{Device, DriverParams, DriverRegistry};
+use grow::GrowError;
+
+use super::conf::*;
+use super::pcf8591::{AdcInput, AdcMutex};
+use super::{dummy, lpu, pcf8591, pwmfan, refill};
+
+/// Drivers of this installation by the names used in the config bindings
+pub fn registry(
+    cancel: CancellationToken,
+    hub: HubMutex,
+    adc: AdcMutex,
+) -> DriverRegistry {
+    let mut drivers = DriverRegistry::default();
+
+    // Direct GPIO/PWM
+    let c = cancel.clone();
+    drivers.register("pwmfan", move |id, params| {
+        let supported = params.supported("pwmfan", &[]);
+        let c = c.clone();
+        async move {
+            supported?;
+            Ok(Device::Fan(Box::new(pwmfan::PwmFan::new(id, c))))
+        }
+    });
+    drivers.register("gpio-valve", |id, params| async move {
+        params.supported("gpio-valve", &["pin"])?;
+        let pin = params.pin.unwrap_or(REFILL_VALVE_PIN);
+        Ok(Device::Refill(Box::new(refill::GpioValve::with_pin(id, pin))))
+    });
+
+    // PCF8591 ADC
+    let a = adc.clone();
+    drivers.register("pcf8591-thermistor", move |id, params| {
+        let adc = a.clone();
+        // Any change in temperature is sent unless a delta is set
+        let input = adc_input(id, &params, &TEMP_SENSOR, DELAY_TEMP_1, 0.0);
+        async move { Ok(Device::Thermo(Box::new(pcf8591::Thermistor::new(id, adc, input?)))) }
+    });
+    let a = adc.clone();
+    drivers.register("pcf8591-photoresistor", move |id, params| {
+        let adc = a.clone();
+        let input = adc_input(id, &params, &LIGHT_SENSOR, DELAY_LIGHT_1, LIGHT_1_DELTA);
+        async move { Ok(Device::Lightmeter(Box::new(pcf8591::Photoresistor::new(id, adc, input?)))) }
+    });
+    let a = adc.clone();
+    drivers.register("pcf8591-led", move |id, params| {
+        let adc = a.clone();
+        let shared = params.supported("pcf8591-led", &["bus", "address"]).and_then(|_| shared_adc(&params));
+        async move {
+            shared?;
+            Ok(Device::Lamp(Box::new(pcf8591::Led::new(id, adc))))
+        }
+    });
+    let a = adc.clone();
+    drivers.register("pcf8591-moisture", move |id, params| {
+        let adc = a.clone();
+        let input = adc_input(id, &params, &MOIST_SENSOR, DELAY_MOIST_1, MOIST_1_AND_2_DELTA);
+        async move {
+            Ok(Device::Moist(Box::new(pcf8591::CapacitiveMoistureSensor::new(id, adc, input?))))
+        }
+    });
+
+    // Lego Powered Up, `address` is the hub port
+    let h = hub.clone();
+    drivers.register("lpu-temp", move |id, params| {
+        let hub = h.clone();
+        let port = params.supported("lpu-temp", &["address"]).and_then(|_| hub_port(&params, LPU_TEMP_SENSOR_2_ADDR));
+        async move { Ok(Device::Thermo(Box::new(lpu::LpuTemp::new(id, hub, port?)))) }
+    });
+    let h = hub.clone();
+    let c = cancel.clone();
+    drivers.register("lpu-hub", move |id, params| {
+        let supported = params.supported("lpu-hub", &[]);
+        let hub = h.clone();
+        let cancel = c.clone();
+        async move {
+            supported?;
+            Ok(Device::Aux(Box::new(lpu::LpuHub::new(id, hub, cancel))))
+        }
+    });
+    let h = hub.clone();
+    drivers.register("lpu-vision", move |id, params| {
+        let supported = params.supported("lpu-vision", &[]);
+        let hub = h.clone();
+        async move {
+            supported?;
+            Ok(Device::TankSensor(Box::new(lpu::VisionSensor::new(id, hub))))
+        }
+    });
+    let h = hub.clone();
+    drivers.register("lpu-pump", move |id, params| {
+        let hub = h.clone();
+        // `pin` is the GPIO of the helper pump relay
+        let port = params.supported("lpu-pump", &["address", "pin"]).and_then(|_| hub_port(&params, PUMP_ADDR));
+        let helper_pin = params.pin.unwrap_or(HELPER_PUMP_PIN);
+        async move { Ok(Device::Pump(Box::new(lpu::BrickPump::new(id, hub, port?, helper_pin).await))) }
+    });
+    let h = hub.clone();
+    drivers.register("lpu-arm", move |id, params| {
+        // Two motors on fixed ports, one address can not name both
+        let supported = params.supported("lpu-arm", &[]);
+        let hub = h.clone();
+        async move {
+            supported?;
+            Ok(Device::Arm(Box::new(lpu::BrickArm::new(id, hub).await)))
+        }
+    });
+
+    // Dummy
+    drivers.register("dummy-moisture", |id, params| async move {
+        params.supported("dummy-moisture", &[])?;
+        Ok(Device::Moist(Box::new(dummy::DummyMoistureSensor::new(
+            id,
+            MOIST_DUMMY_VALUE,
+        ))))
+    });
+
+    drivers
+}
+
+/// The PCF8591 drivers share one ADC, a binding may only repeat its bus and address
+fn shared_adc(params: &DriverParams) -> Result<(), GrowError> {
+    if params.bus.as_deref().is_some_and(|bus| bus != YL40_BUS)
+        | params.address.is_some_and(|address| address != YL40_ADDR)
+    {
+        return Err(GrowError::Config(format!(
+            "PCF8591 drivers share the ADC at {} {:#x}",
+            YL40_BUS, YL40_ADDR
+        )));
+    }
+    Ok(())
+}
+
+/// Input `pin` 0-3 of the ADC, or the default input for the zone id, with poll interval
+/// in seconds and report delta defaults
+fn adc_input(
+    id: u8,
+    params: &DriverParams,
+    pins: &[Pin],
+    poll: u64,
+    delta: f32,
+) -> Result<AdcInput, GrowError> {
+    params.supported("pcf8591", &["bus", "address", "pin", "poll", "delta"])?;
+    shared_adc(params)?;
+    let pin = match params.pin {
+        Some(0) => Pin::AIN0,
+        Some(1) => Pin::AIN1,
+        Some(2) => Pin::AIN2,
+        Some(3) => Pin::AIN3,
+        Some(pin) => return Err(GrowError::Config(format!("PCF8591 has no input {}", pin))),
+        None => *(id as usize)
+            .checked_sub(1)
+            .and_then(|i| pins.get(i))
+            .ok_or_else(|| GrowError::Config(format!("No default PCF8591 input for id {}", id)))?,
+    };
+    Ok(AdcInput {
+        pin,
+        poll: params.poll.unwrap_or(Duration::from_secs(poll)),
+        delta: params.delta.unwrap_or(delta),
+    })
+}
+
+/// Hub port from `address`, or the default port of the driver
+fn hub_port(params: &DriverParams, default: u8) -> Result<u8, GrowError> {
+    match params.address {
+        Some(address) => u8::try_from(address)
+            .map_err(|_| GrowError::Config(format!("Hub port {:#x} out of range", address))),
+        None => Ok(default),
+    }
+}
@@ -13,6 +13,7 @@ use tokio::task::JoinHandle;
 
 // use super::conf::*;
 use grow::zone;
+use grow::GrowError;
 // use grow::zone::light::LampState;
 
 #[derive(Debug)]
@@ -25,13 +26,13 @@ impl zone::water::MoistureSensor for DummyMoistureSensor {
     fn id(&self) -> u8 {
         self.id
     }
-    fn read(&self) -> Result<f32, Box<dyn Error + '_>> {
+    fn read(&self) -> Result<f32, GrowError> {
         Ok(self.value)
     }
     fn init(
         &mut self,
         tx_moist: tokio::sync::broadcast::Sender<(u8, Option<f32>)>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         self.feedback_task = Some(
             self.moist_feedback(tx_moist)
                 .expect("Error initializing feedback task"),
@@ -36,6 +36,7 @@ use lego_powered_up::notifications::{StartupInfo, CompletionInfo};
 
 use grow::ops::display::Indicator;
 use grow::zone;
+use grow::GrowError;
 use grow::zone::arm::ArmCmd;
 use grow::zone::pump::PumpCmd;
 use grow::zone::tank::TankLevel;
@@ -82,7 +83,7 @@ impl zone::water::tank::TankSensor for VisionSensor {
     async fn init(
         &mut self,
         tx_tanklevel: tokio::sync::broadcast::Sender<(u8, Option<TankLevel>)>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         self.feedback_task = Some(
             self.tank_feedback(tx_tanklevel)
                 .await
@@ -90,7 +91,7 @@ impl zone::water::tank::TankSensor for VisionSensor {
         );
         Ok(())
     }
-    fn read(&self) -> Result<TankLevel, Box<dyn Error>> {
+    fn read(&self) -> Result<TankLevel, GrowError> {
         Ok(*self.level.read())
     }
 }
@@ -158,43 +159,48 @@ pub struct BrickPump {
     control_task: Option<JoinHandle<()>>,
     feedback_task: Option<JoinHandle<()>>,
     helper_pump: Arc<RwLock<OutputPin>>,
+    speed: Arc<RwLock<i8>>,
 }
 #[async_trait]
 impl zone::water::pump::Pump for BrickPump {
     fn id(&self) -> u8 {
         self.id
     }
-    async fn run_for_secs(&self, secs: u16) -> Result<(), Box<dyn Error>> {
+    async fn run_for_secs(&self, secs: u16) -> Result<(), GrowError> {
         // println!("LPU got cmd: run_for_secs({}", &secs);
-        self.device.start_speed(50, 100).await?;
+        self.device.start_speed(*self.speed.read(), 100).await.map_err(GrowError::hardware)?;
         self.helper_pump.write().set_high();
         sleep(Duration::from_secs(secs as u64)).await;
         self.helper_pump.write().set_low();
-        self.device.start_power(Power::Float).await?;
+        self.device.start_power(Power::Float).await.map_err(GrowError::hardware)?;
         Ok(())
     }
-    async fn run(&self) -> Result<(), Box<dyn Error>> {
+    async fn run(&self) -> Result<(), GrowError> {
         // println!("LPU got cmd: RUN");
-        self.device.start_speed(50, 100).await?;
+        self.device.start_speed(*self.speed.read(), 100).await.map_err(GrowError::hardware)?;
         self.helper_pump.write().set_high();
         Ok(())
     }
-    async fn stop(&self) -> Result<(), Box<dyn Error>> {
+    async fn set_speed(&self, speed: i8) -> Result<(), GrowError> {
+        *self.speed.write() = speed;
+        Ok(())
+    }
+    async fn stop(&self) -> Result<(), GrowError> {
         // println!("LPU got cmd: STOP");
         self.helper_pump.write().set_low();
-        self.device.start_power(Power::Brake).await?;
+        self.device.start_power(Power::Brake).await.map_err(GrowError::hardware)?;
         Ok(())
     }
-    async fn float(&self) -> Result<(), Box<dyn Error>> {
+    async fn float(&self) -> Result<(), GrowError> {
         self.helper_pump.write().set_low();
-        self.device.start_power(Power::Float).await?;
+        self.device.start_power(Power::Float).await.map_err(GrowError::hardware)?;
         Ok(())
     }
     async fn init(
         &mut self,
         rx_pumpcmd: tokio::sync::broadcast::Receiver<(u8, PumpCmd)>,
         tx_pump: tokio::sync::broadcast::Sender<(u8, (i8, i32))>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         self.control_task = Some(
             self.pump_control(rx_pumpcmd)
                 .await
@@ -209,13 +215,14 @@ impl zone::water::pump::Pump for BrickPump {
     }
 }
 impl BrickPump {
-    pub async fn new(id: u8, hub: HubMutex) -> Self {
+    /// Pump motor on hub `port`, helper pump relay on GPIO `helper_pin`
+    pub async fn new(id: u8, hub: HubMutex, port: u8, helper_pin: u8) -> Self {
         let device = hub.lock().await
-        .io_from_port(PUMP_ADDR)
+        .io_from_port(port)
         .expect("Error accessing LPU device");
         let helper_pump = Gpio::new()
         .expect("New gpio error")
-        .get(HELPER_PUMP_PIN)
+        .get(helper_pin)
         .expect("Get pin error")
         .into_output_low();
         Self {
@@ -225,6 +232,7 @@ impl BrickPump {
             control_task: None,
             feedback_task: None,
             helper_pump: Arc::new(RwLock::new(helper_pump)),
+            speed: Arc::new(RwLock::new(50)),
         }
     }
 
@@ -235,13 +243,15 @@ impl BrickPump {
         let _id = self.id;
         let device = self.device.clone();
         let helper_pump = self.helper_pump.clone();
+        let speed = self.speed.clone();
         Ok(tokio::spawn(async move {
             // println!("Spawned pump control");
             while let Ok(data) = rx_cmd.recv().await {
                 // println!("Pump recv cmd: {:?}", &data);
                 match data {
                     (_id, PumpCmd::RunForSec(secs)) => {
-                        let _ = device.start_speed(50, 100);
+                        let speed = *speed.read();
+                        let _ = device.start_speed(speed, 100);
                         helper_pump.write().set_high();
                         sleep(Duration::from_secs(secs as u64)).await;
                         helper_pump.write().set_low();
@@ -298,7 +308,7 @@ impl zone::water::arm::Arm for BrickArm {
         _tx_axis_z: tokio::sync::broadcast::Sender<(i8, i32)>,
         tx_control: grow::zone::arm::ControlFeedbackTx,
         rx_cmd: tokio::sync::broadcast::Receiver<ArmCmd>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         self.feedback_task = Some(
             self.arm_feedback(
                 tx_axis_x,
@@ -316,76 +326,76 @@ impl zone::water::arm::Arm for BrickArm {
         );
         Ok(())
     }
-    async fn goto(&self, x: i32, y: i32, _z: i32) -> Result<(), Box<dyn Error>> {
+    async fn goto(&self, x: i32, y: i32, _z: i32) -> Result<(), GrowError> {
         self.device_x
-            .goto_absolute_position_soc(x, 40, 20, EndState::Brake, StartupInfo::ExecuteImmediately, CompletionInfo::CommandFeedback).await?;
+            .goto_absolute_position_soc(x, 40, 20, EndState::Brake, StartupInfo::ExecuteImmediately, CompletionInfo::CommandFeedback).await.map_err(GrowError::hardware)?;
         self.device_y
-            .goto_absolute_position_soc(y, 100, 60, EndState::Brake, StartupInfo::ExecuteImmediately, CompletionInfo::CommandFeedback).await?;
+            .goto_absolute_position_soc(y, 100, 60, EndState::Brake, StartupInfo::ExecuteImmediately, CompletionInfo::CommandFeedback).await.map_err(GrowError::hardware)?;
         Ok(())
     }
-    async fn stop(&self) -> Result<(), Box<dyn Error>> {
-        self.device_x.start_power(Power::Brake).await?;
-        self.device_y.start_power(Power::Brake).await?;
+    async fn stop(&self) -> Result<(), GrowError> {
+        self.device_x.start_power(Power::Brake).await.map_err(GrowError::hardware)?;
+        self.device_y.start_power(Power::Brake).await.map_err(GrowError::hardware)?;
         Ok(())
     }
 
-    async fn update_pos(&self) -> Result<(), Box<dyn Error>> {
+    async fn update_pos(&self) -> Result<(), GrowError> {
         self.device_x.device_mode(
             modes::TechnicLargeLinearMotorTechnicHub::SPEED,
             1,
             true,
-        ).await?;
+        ).await.map_err(GrowError::hardware)?;
         self.device_y.device_mode(
             modes::TechnicLargeLinearMotorTechnicHub::SPEED,
             1,
             true,
-        ).await?;
+        ).await.map_err(GrowError::hardware)?;
         // sleep(Duration::from_millis(100)).await;
         self.device_x.device_mode(
             modes::TechnicLargeLinearMotorTechnicHub::POS,
             1,
             true,
-        ).await?;
+        ).await.map_err(GrowError::hardware)?;
         self.device_y.device_mode(
             modes::TechnicLargeLinearMotorTechnicHub::POS,
             1,
             true,
-        ).await?;
+        ).await.map_err(GrowError::hardware)?;
 
         Ok(())
     }
-    async fn goto_x(&self, x: i32) -> Result<(), Box<dyn Error>> {
+    async fn goto_x(&self, x: i32) -> Result<(), GrowError> {
         self.device_x
             // .goto_absolute_position(x, 50, 20, EndState::Brake).await?;
-            .goto_absolute_position_soc(x, 40, 20, EndState::Brake, StartupInfo::ExecuteImmediately, CompletionInfo::CommandFeedback).await?;
+            .goto_absolute_position_soc(x, 40, 20, EndState::Brake, StartupInfo::ExecuteImmediately, CompletionInfo::CommandFeedback).await.map_err(GrowError::hardware)?;
         Ok(())
     }
-    async fn goto_y(&self, y: i32) -> Result<(), Box<dyn Error>> {
+    async fn goto_y(&self, y: i32) -> Result<(), GrowError> {
         self.device_y
             // .goto_absolute_position(y, 100, 20, EndState::Brake).await?;
-            .goto_absolute_position_soc(y, 100, 60, EndState::Brake, StartupInfo::ExecuteImmediately, CompletionInfo::CommandFeedback).await?;
+            .goto_absolute_position_soc(y, 100, 60, EndState::Brake, StartupInfo::ExecuteImmediately, CompletionInfo::CommandFeedback).await.map_err(GrowError::hardware)?;
         Ok(())
     }
-    async fn start_x(&self, speed: i8) -> Result<(), Box<dyn Error>> {
-        self.device_x.start_speed(-speed, 15).await?;
+    async fn start_x(&self, speed: i8) -> Result<(), GrowError> {
+        self.device_x.start_speed(-speed, 15).await.map_err(GrowError::hardware)?;
         Ok(())
     }
-    async fn stop_x(&self) -> Result<(), Box<dyn Error>> {
-        self.device_x.start_power(Power::Brake).await?;
+    async fn stop_x(&self) -> Result<(), GrowError> {
+        self.device_x.start_power(Power::Brake).await.map_err(GrowError::hardware)?;
         Ok(())
     }
-    async fn start_y(&self, speed: i8) -> Result<(), Box<dyn Error>> {
-        self.device_y.start_speed(speed, 60).await?;
+    async fn start_y(&self, speed: i8) -> Result<(), GrowError> {
+        self.device_y.start_speed(speed, 60).await.map_err(GrowError::hardware)?;
         Ok(())
     }
-    async fn stop_y(&self) -> Result<(), Box<dyn Error>> {
-        self.device_y.start_power(Power::Brake).await?;
+    async fn stop_y(&self) -> Result<(), GrowError> {
+        self.device_y.start_power(Power::Brake).await.map_err(GrowError::hardware)?;
         Ok(())
     }
-    fn position(&self) -> Result<(i32, i32, i32), Box<dyn Error>> {
+    fn position(&self) -> Result<(i32, i32, i32), GrowError> {
         Ok((*self.pos_x.read(), *self.pos_y.read(), 0))
     }
-    async fn calibrate(&self) -> Result<(i32, i32, i32), Box<dyn Error>> {
+    async fn calibrate(&self) -> Result<(i32, i32, i32), GrowError> {
         let cancel = CancellationToken::new();
         let _guard = cancel.clone().drop_guard();
         let device_x = self.device_x.clone();
@@ -462,7 +472,7 @@ impl zone::water::arm::Arm for BrickArm {
 
     /// Calibrate zero-point and range
     /// Needed if we want to use relative position settings, using absolute values for now
-    async fn calibrate_with_range(&self) -> Result<(), Box<dyn Error>> {
+    async fn calibrate_with_range(&self) -> Result<(), GrowError> {
         Ok(())
     }
 }
@@ -562,6 +572,8 @@ impl BrickArm {
                         )
                         .await;
                     }
+                    // No z axis on this arm
+                    ArmCmd::GotoZ { .. } => {}
                 }
             }
         }))
@@ -655,7 +667,7 @@ impl zone::auxiliary::AuxDevice for LpuHub {
     async fn init(
         &mut self,
         tx_status: tokio::sync::broadcast::Sender<(u8, DisplayStatus)>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         self.feedback_task = Some(
             self.hub_feedback(tx_status)
                 .await
@@ -663,7 +675,7 @@ impl zone::auxiliary::AuxDevice for LpuHub {
         );
         Ok(())
     }
-    fn read(&self) -> Result<String, Box<dyn Error>> {
+    fn read(&self) -> Result<String, GrowError> {
         Ok(String::from("Placeholder"))
     }
 }
@@ -840,7 +852,7 @@ impl zone::air::Thermometer for LpuTemp {
     async fn init(
         &mut self,
         tx_temp: tokio::sync::broadcast::Sender<(u8, Option<f64>)>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         self.feedback_task = Some(
             self.temp_feedback(tx_temp).await
                 .expect("Error initializing feedback task"),
@@ -848,12 +860,13 @@ impl zone::air::Thermometer for LpuTemp {
 
         Ok(())
     }
-    fn read(&self) -> Result<f64, Box<dyn Error + '_>> {
+    fn read(&self) -> Result<f64, GrowError> {
         Ok((*self.temperature.read()).into())
     }
 }
 impl LpuTemp {
-    pub fn new(id: u8, hub: HubMutex) -> Self {
+    /// Temperature sensor of the device on hub `port`
+    pub fn new(id: u8, hub: HubMutex, port: u8) -> Self {
         let device: IoDevice;
         {
             // let lock = hub.lock().await;
@@ -861,7 +874,7 @@ impl LpuTemp {
                 hub.blocking_lock_owned()
             });
             device = lock
-                .io_from_port(LPU_TEMP_SENSOR_2_ADDR)
+                .io_from_port(port)
                 .expect("Error accessing LPU device");
         }
         Self {
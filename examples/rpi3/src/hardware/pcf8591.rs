@@ -15,8 +15,18 @@ use tokio_util::sync::CancellationToken;
 pub type AdcMutex = Arc<Mutex<PCF8591>>;
 use super::conf::*;
 use grow::zone;
+use grow::GrowError;
 use grow::zone::light::LampState;
 
+/// ADC input of a sensor and how often it is read, from the driver params
+#[derive(Clone, Copy)]
+pub struct AdcInput {
+    pub pin: Pin,
+    pub poll: Duration,
+    /// Smallest change sent as a reading
+    pub delta: f32,
+}
+
 // #[derive( Debug, )]
 pub struct Adc {
     mutex: AdcMutex,
@@ -47,7 +57,7 @@ impl zone::light::Lamp for Led {
     fn init(
         &mut self,
         rx_control: tokio::sync::broadcast::Receiver<(u8, bool)>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         let _ = self.set_state(LampState::Off);
         self.control_task = Some(
             self.lamp_control(rx_control)
@@ -58,22 +68,22 @@ impl zone::light::Lamp for Led {
     fn set_state(
         &self,
         state: zone::light::LampState,
-    ) -> Result<(), Box<dyn Error + '_>> {
+    ) -> Result<(), GrowError> {
         println!("Set lampstate called: {:?}", &state);
         match state {
             zone::light::LampState::On => {
-                let mut lock = self.adc.lock()?;
+                let mut lock = self.adc.lock().map_err(GrowError::hardware_msg)?;
                 *self.state.write() = LampState::On;
-                Ok(lock.analog_write_byte(255)?)
+                lock.analog_write_byte(255).map_err(GrowError::hardware)
             }
             zone::light::LampState::Off => {
-                let mut lock = self.adc.lock()?;
+                let mut lock = self.adc.lock().map_err(GrowError::hardware_msg)?;
                 *self.state.write() = LampState::Off;
-                Ok(lock.analog_write_byte(0)?)
+                lock.analog_write_byte(0).map_err(GrowError::hardware)
             }
         }
     }
-    fn state(&self) -> Result<LampState, Box<dyn Error>> {
+    fn state(&self) -> Result<LampState, GrowError> {
         Ok(*self.state.read())
     }
 }
@@ -122,6 +132,7 @@ impl Led {
 pub struct Thermistor {
     id: u8,
     adc: AdcMutex,
+    input: AdcInput,
     feedback_task: Option<JoinHandle<()>>,
 }
 #[async_trait]
@@ -132,7 +143,7 @@ impl zone::air::Thermometer for Thermistor {
     async fn init(
         &mut self,
         tx_temp: tokio::sync::broadcast::Sender<(u8, Option<f64>)>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         self.feedback_task = Some(
             self.temp_feedback(tx_temp)
                 .expect("Error initializing feedback task"),
@@ -140,12 +151,11 @@ impl zone::air::Thermometer for Thermistor {
 
         Ok(())
     }
-    fn read(&self) -> Result<f64, Box<dyn Error + '_>> {
-        let pin = TEMP_SENSOR[self.id as usize - 1];
+    fn read(&self) -> Result<f64, GrowError> {
         let reading: f64;
         {
-            let mut lock = self.adc.lock()?;
-            reading = celcius_from_byte(lock.analog_read_byte(pin)?.into());
+            let mut lock = self.adc.lock().map_err(GrowError::hardware_msg)?;
+            reading = celcius_from_byte(lock.analog_read_byte(self.input.pin).map_err(GrowError::hardware)?.into());
         }
 
         Ok(reading)
@@ -157,10 +167,11 @@ impl Debug for Thermistor {
     }
 }
 impl Thermistor {
-    pub fn new(id: u8, adc: AdcMutex) -> Self {
+    pub fn new(id: u8, adc: AdcMutex, input: AdcInput) -> Self {
         Self {
             id,
             adc,
+            input,
             feedback_task: None,
         }
     }
@@ -171,7 +182,7 @@ impl Thermistor {
     ) -> Result<JoinHandle<()>, Box<dyn Error>> {
         let id = self.id;
         let adc = self.adc.clone();
-        let pin = TEMP_SENSOR[self.id as usize - 1];
+        let AdcInput { pin, poll, delta } = self.input;
         Ok(tokio::spawn(async move {
             // let mut previous: Option<f32> = None;
             let mut previous: f64 = f64::MAX;
@@ -186,7 +197,7 @@ impl Thermistor {
                     Ok(raw_reading) => {
                         reading = celcius_from_byte(raw_reading.into());
                         // println!("Temp {:?}   reading {:?}   previous {:?}", &id, &reading, &previous);
-                        if reading != previous {
+                        if (reading != previous) & ((reading - previous).abs() >= f64::from(delta)) {
                             let _ = tx.send((id, Some(reading)));
                             previous = reading;
                         }
@@ -195,7 +206,7 @@ impl Thermistor {
                         let _ = tx.send((id, None));
                     }
                 }
-                tokio::time::sleep(Duration::from_secs(DELAY_TEMP_1)).await;
+                tokio::time::sleep(poll).await;
             }
         }))
     }
@@ -205,6 +216,7 @@ impl Thermistor {
 pub struct Photoresistor {
     id: u8,
     adc: AdcMutex,
+    input: AdcInput,
     feedback_task: Option<JoinHandle<()>>,
 }
 impl zone::light::Lightmeter for Photoresistor {
@@ -214,7 +226,7 @@ impl zone::light::Lightmeter for Photoresistor {
     fn init(
         &mut self,
         tx_light: tokio::sync::broadcast::Sender<(u8, Option<f32>)>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         self.feedback_task = Some(
             self.light_feedback(tx_light)
                 .expect("Error initializing feedback task"),
@@ -222,22 +234,21 @@ impl zone::light::Lightmeter for Photoresistor {
 
         Ok(())
     }
-    fn read(&self) -> Result<f32, Box<dyn Error + '_>> {
-        let _pin: Pin;
-        let pin = LIGHT_SENSOR[self.id as usize - 1];
+    fn read(&self) -> Result<f32, GrowError> {
         let reading: f32;
         {
-            let mut lock = self.adc.lock()?;
-            reading = light_from_byte(lock.analog_read_byte(pin)?);
+            let mut lock = self.adc.lock().map_err(GrowError::hardware_msg)?;
+            reading = light_from_byte(lock.analog_read_byte(self.input.pin).map_err(GrowError::hardware)?);
         }
         Ok(reading)
     }
 }
 impl Photoresistor {
-    pub fn new(id: u8, adc: AdcMutex) -> Self {
+    pub fn new(id: u8, adc: AdcMutex, input: AdcInput) -> Self {
         Self {
             id,
             adc,
+            input,
             feedback_task: None,
         }
     }
@@ -247,7 +258,7 @@ impl Photoresistor {
     ) -> Result<JoinHandle<()>, Box<dyn Error>> {
         let id = self.id;
         let adc = self.adc.clone();
-        let pin = LIGHT_SENSOR[self.id as usize - 1];
+        let AdcInput { pin, poll, delta } = self.input;
         Ok(tokio::spawn(async move {
             let mut previous = f32::MAX;
             loop {
@@ -261,7 +272,7 @@ impl Photoresistor {
                     Ok(raw_reading) => {
                         reading = light_from_byte(raw_reading.into());
                         // println!("Light {:?}   reading {:?}   previous {:?}", &id, &reading, &previous);
-                        if (reading - previous).abs() >= LIGHT_1_DELTA {
+                        if (reading - previous).abs() >= delta {
                             // if reading != previous {
                             let _ = tx.send((id, Some(reading)));
                             previous = reading;
@@ -272,7 +283,7 @@ impl Photoresistor {
                     }
                 }
 
-                tokio::time::sleep(Duration::from_secs(DELAY_LIGHT_1)).await;
+                tokio::time::sleep(poll).await;
             }
         }))
     }
@@ -282,18 +293,18 @@ impl Photoresistor {
 pub struct CapacitiveMoistureSensor {
     id: u8,
     adc: AdcMutex,
+    input: AdcInput,
     feedback_task: Option<JoinHandle<()>>,
 }
 impl zone::water::MoistureSensor for CapacitiveMoistureSensor {
     fn id(&self) -> u8 {
         self.id
     }
-    fn read(&self) -> Result<f32, Box<dyn Error + '_>> {
-        let pin = MOIST_SENSOR[self.id as usize - 1];
+    fn read(&self) -> Result<f32, GrowError> {
         let reading: f32;
         {
-            let mut lock = self.adc.lock()?;
-            reading = moist_from_byte(lock.analog_read_byte(pin)?);
+            let mut lock = self.adc.lock().map_err(GrowError::hardware_msg)?;
+            reading = moist_from_byte(lock.analog_read_byte(self.input.pin).map_err(GrowError::hardware)?);
         }
 
         Ok(reading)
@@ -301,7 +312,7 @@ impl zone::water::MoistureSensor for CapacitiveMoistureSensor {
     fn init(
         &mut self,
         tx_moist: tokio::sync::broadcast::Sender<(u8, Option<f32>)>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         self.feedback_task = Some(
             self.moist_feedback(tx_moist)
                 .expect("Error initializing feedback task"),
@@ -311,10 +322,11 @@ impl zone::water::MoistureSensor for CapacitiveMoistureSensor {
     }
 }
 impl CapacitiveMoistureSensor {
-    pub fn new(id: u8, adc: AdcMutex) -> Self {
+    pub fn new(id: u8, adc: AdcMutex, input: AdcInput) -> Self {
         Self {
             id,
             adc,
+            input,
             feedback_task: None,
         }
     }
@@ -324,11 +336,11 @@ impl CapacitiveMoistureSensor {
     ) -> Result<JoinHandle<()>, Box<dyn Error>> {
         let id = self.id;
         let adc = self.adc.clone();
-        let pin = MOIST_SENSOR[self.id as usize - 1];
+        let AdcInput { pin, poll, delta } = self.input;
         Ok(tokio::spawn(async move {
             let mut previous = f32::MAX;
             loop {
-                tokio::time::sleep(Duration::from_secs(DELAY_MOIST_1)).await;
+                tokio::time::sleep(poll).await;
                 let reading: f32;
                 let read_result: Result<u8, LinuxI2CError>;
                 {
@@ -339,7 +351,7 @@ impl CapacitiveMoistureSensor {
                     Ok(raw_reading) => {
                         reading = moist_from_byte(raw_reading.into());
                         // println!("Moist {:?}   reading {:?}   previous {:?}", &id, &reading, &previous);
-                        if (reading - previous).abs() >= MOIST_1_AND_2_DELTA {
+                        if (reading - previous).abs() >= delta {
                             // if reading != previous {
                             let _ = tx.send((id, Some(reading)));
                             previous = reading;
@@ -1,4 +1,5 @@
 use grow::zone;
+use grow::GrowError;
 
 use core::time::Duration;
 use std::time::Instant;
@@ -30,34 +31,34 @@ pub struct PwmFan {
     rpm: Arc<RwLock<Option<f32>>>,
 }
 impl zone::air::Fan for PwmFan {
-    fn read(&mut self) -> Result<Option<f32>, Box<dyn Error + '_>> {
+    fn read(&mut self) -> Result<Option<f32>, GrowError> {
         // Ok(self.get_rpm())
         Ok(*self.rpm.read())
     }
-    fn to_high(&self) -> Result<(), Box<dyn Error + '_>> {
+    fn to_high(&self) -> Result<(), GrowError> {
         // println!("Fan set to high");
         let lock = self.pwm_channel.lock();
-        Ok(lock.set_duty_cycle(1.0)?)
+        lock.set_duty_cycle(1.0).map_err(GrowError::hardware)
     }
-    fn to_low(&self) -> Result<(), Box<dyn Error + '_>> {
+    fn to_low(&self) -> Result<(), GrowError> {
         // println!("Fan set to low");
         let lock = self.pwm_channel.lock();
-        Ok(lock.set_duty_cycle(0.5)?)
+        lock.set_duty_cycle(0.5).map_err(GrowError::hardware)
     }
     fn set_duty_cycle(
         &self,
         duty_cycle: f64,
-    ) -> Result<(), Box<dyn Error + '_>> {
+    ) -> Result<(), GrowError> {
         println!("Fan set to {:?}", &duty_cycle);
         let lock = self.pwm_channel.lock();
-        Ok(lock.set_duty_cycle(duty_cycle)?)
+        lock.set_duty_cycle(duty_cycle).map_err(GrowError::hardware)
     }
     // TODO: return result
     fn init(
         &mut self,
         tx_rpm: tokio::sync::broadcast::Sender<(u8, Option<f32>)>,
         rx_control: tokio::sync::broadcast::Receiver<FanSetting>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), GrowError> {
         self.feedback_task = Some(
             self.fan_feedback(tx_rpm)
                 .expect("Error initializing feedback task"),
@@ -0,0 +1,75 @@
+use core::fmt::Debug;
+use std::sync::Arc;
+use parking_lot::RwLock;
+use rppal::gpio::{Gpio, OutputPin};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use super::conf::REFILL_VALVE_PIN;
+use grow::zone;
+use grow::GrowError;
+
+/// Solenoid valve or helper pump on a relay, high is open
+pub struct GpioValve {
+    id: u8,
+    pin: Arc<RwLock<OutputPin>>,
+    control_task: Option<JoinHandle<()>>,
+}
+impl zone::water::tank::RefillValve for GpioValve {
+    fn id(&self) -> u8 {
+        self.id
+    }
+    fn init(
+        &mut self,
+        rx_refill: broadcast::Receiver<(u8, bool)>,
+    ) -> Result<(), GrowError> {
+        let _ = self.set_open(false);
+        self.control_task = Some(self.valve_control(rx_refill));
+        Ok(())
+    }
+    fn set_open(&self, open: bool) -> Result<(), GrowError> {
+        match open {
+            true => self.pin.write().set_high(),
+            false => self.pin.write().set_low(),
+        }
+        Ok(())
+    }
+    fn is_open(&self) -> Result<bool, GrowError> {
+        Ok(self.pin.read().is_set_high())
+    }
+}
+impl Debug for GpioValve {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "GpioValve: {}", self.id)
+    }
+}
+impl GpioValve {
+    pub fn new(id: u8) -> Self {
+        Self::with_pin(id, REFILL_VALVE_PIN)
+    }
+    pub fn with_pin(id: u8, pin: u8) -> Self {
+        let pin = Gpio::new()
+            .expect("New gpio error")
+            .get(pin)
+            .expect("Get pin error")
+            .into_output_low();
+        Self {
+            id,
+            pin: Arc::new(RwLock::new(pin)),
+            control_task: None,
+        }
+    }
+    fn valve_control(&self, mut rx: broadcast::Receiver<(u8, bool)>) -> JoinHandle<()> {
+        let pin = self.pin.clone();
+        tokio::spawn(async move {
+            while let Ok((_id, open)) = rx.recv().await {
+                match open {
+                    true => pin.write().set_high(),
+                    false => pin.write().set_low(),
+                }
+            }
+            // Runner gone, leave the valve closed
+            pin.write().set_low();
+        })
+    }
+}
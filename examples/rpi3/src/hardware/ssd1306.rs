@@ -6,6 +6,7 @@ use core::error::Error;
 use async_trait::async_trait;
 use std::time::Duration;
 use time::OffsetDateTime;
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
 use tokio::time::interval;
 use tokio_util::sync::CancellationToken;
@@ -29,6 +30,7 @@ use grow::ops::display::DisplayStatus;
 use grow::ops::display::Indicator;
 use grow::ops::SysLogTx;
 use grow::ops::io::TextDisplay;
+use grow::ops::menu::MenuView;
 
 use grow::zone::ZoneDisplay;
 use grow::zone::ZoneKind;
@@ -36,6 +38,7 @@ use grow::zone::ZoneStatusRx;
 
 pub struct Oled {
     cancel: CancellationToken,
+    menu: watch::Sender<Option<MenuView>>,
 }
 #[async_trait]
 impl TextDisplay for Oled {
@@ -44,7 +47,7 @@ impl TextDisplay for Oled {
         from_zones: ZoneStatusRx,
         to_syslog: SysLogTx,
     ) -> Result<JoinHandle<()>, Box<dyn Error>> {
-        self.display_control(from_zones, self.cancel.clone(), to_syslog)
+        self.display_control(from_zones, self.menu.subscribe(), self.cancel.clone(), to_syslog)
     }
     fn set(
         &mut self,
@@ -52,10 +55,14 @@ impl TextDisplay for Oled {
     ) -> Result<(), Box<dyn Error>> {
         Ok(())
     }
+    fn render(&self, view: Option<MenuView>) -> Result<(), Box<dyn Error>> {
+        self.menu.send(view)?;
+        Ok(())
+    }
 }
 impl Oled {
     pub fn new(cancel: CancellationToken) -> Self {
-        Self { cancel }
+        Self { cancel, menu: watch::channel(None).0 }
     }
 
     fn get_display(&self) -> OledDisplay {
@@ -79,6 +86,7 @@ impl Oled {
     fn display_control(
         &self,
         mut from_zones: ZoneStatusRx,
+        mut from_menu: watch::Receiver<Option<MenuView>>,
         cancel: CancellationToken,
         _to_syslog: SysLogTx,
     ) -> Result<JoinHandle<()>, Box<dyn Error>> {
@@ -110,8 +118,28 @@ impl Oled {
                         println!("Oled disabled");
                         break;
                     }
+                    Ok(()) = from_menu.changed() => {
+                        let view = from_menu.borrow_and_update().clone();
+                        display.clear_buffer();
+                        match view {
+                            Some(view) => {
+                                Text::new(&view.title, Point::new(0,14), style_heading).draw(&mut display).unwrap();
+                                for (row, line) in view.window(3).iter().enumerate() {
+                                    Text::new(line, Point::new(0, 30 + 14 * row as i32), style_msg).draw(&mut display).unwrap();
+                                }
+                            }
+                            None => {
+                                // Back to zone pages on next tick
+                                interval.reset();
+                            }
+                        }
+                        display.flush().unwrap();
+                    }
                     _ = interval.tick()  => {
                         // println!("=== Display: interval.tick() === ");
+                        if from_menu.borrow().is_some() {
+                            continue;
+                        }
                         let mut pages: Vec<( String, String, String, String )> = pagemap.values().map(|x|x.clone()).collect();
                         // dbg!(&pages); dbg!(&next_page);
                         if pages.len() > 0 {
@@ -167,6 +195,10 @@ impl Oled {
                                 let text = Self::format_zonedisplay(id, info, "Pump");
                                 pagemap.insert(( ZoneKind::Pump, id), text.clone() );
                             }
+                            ZoneDisplay::House {id, info} => {
+                                let text = Self::format_zonedisplay(id, info, "House");
+                                pagemap.insert(( ZoneKind::House, id), text.clone() );
+                            }
                             // _ => {}
                         }
                     }
@@ -9,7 +9,6 @@ use grow::ops;
 use grow::ops::manager::Manager;
 
 use grow::House;
-use grow::zone::*;
 use grow::HouseMutex;
 use grow::ManagerMutex;
 use grow::ops::OpsChannelsTx;
@@ -49,78 +48,10 @@ pub async fn house_hardware_init(
         .expect("Error from lpu::init()"); //thread 'main' panicked at 'called `Result::unwrap()` on an `Err` value: BluetoothError(DeviceNotFound)', src/init.rs:19:64
     let adc_1 = hardware::pcf8591::Adc::new(cancel.clone());
 
-    for zone in house.zones() {
-        match zone {
-            Zone::Air {id, interface, ..} if id == &1 => {
-                interface.fan =
-                    Some(Box::new(hardware::pwmfan::PwmFan::new(*id, cancel.clone())));
-                interface.thermo = Some(Box::new(
-                    hardware::pcf8591::Thermistor::new(*id, adc_1.new_mutex()),
-                ));
-            }
-            Zone::Air {id, interface, ..} if id == &2 => {
-                interface.fan = None;
-                interface.thermo = Some(Box::new(
-                    hardware::lpu::LpuTemp::new(*id, lpu_hub.clone()),
-                ));
-            }
-            Zone::Aux {id, interface, ..} => {
-                interface.auxiliary_device =
-                    Some(Box::new(hardware::lpu::LpuHub::new(
-                        *id,
-                        lpu_hub.clone(),
-                        cancel.clone(),
-                    )));
-            }
-            Zone::Light {id, interface, ..} => {
-                interface.lightmeter =
-                    Some(Box::new(hardware::pcf8591::Photoresistor::new(
-                        *id,
-                        adc_1.new_mutex(),
-                    )));
-                interface.lamp = Some(Box::new(hardware::pcf8591::Led::new(
-                    *id,
-                    adc_1.new_mutex(),
-                )));
-            }
-            Zone::Water {id, interface, ..} => {
-                match id {
-                    1..=2 => {
-                        interface.moist = Some(Box::new(
-                            hardware::pcf8591::CapacitiveMoistureSensor::new(
-                                *id,
-                                adc_1.new_mutex(),
-                            ),
-                        ));        
-                    }
-                    _ => {
-                        interface.moist = Some(Box::new(
-                            hardware::dummy::DummyMoistureSensor::new(
-                                *id,
-                                hardware::conf::MOIST_DUMMY_VALUE
-                            ),
-                        ));
-                    }
-                }
-      
-            }
-            Zone::Tank {id, interface, ..} => {
-                interface.tank_sensor = Some(Box::new(
-                    hardware::lpu::VisionSensor::new(*id, lpu_hub.clone()),
-                ));
-            }
-            Zone::Pump {id, interface, ..} => {
-                interface.pump = Some(Box::new(
-                    hardware::lpu::BrickPump::new(*id, lpu_hub.clone()).await,
-                ));
-            }
-            Zone::Arm {id, interface, ..} => {
-                interface.arm = Some(Box::new(
-                    hardware::lpu::BrickArm::new(*id, lpu_hub.clone()).await,
-                ));
-            } 
-            _ => (),
-        }
+    *house.drivers_mut() =
+        hardware::drivers::registry(cancel.clone(), lpu_hub, adc_1.new_mutex());
+    for (kind, id, e) in house.bind_hardware().await {
+        eprintln!("Hardware binding error on {:?} zone {}: {}", kind, id, e);
     }
     // dbg!(&house);
   
@@ -9,13 +9,15 @@ use core::error::Error;
 use core::time::Duration;
 use std::sync::Arc;
 
-use tokio::time::sleep;
+use tokio::time::{sleep, timeout};
 use tokio::signal;
 use tokio::sync::mpsc;
 use tokio::sync::Mutex as TokioMutex;
 use tokio_util::sync::CancellationToken;
 pub type HouseMutex = Arc<TokioMutex<grow::House>>;
 
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(30);
+
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -44,9 +46,18 @@ async fn main() -> Result<(), Box<dyn Error>> {
         _ = shutdown_recv.recv() => {},
     }
 
-    // Cleanup
-    cancel_token.cancel();
+    // Cleanup, drivers stay up until the house is in safe state
     println!("Start shutdown procedure");
+    let report = match timeout(SHUTDOWN_DEADLINE, manager.lock()).await {
+        Ok(mut manager) => manager.shutdown(SHUTDOWN_DEADLINE).await,
+        // Manager held, e.g. by a remote control session, the house still goes to safe state
+        Err(_) => {
+            println!("Manager busy, shutting down the house only");
+            house.lock().await.shutdown(SHUTDOWN_DEADLINE).await
+        }
+    };
+    println!("{}", report);
+    cancel_token.cancel();
     // cmd_task.unwrap().abort();
     sleep(Duration::from_millis(1000)).await;
 
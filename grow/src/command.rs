@@ -0,0 +1,118 @@
+//! Typed commands and queries for a house, dispatched through House::execute
+use crate::ops::display::DisplayStatus;
+use crate::ops::profile::{StageInfo, STAGE_DISPLAY_ID};
+use crate::zone::light::LampState;
+use crate::zone::tank::TankLevel;
+use crate::zone::ZoneKind;
+use crate::{GrowError, House};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    ReadMoisture { id: u8 },
+    ReadLight { id: u8 },
+    ReadTemperature { id: u8 },
+    ReadTankLevel { id: u8 },
+    ReadFanSpeed { id: u8 },
+    SetLamp { id: u8, state: LampState },
+    SetFanDutyCycle { id: u8, duty_cycle: f64 },
+    PumpRun { id: u8 },
+    PumpRunFor { id: u8, secs: u16 },
+    PumpStop { id: u8 },
+    PumpSpeed { id: u8, speed: i8 },
+    /// Planned moves run by the arm runner, the single axis moves keep the other axes
+    ArmGoto { id: u8, x: i32, y: i32, z: i32 },
+    ArmGotoX { id: u8, x: i32 },
+    ArmGotoY { id: u8, y: i32 },
+    ArmUpdate { id: u8 },
+    ArmPosition { id: u8 },
+    ArmCalibrate { id: u8 },
+    TankRefill { id: u8, on: bool },
+    DisplayStatus { kind: ZoneKind, id: u8 },
+    /// Grow profile activated by hand, `None` follows the calendar
+    ActivateProfile { name: Option<String> },
+    Stage,
+}
+impl Command {
+    /// Zone the command is addressed to
+    pub fn target(&self) -> (ZoneKind, u8) {
+        match *self {
+            Command::ReadMoisture { id } => (ZoneKind::Water, id),
+            Command::ReadLight { id } | Command::SetLamp { id, .. } => (ZoneKind::Light, id),
+            Command::ReadTemperature { id }
+            | Command::ReadFanSpeed { id }
+            | Command::SetFanDutyCycle { id, .. } => (ZoneKind::Air, id),
+            Command::ReadTankLevel { id } | Command::TankRefill { id, .. } => (ZoneKind::Tank, id),
+            Command::PumpRun { id }
+            | Command::PumpRunFor { id, .. }
+            | Command::PumpStop { id }
+            | Command::PumpSpeed { id, .. } => (ZoneKind::Pump, id),
+            Command::ArmGoto { id, .. }
+            | Command::ArmGotoX { id, .. }
+            | Command::ArmGotoY { id, .. }
+            | Command::ArmUpdate { id }
+            | Command::ArmPosition { id }
+            | Command::ArmCalibrate { id } => (ZoneKind::Arm, id),
+            Command::DisplayStatus { kind, id } => (kind, id),
+            Command::ActivateProfile { .. } | Command::Stage => (ZoneKind::House, STAGE_DISPLAY_ID),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reply {
+    Done,
+    Moisture(f32),
+    Light(f32),
+    Temperature(f64),
+    TankLevel(TankLevel),
+    FanSpeed(Option<f32>),
+    Position((i32, i32, i32)),
+    Status(DisplayStatus),
+    Stage(Option<StageInfo>),
+}
+
+impl House {
+    /// Single entry point for commands and queries
+    pub async fn execute(&mut self, cmd: Command) -> Result<Reply, GrowError> {
+        let (kind, id) = cmd.target();
+        if (kind != ZoneKind::House) & !self.zones.contains(kind, id) {
+            return Err(GrowError::ZoneNotFound { kind, id });
+        }
+        match cmd {
+            Command::ReadMoisture { id } => self.read_moisture_value(id).map(Reply::Moisture),
+            Command::ReadLight { id } => self.read_light_value(id).map(Reply::Light),
+            Command::ReadTemperature { id } => {
+                self.read_temperature_value(id).map(Reply::Temperature)
+            }
+            Command::ReadTankLevel { id } => self.read_tank_level(id).map(Reply::TankLevel),
+            Command::ReadFanSpeed { id } => self.read_fan_speed(id).map(Reply::FanSpeed),
+            Command::SetLamp { id, state } => self.set_lamp_state(id, state).map(|_| Reply::Done),
+            Command::SetFanDutyCycle { id, duty_cycle } => {
+                self.set_fan_duty_cycle(id, duty_cycle).map(|_| Reply::Done)
+            }
+            Command::PumpRun { id } => self.pump_run(id).await.map(|_| Reply::Done),
+            Command::PumpRunFor { id, secs } => {
+                self.pump_run_for_secs(id, secs).await.map(|_| Reply::Done)
+            }
+            Command::PumpStop { id } => self.pump_stop(id).await.map(|_| Reply::Done),
+            Command::PumpSpeed { id, speed } => {
+                self.pump_set_speed(id, speed).await.map(|_| Reply::Done)
+            }
+            Command::ArmGoto { id, x, y, z } => {
+                self.arm_goto(id, x, y, z).await.map(|_| Reply::Done)
+            }
+            Command::ArmGotoX { id, x } => self.arm_goto_x(id, x).await.map(|_| Reply::Done),
+            Command::ArmGotoY { id, y } => self.arm_goto_y(id, y).await.map(|_| Reply::Done),
+            Command::ArmUpdate { id } => self.arm_update(id).await.map(|_| Reply::Done),
+            Command::ArmPosition { id } => self.arm_position(id).map(Reply::Position),
+            Command::ArmCalibrate { id } => self.arm_calibrate(id).await.map(Reply::Position),
+            Command::TankRefill { id, on } => self.tank_refill(id, on).map(|_| Reply::Done),
+            Command::DisplayStatus { kind, id } => self
+                .get_displaystatus(kind, id)
+                .map(Reply::Status)
+                .ok_or(GrowError::ZoneNotFound { kind, id }),
+            Command::ActivateProfile { name } => self.activate_profile(name).await.map(|_| Reply::Done),
+            Command::Stage => Ok(Reply::Stage(self.stage())),
+        }
+    }
+}
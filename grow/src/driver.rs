@@ -0,0 +1,237 @@
+//! Hardware driver registry, zone interfaces are wired from the bindings in the config
+use core::fmt::Debug;
+use core::future::Future;
+use core::pin::Pin;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::zone::{air, arm, auxiliary, light, pump, tank, water, Zone, ZoneKind};
+use crate::GrowError;
+
+/// Interface field a driver is attached to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Slot {
+    Fan,
+    Thermo,
+    Aux,
+    Lamp,
+    Lightmeter,
+    Moist,
+    TankSensor,
+    Refill,
+    Pump,
+    Arm,
+}
+impl Slot {
+    pub fn kind(&self) -> ZoneKind {
+        match self {
+            Slot::Fan | Slot::Thermo => ZoneKind::Air,
+            Slot::Aux => ZoneKind::Aux,
+            Slot::Lamp | Slot::Lightmeter => ZoneKind::Light,
+            Slot::Moist => ZoneKind::Water,
+            Slot::TankSensor | Slot::Refill => ZoneKind::Tank,
+            Slot::Pump => ZoneKind::Pump,
+            Slot::Arm => ZoneKind::Arm,
+        }
+    }
+}
+
+/// Parameters from the config, drivers use what applies and fall back to their defaults
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DriverParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bus: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<f32>,
+}
+impl DriverParams {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+    pub fn bus(&self) -> Result<&str, GrowError> {
+        self.bus.as_deref().ok_or_else(|| missing("bus"))
+    }
+    pub fn address(&self) -> Result<u16, GrowError> {
+        self.address.ok_or_else(|| missing("address"))
+    }
+    pub fn pin(&self) -> Result<u8, GrowError> {
+        self.pin.ok_or_else(|| missing("pin"))
+    }
+    /// Refuse params `driver` does not use, they would be silently ignored
+    pub fn supported(&self, driver: &str, names: &[&str]) -> Result<(), GrowError> {
+        let given = [
+            ("bus", self.bus.is_some()),
+            ("address", self.address.is_some()),
+            ("pin", self.pin.is_some()),
+            ("poll", self.poll.is_some()),
+            ("delta", self.delta.is_some()),
+        ];
+        match given.iter().find(|(name, set)| *set & !names.contains(name)) {
+            Some((name, _)) => Err(GrowError::Config(format!("Driver {} does not use parameter {}", driver, name))),
+            None => Ok(()),
+        }
+    }
+}
+fn missing(param: &str) -> GrowError {
+    GrowError::Config(format!("Driver parameter {} missing", param))
+}
+
+/// Driver for one interface of a zone, as declared in the config
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub slot: Slot,
+    pub driver: String,
+    #[serde(default, skip_serializing_if = "DriverParams::is_empty")]
+    pub params: DriverParams,
+}
+
+/// Constructed driver, ready to attach to a zone
+pub enum Device {
+    Fan(Box<dyn air::Fan>),
+    Thermo(Box<dyn air::Thermometer>),
+    Aux(Box<dyn auxiliary::AuxDevice>),
+    Lamp(Box<dyn light::Lamp>),
+    Lightmeter(Box<dyn light::Lightmeter>),
+    Moist(Box<dyn water::MoistureSensor>),
+    TankSensor(Box<dyn tank::TankSensor>),
+    Refill(Box<dyn tank::RefillValve>),
+    Pump(Box<dyn pump::Pump>),
+    Arm(Box<dyn arm::Arm>),
+}
+impl Device {
+    pub fn slot(&self) -> Slot {
+        match self {
+            Device::Fan(_) => Slot::Fan,
+            Device::Thermo(_) => Slot::Thermo,
+            Device::Aux(_) => Slot::Aux,
+            Device::Lamp(_) => Slot::Lamp,
+            Device::Lightmeter(_) => Slot::Lightmeter,
+            Device::Moist(_) => Slot::Moist,
+            Device::TankSensor(_) => Slot::TankSensor,
+            Device::Refill(_) => Slot::Refill,
+            Device::Pump(_) => Slot::Pump,
+            Device::Arm(_) => Slot::Arm,
+        }
+    }
+
+    /// Put the device in the interface of the zone, replacing what was there
+    pub fn attach(self, zone: &mut Zone) -> Result<(), GrowError> {
+        match (self, zone) {
+            (Device::Fan(d), Zone::Air { interface, .. }) => interface.fan = Some(d),
+            (Device::Thermo(d), Zone::Air { interface, .. }) => interface.thermo = Some(d),
+            (Device::Aux(d), Zone::Aux { interface, .. }) => interface.auxiliary_device = Some(d),
+            (Device::Lamp(d), Zone::Light { interface, .. }) => interface.lamp = Some(d),
+            (Device::Lightmeter(d), Zone::Light { interface, .. }) => interface.lightmeter = Some(d),
+            (Device::Moist(d), Zone::Water { interface, .. }) => interface.moist = Some(d),
+            (Device::TankSensor(d), Zone::Tank { interface, .. }) => interface.tank_sensor = Some(d),
+            (Device::Refill(d), Zone::Tank { interface, .. }) => interface.refill = Some(d),
+            (Device::Pump(d), Zone::Pump { interface, .. }) => interface.pump = Some(Arc::from(d)),
+            (Device::Arm(d), Zone::Arm { interface, .. }) => interface.arm = Some(Arc::from(d)),
+            (device, zone) => {
+                let (kind, id) = zone.key();
+                return Err(GrowError::Config(format!(
+                    "{:?} driver does not fit {:?} zone {}",
+                    device.slot(),
+                    kind,
+                    id
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+impl Debug for Device {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Device {{{:?}}}", self.slot())
+    }
+}
+
+pub type DriverFuture = Pin<Box<dyn Future<Output = Result<Device, GrowError>> + Send>>;
+type Constructor = Box<dyn Fn(u8, DriverParams) -> DriverFuture + Send + Sync>;
+
+/// Drivers by name, constructors get the zone id and the params of the binding
+#[derive(Default)]
+pub struct DriverRegistry {
+    drivers: HashMap<String, Constructor>,
+}
+impl DriverRegistry {
+    pub fn register<F, Fut>(&mut self, name: &str, constructor: F)
+    where
+        F: Fn(u8, DriverParams) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Device, GrowError>> + Send + 'static,
+    {
+        self.drivers.insert(
+            name.to_string(),
+            Box::new(move |id, params| Box::pin(constructor(id, params))),
+        );
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.drivers.contains_key(name)
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.drivers.keys().map(String::as_str)
+    }
+
+    /// Construct the driver of a binding for zone `id`
+    pub async fn build(&self, id: u8, binding: &Binding) -> Result<Device, GrowError> {
+        let constructor = self
+            .drivers
+            .get(&binding.driver)
+            .ok_or_else(|| GrowError::Config(format!("Unknown driver {}", binding.driver)))?;
+        let device = constructor(id, binding.params.clone()).await?;
+        if device.slot() != binding.slot {
+            return Err(GrowError::Config(format!(
+                "Driver {} provides {:?}, bound as {:?}",
+                binding.driver,
+                device.slot(),
+                binding.slot
+            )));
+        }
+        Ok(device)
+    }
+
+    /// Build and attach all bindings of a zone
+    pub async fn bind(&self, zone: &mut Zone, bindings: &[Binding]) -> Result<(), GrowError> {
+        let (kind, id) = zone.key();
+        for binding in bindings {
+            if binding.slot.kind() != kind {
+                return Err(GrowError::Config(format!(
+                    "{:?} binding {} on {:?} zone {}",
+                    binding.slot, binding.driver, kind, id
+                )));
+            }
+            self.build(id, binding).await?.attach(zone)?;
+        }
+        Ok(())
+    }
+}
+impl Debug for DriverRegistry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut names: Vec<&str> = self.names().collect();
+        names.sort();
+        write!(f, "DriverRegistry {:?}", names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_params_rejected() {
+        let params = DriverParams { pin: Some(4), poll: Some(Duration::from_secs(5)), ..Default::default() };
+        assert!(params.supported("adc", &["pin", "poll", "delta"]).is_ok());
+        assert!(matches!(params.supported("valve", &["pin"]), Err(GrowError::Config(_))));
+        assert!(DriverParams::default().supported("fan", &[]).is_ok());
+    }
+}
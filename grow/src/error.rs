@@ -1,46 +1,96 @@
 use std::error::Error;
 use std::fmt;
 
+use crate::zone::ZoneKind;
+
+/// Errors from `House` and the zones, callers match on the kind instead of the message
 #[derive(Debug)]
-pub struct ZoneError {
-    details: String,
+pub enum GrowError {
+    ZoneNotFound { kind: ZoneKind, id: u8 },
+    /// A zone with the same kind and id is already in the house
+    ZoneExists { kind: ZoneKind, id: u8 },
+    /// Zone exists but has no hardware attached for the request
+    InterfaceMissing { kind: ZoneKind, id: u8 },
+    /// Error reported by a hardware driver
+    HardwareError(Box<dyn Error + Send + Sync>),
+    Timeout(String),
+    /// Refused for safety, e.g. a pump interlock or an arm in fault
+    Interlock { kind: ZoneKind, id: u8, reason: String },
+    /// Invalid setting or request, e.g. a target outside limits or an unknown position
+    Config(String),
+    /// Arm did not reach `target`, ended at `position`
+    PositionNotConfirmed { arm_id: u8, target: (i32, i32, i32), position: (i32, i32, i32) },
+    /// Zone runner is not running to take the request
+    RunnerStopped { kind: ZoneKind, id: u8 },
+    /// Manager task is not running to take the request, e.g. the alerter
+    TaskStopped(String),
+    /// Shared actuator leased by a holder of the same or higher priority
+    Leased { kind: ZoneKind, id: u8, holder: String },
+    /// Lease preempted or past its time limit while in use
+    LeaseLost { kind: ZoneKind, id: u8, reason: String },
 }
-impl ZoneError {
-    pub fn new(msg: &str) -> Self {
-        Self {
-            details: msg.to_string(),
-        }
+impl GrowError {
+    /// Error of a hardware driver, kept as the source
+    pub fn hardware(e: impl Error + Send + Sync + 'static) -> Self {
+        GrowError::HardwareError(Box::new(e))
+    }
+    /// Hardware fault without a source error to keep, e.g. a stalled move or an error borrowing from its driver
+    pub fn hardware_msg(msg: impl fmt::Display) -> Self {
+        GrowError::HardwareError(msg.to_string().into())
     }
 }
-impl fmt::Display for ZoneError {
+impl fmt::Display for GrowError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+        match self {
+            GrowError::ZoneNotFound { kind, id } => write!(f, "{:?} zone {} not found", kind, id),
+            GrowError::ZoneExists { kind, id } => write!(f, "{:?} zone {} already exists", kind, id),
+            GrowError::InterfaceMissing { kind, id } => {
+                write!(f, "{:?} zone {} has no hardware interface", kind, id)
+            }
+            GrowError::HardwareError(e) => write!(f, "Hardware error: {}", e),
+            GrowError::Timeout(msg) => write!(f, "Timed out: {}", msg),
+            GrowError::Interlock { kind, id, reason } => write!(f, "{:?} {} refused: {}", kind, id, reason),
+            GrowError::Config(msg) => write!(f, "{}", msg),
+            GrowError::PositionNotConfirmed { arm_id, target, position } => write!(
+                f,
+                "Arm {} position not confirmed, at {:?} target {:?}",
+                arm_id, position, target
+            ),
+            GrowError::RunnerStopped { kind, id } => write!(f, "{:?} zone {} runner not running", kind, id),
+            GrowError::TaskStopped(task) => write!(f, "{} not running", task),
+            GrowError::Leased { kind, id, holder } => write!(f, "{:?} {} leased by {}", kind, id, holder),
+            GrowError::LeaseLost { kind, id, reason } => write!(f, "{:?} {} lease lost: {}", kind, id, reason),
+        }
     }
 }
-impl Error for ZoneError {
-    fn description(&self) -> &str {
-        &self.details
+impl Error for GrowError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GrowError::HardwareError(e) => Some(e.as_ref()),
+            _ => None,
+        }
     }
 }
-
-#[derive(Debug)]
-pub struct WateringError {
-    details: String,
-}
-impl WateringError {
-    pub fn new(msg: &str) -> Self {
-        Self {
-            details: msg.to_string(),
-        }
+impl From<std::io::Error> for GrowError {
+    fn from(e: std::io::Error) -> Self {
+        GrowError::Config(format!("Config file: {}", e))
     }
 }
-impl fmt::Display for WateringError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+impl From<serde_json::Error> for GrowError {
+    fn from(e: serde_json::Error) -> Self {
+        GrowError::Config(format!("Config format: {}", e))
     }
 }
-impl Error for WateringError {
-    fn description(&self) -> &str {
-        &self.details
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hardware_keeps_source() {
+        let e = GrowError::hardware(std::io::Error::new(std::io::ErrorKind::TimedOut, "bus stalled"));
+        let source = e.source().and_then(|s| s.downcast_ref::<std::io::Error>());
+        assert_eq!(source.map(|s| s.kind()), Some(std::io::ErrorKind::TimedOut));
+        assert_eq!(e.to_string(), "Hardware error: bus stalled");
     }
 }
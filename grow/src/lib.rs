@@ -3,9 +3,12 @@
 // #![feature(file_create_new)]
 
 extern crate alloc;
+use alloc::collections::BTreeMap;
 use core::error::Error;
 pub type BoxResult<T> = core::result::Result<T, Box<dyn Error>>;
+use core::time::Duration;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Mutex;
 // use std::sync::Mutex;
 pub use tokio::sync::broadcast;
@@ -14,18 +17,29 @@ pub use tokio::sync::mpsc;
 use std::fs::File;
 use std::io::Write;
 
+pub mod command;
+pub mod driver;
+pub mod shutdown;
+pub mod supervisor;
 mod error;
-pub use error::ZoneError;
+pub use error::GrowError;
 pub mod ops;
 pub mod zone;
-use ops::display::DisplayStatus;
+use ops::display::{DisplayStatus, Indicator};
 use ops::OpsChannelsTx;
-use ops::SysLog;
+use ops::{LogLevel, SysLog};
+use zone::air::FanSetting;
+use zone::lease::MANUAL_LEASE;
 use zone::light::LampState;
 use zone::tank::TankLevel;
 use zone::ZoneDisplay;
 use zone::*;
-use zone::{arm::ArmCmd, pump::PumpCmd, Zone};
+use zone::{arm::ArmCmd, arm::ArmHoming, arm::ArmMove, pump::PumpCmd, Zone};
+use driver::{Binding, DriverRegistry};
+use ops::profile::{Profile, ProfileBase, ProfileSettings, StageInfo, Stages, STAGE_DISPLAY_ID};
+use ops::rules::RuleEffects;
+use ops::state::{Overrides, RuntimeState, WaterState, STATE_FILE};
+use time::OffsetDateTime;
 pub type HouseMutex = Arc<Mutex<House>>;
 pub type ManagerMutex = Arc<Mutex<ops::manager::Manager>>;
 
@@ -33,11 +47,15 @@ pub const TIME_OFFSET: time::UtcOffset = time::macros::offset!(+1); // CET
 // pub const TIME_OFFSET: time::UtcOffset = time::macros::offset!(+2); // CEST
 
 macro_rules! save_match {
-    ($x:ident, $y:ident, [$( $variant:tt ),+] ) => {
+    ($x:ident, $y:ident, $b:expr, [$( $variant:tt ),+] ) => {
        match $x {
         $(
         Zone::$variant {id, settings, ..} => {
-                   $y.push(ZoneSave::$variant { id:*id, settings:*settings });
+                   $y.push(ZoneSave::$variant {
+                       id: *id,
+                       settings: settings.clone(),
+                       hardware: $b.get(&(ZoneKind::$variant, *id)).cloned().unwrap_or_default(),
+                   });
                }
         )+
        }
@@ -46,21 +64,42 @@ macro_rules! save_match {
 
 #[derive(Debug)]
 pub struct House {
-    zones: Vec<Zone>,
+    zones: ZoneRegistry,
+    drivers: DriverRegistry,
+    /// Hardware bindings from the config, by zone
+    bindings: BTreeMap<(ZoneKind, u8), Vec<Binding>>,
+    overrides: Overrides,
+    leases: Leases,
+    rule_effects: RuleEffects,
+    stages: Stages,
     ops_tx: OpsChannelsTx,
     zone_tx: ZoneChannelsTx,
 }
 impl House {
     pub fn new(zone_tx: ZoneChannelsTx, ops_tx: OpsChannelsTx) -> Self {
+        let zones = ZoneRegistry::default();
         Self {
-            zones: Vec::new(),
+            leases: Leases::new(zones.view(), zone_tx.zonestatus.clone()),
+            zones,
+            drivers: DriverRegistry::default(),
+            bindings: BTreeMap::new(),
+            overrides: Overrides::default(),
+            rule_effects: RuleEffects::default(),
+            stages: Stages::default(),
             zone_tx,
             ops_tx,
         }
     }
     pub fn new2(zones: Vec<Zone>, zone_tx: ZoneChannelsTx, ops_tx: OpsChannelsTx) -> Self {
+        let zones = ZoneRegistry::new(zones);
         Self {
+            leases: Leases::new(zones.view(), zone_tx.zonestatus.clone()),
             zones,
+            drivers: DriverRegistry::default(),
+            bindings: BTreeMap::new(),
+            overrides: Overrides::default(),
+            rule_effects: RuleEffects::default(),
+            stages: Stages::default(),
             zone_tx,
             ops_tx,
         }
@@ -69,200 +108,403 @@ impl House {
     // pub fn zones(&self) -> &Vec<Zone> {
     //     &self.zones
     // }
-    pub fn zones(&mut self) -> &mut Vec<Zone> {
-        &mut self.zones
+    pub fn zones(&self) -> &ZoneRegistry {
+        &self.zones
     }
-    pub fn zones_mut(&mut self) -> &mut Vec<Zone> {
+    pub fn zones_mut(&mut self) -> &mut ZoneRegistry {
         &mut self.zones
     }
 
+    /// Zone statuses to read without the House lock
+    pub fn view(&self) -> ZoneView {
+        self.zones.view()
+    }
+    /// Arm and pump leases, taken before using them outside the House lock
+    pub fn leases(&self) -> Leases {
+        self.leases.clone()
+    }
+    /// Actions of the active automation rules, set by the rules engine
+    pub fn rule_effects(&self) -> RuleEffects {
+        self.rule_effects.clone()
+    }
+
+    pub fn drivers_mut(&mut self) -> &mut DriverRegistry {
+        &mut self.drivers
+    }
+    pub fn bindings(&self, kind: ZoneKind, id: u8) -> &[Binding] {
+        self.bindings.get(&(kind, id)).map_or(&[], |b| b.as_slice())
+    }
+    /// Set the hardware of a zone, applied by `bind_hardware` or `add_zone`
+    pub fn set_bindings(&mut self, kind: ZoneKind, id: u8, bindings: Vec<Binding>) {
+        if bindings.is_empty() {
+            self.bindings.remove(&(kind, id));
+        } else {
+            self.bindings.insert((kind, id), bindings);
+        }
+    }
+    /// Construct the bound drivers from the registry and attach them to the zones, before `init`.
+    /// A failing binding is logged and skipped, the failures are returned once all are tried.
+    pub async fn bind_hardware(&mut self) -> Vec<(ZoneKind, u8, GrowError)> {
+        let mut failed = Vec::new();
+        for ((kind, id), bindings) in &self.bindings {
+            match self.zones.get_mut(*kind, *id) {
+                Some(zone) => {
+                    for binding in bindings {
+                        if let Err(e) = self.drivers.bind(zone, core::slice::from_ref(binding)).await {
+                            let _ = self
+                                .ops_tx
+                                .syslog
+                                .send(
+                                    SysLog::with_level(LogLevel::Error, format!("Driver {} not bound: {}", binding.driver, e))
+                                        .source(&shutdown::source(*kind, *id)),
+                                )
+                                .await;
+                            failed.push((*kind, *id, e));
+                        }
+                    }
+                }
+                None => {
+                    let _ = self
+                        .ops_tx
+                        .syslog
+                        .send(SysLog::with_level(
+                            LogLevel::Warning,
+                            format!("Hardware bound to missing {:?} zone {}", kind, id),
+                        ))
+                        .await;
+                }
+            }
+        }
+        failed
+    }
+
     pub async fn init(&mut self) -> () {
-        let zone_channels = self.zone_tx.clone();
-        let ops_channels = self.ops_tx.clone();
-        for zone in self.zones_mut() {
+        match RuntimeState::load(STATE_FILE) {
+            Ok(state) => self.restore_state(&state),
+            Err(e) => {
+                let _ = self
+                    .ops_tx
+                    .syslog
+                    .send(SysLog::new(format!("No runtime state restored: {}", e)))
+                    .await;
+            }
+        }
+        let (tanks, pumps) = self.level_links();
+        for zone in self.zones.iter_mut() {
+            init_zone(zone, &tanks, &pumps, &self.zone_tx, &self.ops_tx).await;
+        }
+        let _ = self
+            .ops_tx
+            .syslog
+            .send(SysLog::new(format!("House zones initiated")))
+            .await;
+    }
+    /// Runtime state for the state file, alerts are added by the manager
+    pub fn runtime_state(&self) -> RuntimeState {
+        let mut state = RuntimeState {
+            saved: Some(OffsetDateTime::now_utc().to_offset(TIME_OFFSET)),
+            overrides: self.overrides.clone(),
+            profile: self.stages.manual.clone(),
+            ..Default::default()
+        };
+        for zone in self.zones.iter() {
             match zone {
-                Zone::Air {
-                    settings,
-                    interface,
-                    runner,
-                    ..
-                } => {
-                    let mut have_fan = false;
-                    let channels = runner.fan_channels();
-                    let i = interface
-                        .fan
-                        .as_mut();
-                        if i.is_some() {
-                            let _ = i.unwrap().init(channels.0, channels.1);
-                            have_fan = true;
-                        } 
-                    let _ = interface
-                        .thermo
-                        .as_mut()
-                        .unwrap()
-                        .init(runner.thermo_feedback_sender()).await;
-                    runner.run(
-                        settings.clone(),
-                        zone_channels.clone(),
-                        ops_channels.clone(),
-                        have_fan,
-                    );
+                Zone::Water { id, status, .. } => {
+                    let status = status.read();
+                    state.water.insert(*id, WaterState {
+                        last_watering: status.last_watering,
+                        waterings_today: status.waterings_today,
+                    });
                 }
-                Zone::Aux {
-                    settings,
-                    interface,
-                    runner,
-                    ..
-                } => {
-                    let _ = interface
-                        .auxiliary_device
-                        .as_mut()
-                        .unwrap()
-                        .init(runner.auxiliary_feedback_sender())
-                        .await;
-                    runner.run(
-                        settings.clone(),
-                        zone_channels.clone(),
-                        ops_channels.clone(),
-                    );
+                Zone::Light { id, status, .. } => {
+                    if let Some(lamp_state) = status.read().lamp_state {
+                        state.lamps.insert(*id, lamp_state);
+                    }
                 }
-                Zone::Light {
-                    settings,
-                    interface,
-                    runner,
-                    ..
-                } => {
-                    let _ = interface
-                        .lightmeter
-                        .as_mut()
-                        .unwrap()
-                        .init(runner.lightmeter_feedback_sender());
-                    let _ = interface
-                        .lamp
-                        .as_mut()
-                        .unwrap()
-                        .init(runner.lamp_cmd_receiver());
-                    runner.run(
-                        settings.clone(),
-                        zone_channels.clone(),
-                        ops_channels.clone(),
-                    );
+                _ => {}
+            }
+        }
+        state
+    }
+    /// Put saved state into the zones and hardware, before the runners start.
+    /// Arm positions are not kept, the encoders start from zero after a restart and need homing.
+    fn restore_state(&mut self, state: &RuntimeState) {
+        self.overrides = state.overrides.clone();
+        self.stages.manual = state.profile.clone();
+        for zone in self.zones.iter() {
+            match zone {
+                Zone::Water { id, status, .. } => {
+                    if let Some(saved) = state.water.get(id) {
+                        let mut status = status.write();
+                        status.last_watering = saved.last_watering;
+                        status.waterings_today = saved.waterings_today;
+                    }
                 }
-                Zone::Water {
-                    settings,
-                    interface,
-                    runner,
-                    ..
-                } => {
-                    let _ = interface
-                        .moist
-                        .as_mut()
-                        .unwrap()
-                        .init(runner.moisture_feedback_sender());
-                    runner.run(
-                        settings.clone(),
-                        zone_channels.clone(),
-                        ops_channels.clone(),
-                    );
+                Zone::Light { id, status, interface, .. } => {
+                    let lamp_state = self.overrides.lamps.get(id).or(state.lamps.get(id));
+                    if let Some(lamp_state) = lamp_state {
+                        status.write().lamp_state = Some(*lamp_state);
+                        if let Some(lamp) = interface.lamp.as_ref() {
+                            let _ = lamp.set_state(*lamp_state);
+                        }
+                    }
                 }
-                Zone::Tank {
-                    settings,
-                    interface,
-                    runner,
-                    ..
-                } => {
-                    let _ = interface
-                        .tank_sensor
-                        .as_mut()
-                        .unwrap()
-                        .init(runner.tank_feedback_sender())
-                        .await;
-                    runner.run(
-                        settings.clone(),
-                        zone_channels.clone(),
-                        ops_channels.clone(),
-                    );
+                Zone::Air { id, interface, .. } => {
+                    if let (Some(duty_cycle), Some(fan)) = (self.overrides.fan_duty_cycles.get(id), interface.fan.as_ref()) {
+                        let _ = fan.set_duty_cycle(*duty_cycle);
+                    }
                 }
-                Zone::Pump {
-                    settings,
-                    interface,
-                    runner,
-                    ..
-                } => {
-                    let _ = interface
-                        .pump
-                        .as_mut()
-                        .unwrap()
-                        .init(runner.cmd_receiver(), runner.feedback_sender())
-                        .await;
-                    runner.run(settings.clone());
-                    // runner.run(settings.clone(), zone_channels.clone(), ops_channels.clone() );
+                _ => {}
+            }
+        }
+    }
+    /// Forget manual lamp and fan settings
+    pub fn clear_overrides(&mut self) {
+        self.overrides = Overrides::default();
+    }
+
+    /// Profiles from grow-profiles.js, the manual or calendar stage is applied at once, on error the previous profiles are kept
+    pub async fn set_profiles(&mut self, settings: ProfileSettings) -> Result<(), GrowError> {
+        if let Some(calendar) = &settings.calendar {
+            if let Some(stage) = calendar.stages.iter().find(|stage| settings.profile(&stage.profile).is_none()) {
+                return Err(GrowError::Config(format!("Calendar profile {} not found", stage.profile)));
+            }
+        }
+        let manual = self.stages.manual.clone().filter(|name| settings.profile(name).is_some());
+        let previous = (
+            core::mem::replace(&mut self.stages.settings, settings),
+            core::mem::replace(&mut self.stages.manual, manual),
+            // Profiles may have changed under the same name
+            self.stages.current.take(),
+        );
+        let result = self.update_stage().await;
+        if result.is_err() {
+            (self.stages.settings, self.stages.manual, self.stages.current) = previous;
+        }
+        result
+    }
+    /// Activate a profile by hand until cleared, `None` goes back to the calendar
+    pub async fn activate_profile(&mut self, name: Option<String>) -> Result<(), GrowError> {
+        if let Some(name) = &name {
+            if self.stages.settings.profile(name).is_none() {
+                return Err(GrowError::Config(format!("Profile {} not found", name)));
+            }
+        }
+        let previous = core::mem::replace(&mut self.stages.manual, name);
+        let result = self.update_stage().await;
+        if result.is_err() {
+            self.stages.manual = previous;
+        }
+        result
+    }
+    pub fn stage(&self) -> Option<StageInfo> {
+        self.stages.current.clone()
+    }
+    /// Apply the manual or calendar stage if it changed, the manager checks each minute
+    pub async fn update_stage(&mut self) -> Result<(), GrowError> {
+        let now = OffsetDateTime::now_utc().to_offset(TIME_OFFSET);
+        let wanted = self.stages.wanted(now);
+        let info = wanted.as_ref().map(|(_, info)| info.clone());
+        if info == self.stages.current {
+            return Ok(());
+        }
+        let name = info.as_ref().map(|info| info.profile.clone());
+        if name != self.stages.current.as_ref().map(|current| current.profile.clone()) {
+            self.apply_profile(wanted.as_ref().map(|(profile, _)| profile))?;
+            let _ = self
+                .ops_tx
+                .syslog
+                .send(SysLog::new(format!("Profile {} applied", name.as_deref().unwrap_or("none"))).source("profile"))
+                .await;
+        }
+        let ds = match &info {
+            Some(info) => info.display_status(),
+            None => DisplayStatus::new(Indicator::Blue, Some(String::from("No stage"))),
+        };
+        let _ = self.zone_tx.zonestatus.send(ZoneDisplay::House { id: STAGE_DISPLAY_ID, info: ds });
+        self.stages.current = info;
+        Ok(())
+    }
+    /// Undo the previous profile and put this one on top of the light, water and air settings,
+    /// sent to the runners. A profile naming a zone not in the house changes nothing.
+    fn apply_profile(&mut self, profile: Option<&Profile>) -> Result<(), GrowError> {
+        if let Some(profile) = profile {
+            let targets = profile
+                .light
+                .keys()
+                .map(|id| (ZoneKind::Light, *id))
+                .chain(profile.water.keys().map(|id| (ZoneKind::Water, *id)))
+                .chain(profile.air.keys().map(|id| (ZoneKind::Air, *id)));
+            for (kind, id) in targets {
+                if !self.zones.contains(kind, id) {
+                    return Err(GrowError::ZoneNotFound { kind, id });
                 }
-                Zone::Arm {
-                    settings,
-                    interface,
-                    runner,
-                    ..
-                } => {
-                    let _ = interface
-                        .arm
-                        .as_mut()
-                        .unwrap()
-                        .init(
-                            runner.pos_feedback_sender().0,
-                            runner.pos_feedback_sender().1,
-                            runner.pos_feedback_sender().2,
-                            runner.control_feedback_sender(),
-                            runner.cmd_receiver(),
-                        )
-                        .await;
-                    runner.run(settings.clone());
-                    // runner.run(settings.clone(), zone_channels.clone(), ops_channels.clone() );
-                } // _ => ()
             }
         }
+        let previous = self.stages.base.take();
+        let mut next = profile.map(|profile| ProfileBase { profile: profile.clone(), ..Default::default() });
+        for zone in self.zones.iter_mut() {
+            match zone {
+                Zone::Light { id, settings, runner, .. } => {
+                    let mut new = *settings;
+                    if let Some(previous) = &previous {
+                        previous.reset_light(*id, &mut new);
+                    }
+                    if let Some(next) = next.as_mut() {
+                        next.light.insert(*id, new);
+                        if let Some(overrides) = next.profile.light.get(id) {
+                            overrides.apply(&mut new);
+                        }
+                    }
+                    if new != *settings {
+                        *settings = new;
+                        let _ = runner.settings_sender().send(new);
+                    }
+                }
+                Zone::Water { id, settings, runner, .. } => {
+                    let mut new = settings.clone();
+                    if let Some(previous) = &previous {
+                        previous.reset_water(*id, &mut new);
+                    }
+                    if let Some(next) = next.as_mut() {
+                        next.water.insert(*id, new.clone());
+                        if let Some(overrides) = next.profile.water.get(id) {
+                            overrides.apply(&mut new);
+                        }
+                    }
+                    if new != *settings {
+                        *settings = new;
+                        let _ = runner.settings_sender().send(settings.clone());
+                    }
+                }
+                Zone::Air { id, settings, runner, .. } => {
+                    let mut new = *settings;
+                    if let Some(previous) = &previous {
+                        previous.reset_air(*id, &mut new);
+                    }
+                    if let Some(next) = next.as_mut() {
+                        next.air.insert(*id, new);
+                        if let Some(overrides) = next.profile.air.get(id) {
+                            overrides.apply(&mut new);
+                        }
+                    }
+                    if new != *settings {
+                        *settings = new;
+                        let _ = runner.settings_sender().send(new);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.stages.base = next;
+        Ok(())
+    }
+
+    /// Pumps watch the level of the tank feeding them, tanks forecast from the pumps drawing from them
+    fn level_links(&self) -> (Vec<TankLink>, Vec<PumpLink>) {
+        let tanks = self
+            .zones
+            .iter()
+            .filter_map(|zone| match zone {
+                Zone::Tank { id, status, .. } => Some((*id, status.clone())),
+                _ => None,
+            })
+            .collect();
+        let pumps = self
+            .zones
+            .iter()
+            .filter_map(|zone| match zone {
+                Zone::Pump { settings, status, .. } => settings.tank_id.map(|tid| (tid, status.clone(), settings.flow_rate)),
+                _ => None,
+            })
+            .collect();
+        (tanks, pumps)
+    }
+
+    /// Add a zone at runtime, bind its interface, start the runner and save the config.
+    /// Bindings set for the zone are attached on top of the interface it carries.
+    /// A tank added here does not see pumps already running, restart to link them.
+    pub async fn add_zone(&mut self, mut zone: Zone) -> Result<(), GrowError> {
+        let (kind, id) = zone.key();
+        if self.zones.contains(kind, id) {
+            return Err(GrowError::ZoneExists { kind, id });
+        }
+        if let Some(bindings) = self.bindings.get(&(kind, id)) {
+            self.drivers.bind(&mut zone, bindings).await?;
+        }
+        let (tanks, pumps) = self.level_links();
+        init_zone(&mut zone, &tanks, &pumps, &self.zone_tx, &self.ops_tx).await;
+        let display = zone.zone_display();
+        self.zones.push(zone);
+        self.zone_changed(display, "added").await
+    }
+    /// Remove a zone at runtime, stop its runner and save the config.
+    /// Pumps are stopped and refill valves closed, the returned zone still holds the interface.
+    pub async fn remove_zone(&mut self, kind: ZoneKind, id: u8) -> Result<Zone, GrowError> {
+        let zone = self.zones.remove(kind, id).ok_or(GrowError::ZoneNotFound { kind, id })?;
+        release_zone(&zone).await;
+        self.bindings.remove(&(kind, id));
+        let mut display = zone.zone_display();
+        display.set_info(DisplayStatus::new(Indicator::Blue, Some(String::from("Zone removed"))));
+        self.zone_changed(display, "removed").await?;
+        Ok(zone)
+    }
+    /// Swap a zone for one with the same kind and id, e.g. with new hardware, returns the old zone
+    /// If the new zone cannot be added the old one is restarted and the error returned
+    pub async fn replace_zone(&mut self, zone: Zone) -> Result<Zone, GrowError> {
+        let (kind, id) = zone.key();
+        let old = self.zones.remove(kind, id).ok_or(GrowError::ZoneNotFound { kind, id })?;
+        release_zone(&old).await;
+        if let Err(e) = self.add_zone(zone).await {
+            // Back to the old zone, also when the new one was added but could not be saved
+            if let Some(new) = self.zones.remove(kind, id) {
+                release_zone(&new).await;
+            }
+            let mut old = old;
+            let (tanks, pumps) = self.level_links();
+            run_zone(&mut old, &tanks, &pumps, &self.zone_tx, &self.ops_tx);
+            let _ = self.zone_tx.zonestatus.send(old.zone_display());
+            self.zones.push(old);
+            return Err(e);
+        }
+        Ok(old)
+    }
+    /// Refresh the indicator board, log and persist after a zone change
+    async fn zone_changed(&mut self, display: ZoneDisplay, action: &str) -> Result<(), GrowError> {
+        let (kind, id) = display.kind();
+        let _ = self.zone_tx.zonestatus.send(display);
         let _ = self
             .ops_tx
             .syslog
-            .send(SysLog::new(format!("House zones initiated")))
+            .send(SysLog::new(format!("{:?} zone {} {}", kind, id, action)).source(&format!("{}/{}", format!("{:?}", kind).to_lowercase(), id)))
             .await;
+        self.save_settings()
     }
     pub fn collect_display_status(&mut self) -> Vec<ZoneDisplay> {
-        let mut r: Vec<ZoneDisplay> = Vec::new();
-        for zone in self.zones() {
-            r.push(zone.zone_display());
-        }
-
-        r
+        self.zones.view().collect_display_status()
     }
-    pub fn load_settings(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn load_settings(&mut self) -> Result<(), GrowError> {
         let readdata = std::fs::read_to_string("grow-conf.js")?;
         println!("File data:{:?}", &readdata);
         let loaddata: Vec<ZoneSave> = serde_json::from_str(&readdata)?;
         println!("Deser:{:?}", &loaddata);
-        
-        
+
+
         Ok(())
     }
 
-    pub fn save_settings(&self) -> Result<(), Box<dyn Error>> {
+    pub fn save_settings(&self) -> Result<(), GrowError> {
         let mut savedata: Vec<ZoneSave> = Vec::new();
         for zone in &self.zones {
-            save_match!(zone, savedata, [Water, Air, Light, Aux, Tank, Pump, Arm]);
+            save_match!(zone, savedata, self.bindings, [Water, Air, Light, Aux, Tank, Pump, Arm]);
+        }
+        if let Some(base) = &self.stages.base {
+            base.restore(&mut savedata);
         }
         let writestring = serde_json::to_string_pretty(&savedata)?;
         let mut f = File::create("grow-conf.js")?;
         f.write_all(writestring.as_bytes())?;
 
-        let x = ops::xymon::XymonSettings {
-            port: 1984,
-            host: String::from( "192.168.1.81"),
-            client: String::from( "greenhouse"),
-        };
-        let writestring = serde_json::to_string_pretty(&x)?;
-        let mut f = File::create("grow-xymon.js")?;
-        f.write_all(writestring.as_bytes())?;
-
         Ok(())
     }
 
@@ -270,23 +512,10 @@ impl House {
         &mut self,
         zid: u8,
     ) -> Option<zone::water::Settings> {
-        let mut r: Option<zone::water::Settings> = None;
-        for zone in self.zones() {
-            match zone {
-                Zone::Water {
-                    id,
-                    settings,
-                    status: _,
-                    ..
-                } if id == &zid => {
-                    // let lock = status.read();
-                    r = Some(settings.clone());
-                }
-                _ => continue,
-            }
+        match self.zones.get(ZoneKind::Water, zid) {
+            Some(Zone::Water { settings, .. }) => Some(settings.clone()),
+            _ => None,
         }
-
-        r
     }
 
     pub fn get_displaystatus(
@@ -294,157 +523,243 @@ impl House {
         kind: ZoneKind,
         zid: u8,
     ) -> Option<DisplayStatus> {
-        let mut r: Option<DisplayStatus> = None;
-        for zone in self.zones() {
-            match zone {
-                Zone::Tank { id, status, .. }
-                    if (kind == ZoneKind::Tank) & (id == &zid) =>
-                {
-                    r = Some(status.read().disp.clone());
-                }
-                _ => continue,
-            }
+        self.zones.get(kind, zid).map(|zone| zone.display_status())
+    }
+
+    pub fn set_water_position(&mut self, zid: u8, pos: (i32, i32, i32)) -> () {
+        if let Some(Zone::Water { settings, runner, .. }) = self.zones.get_mut(ZoneKind::Water, zid) {
+            settings.position = zone::arm::Position {
+                arm_id: settings.position.arm_id,
+                x: pos.0,
+                y: pos.1,
+                z: pos.2,
+            };
+            let _ = runner.settings_sender().send(settings.clone());
         }
+    }
 
-        r
+    pub fn get_threshold(&mut self, zid: u8, threshold: Threshold) -> Option<f64> {
+        self.zones
+            .get(threshold.kind(), zid)
+            .and_then(|z| threshold.get(z))
+    }
+    /// Allowed values for a threshold, see `Threshold::bounds`
+    pub fn threshold_bounds(&mut self, zid: u8, threshold: Threshold) -> Option<(f64, f64)> {
+        self.zones
+            .get(threshold.kind(), zid)
+            .map(|z| threshold.bounds(z))
+    }
+    /// Set a threshold from the panel and save it to grow-conf.js
+    /// The value has to be within `Threshold::bounds`, out of range or out of order values are rejected
+    pub fn set_threshold(
+        &mut self,
+        zid: u8,
+        threshold: Threshold,
+        value: f64,
+    ) -> Result<(), GrowError> {
+        let zone = self.zones.find_mut(threshold.kind(), zid)?;
+        let (min, max) = threshold.bounds(zone);
+        if !value.is_finite() | (value < min) | (value > max) {
+            return Err(GrowError::Config(format!(
+                "Threshold {:?} {} outside {}..{}",
+                threshold, value, min, max
+            )));
+        }
+        if !threshold.set(zone, value) {
+            return Err(GrowError::Config(format!("Threshold {:?} not set", threshold)));
+        }
+        self.save_settings()
     }
 
-    pub fn set_water_position(&mut self, zid: u8, pos: (i32, i32, i32)) -> () {
-        for zone in self.zones_mut() {
-            match zone {
-                Zone::Water {
-                    id,
-                    settings,
-                    status: _,
-                    ..
-                } if id == &zid => {
-                    settings.position = zone::arm::Position {
-                        arm_id: settings.position.arm_id,
-                        x: pos.0,
-                        y: pos.1,
-                        z: pos.2,
-                    }
-                }
-                _ => continue,
+    /// Water zone route, from `route`, `positions` or `position` in that order
+    /// Pump times are scaled by active rules, see `RuleEffects::pump_time_factor`
+    pub fn water_route(&mut self, zid: u8) -> Result<Vec<zone::water::RouteStep>, GrowError> {
+        let ws = self
+            .get_water_settings(zid)
+            .ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Water, id: zid })?;
+        let arm_id = ws.position.arm_id;
+        let factor = self.rule_effects.pump_time_factor(zid);
+        // Several large factors multiplied can still overflow a Duration
+        let scale = |time: Duration| {
+            Duration::try_from_secs_f64(time.as_secs_f64() * factor)
+                .map_err(|_| GrowError::Config(format!("Pump time {:?} scaled by {} out of range", time, factor)))
+        };
+        let waypoints: Vec<zone::water::Waypoint> = match (ws.route.is_empty(), ws.positions.is_empty()) {
+            (false, _) => ws.route.clone(),
+            (true, false) => ws
+                .positions
+                .iter()
+                .map(|name| zone::water::Waypoint {
+                    at: zone::water::Target::Named(name.clone()),
+                    pump_time: None,
+                    pump_on_way: false,
+                })
+                .collect(),
+            (true, true) => {
+                return Ok(vec![zone::water::RouteStep {
+                    position: ws.position,
+                    pump_time: scale(ws.pump_time)?,
+                    pump_on_way: false,
+                }])
             }
+        };
+        let library = self
+            .arm_settings(arm_id)
+            .ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: arm_id })?;
+        let mut route = Vec::new();
+        for waypoint in waypoints {
+            let (x, y, z) = match &waypoint.at {
+                zone::water::Target::Named(name) => library.position(name).ok_or_else(|| {
+                    GrowError::Config(format!("Position {} not found on arm {}", name, arm_id))
+                })?,
+                zone::water::Target::At(point) => point.xyz(),
+            };
+            library.check(x, y, z)?;
+            route.push(zone::water::RouteStep {
+                position: zone::arm::Position { arm_id, x, y, z },
+                pump_time: scale(waypoint.pump_time.unwrap_or(ws.pump_time))?,
+                pump_on_way: waypoint.pump_on_way,
+            });
         }
+        Ok(route)
+    }
+    /// Water named positions in sequence, an empty list goes back to the single position
+    pub fn set_water_positions(&mut self, zid: u8, names: Vec<String>) -> Result<(), GrowError> {
+        let arm_id = self
+            .get_water_settings(zid)
+            .ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Water, id: zid })?
+            .position
+            .arm_id;
+        let library = self
+            .arm_settings(arm_id)
+            .ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: arm_id })?;
+        if let Some(name) = names.iter().find(|n| library.position(n).is_none()) {
+            return Err(GrowError::Config(format!("Position {} not found on arm {}", name, arm_id)));
+        }
+        if let Some(Zone::Water { settings, runner, .. }) = self.zones.get_mut(ZoneKind::Water, zid) {
+            settings.positions = names;
+            let _ = runner.settings_sender().send(settings.clone());
+        }
+        Ok(())
     }
 
     pub fn confirm_arm_position(
         &mut self,
         zid: u8,
         acceptable_delta: u32,
-    ) -> Result<(bool, (i32, i32, i32)), Box<dyn Error + '_>> {
+    ) -> Result<(bool, (i32, i32, i32)), GrowError> {
         if let Some(ws) = self.get_water_settings(zid) {
-            if let Ok(ap) = self.arm_position(ws.position.arm_id) {
-                let mut r = true;
-                let diff = (
-                    (ws.position.x - ap.0),
-                    (ws.position.y - ap.1),
-                    (ws.position.z - ap.2),
-                );
-                if (diff.0.abs() as u32 > acceptable_delta)
-                    | (diff.0.abs() as u32 > acceptable_delta)
-                    | (diff.0.abs() as u32 > acceptable_delta)
-                {
-                    r = false;
-                }
-                return Ok((r, diff));
-            }
+            let p = ws.position;
+            return self.confirm_arm_at(p.arm_id, (p.x, p.y, p.z), acceptable_delta);
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
+        return Err(GrowError::ZoneNotFound { kind: ZoneKind::Water, id: zid });
+    }
+    /// Check arm is within `acceptable_delta` of `target` on all axes, with the difference
+    pub fn confirm_arm_at(
+        &mut self,
+        arm_id: u8,
+        target: (i32, i32, i32),
+        acceptable_delta: u32,
+    ) -> Result<(bool, (i32, i32, i32)), GrowError> {
+        let ap = self.arm_position(arm_id)?;
+        let diff = (
+            (target.0 - ap.0),
+            (target.1 - ap.1),
+            (target.2 - ap.2),
+        );
+        let r = (diff.0.unsigned_abs() <= acceptable_delta)
+            & (diff.1.unsigned_abs() <= acceptable_delta)
+            & (diff.2.unsigned_abs() <= acceptable_delta);
+        Ok((r, diff))
     }
 
     /// Sensor commands
     pub fn read_moisture_value(
         &mut self,
         zid: u8,
-    ) -> Result<f32, Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Water {id, interface, ..} if id == &zid => {
-                    return Ok(interface
-                        .moist
-                        .as_ref()
-                        .expect("Interface not found")
-                        .read()?)
-                }
-                _ => continue,
-            }
+    ) -> Result<f32, GrowError> {
+        match self.zones.get(ZoneKind::Water, zid) {
+            Some(Zone::Water { interface, .. }) => interface
+                .moist
+                .as_ref()
+                .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Water, id: zid })?
+                .read(),
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Water, id: zid }),
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
     }
     pub fn read_light_value(
         &mut self,
         zid: u8,
-    ) -> Result<f32, Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Light {id, interface, ..} if id == &zid => {
-                    return Ok(interface
-                        .lightmeter
-                        .as_ref()
-                        .expect("Interface not found")
-                        .read()?)
-                }
-                _ => continue,
-            }
+    ) -> Result<f32, GrowError> {
+        match self.zones.get(ZoneKind::Light, zid) {
+            Some(Zone::Light { interface, .. }) => interface
+                .lightmeter
+                .as_ref()
+                .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Light, id: zid })?
+                .read(),
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Light, id: zid }),
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
     }
     pub fn read_temperature_value(
         &mut self,
         zid: u8,
-    ) -> Result<f64, Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Air { id, interface, .. } if id == &zid => {
-                    return Ok(interface
-                        .thermo
-                        .as_ref()
-                        .expect("Interface not found")
-                        .read()?)
+    ) -> Result<f64, GrowError> {
+        match self.zones.get(ZoneKind::Air, zid) {
+            Some(Zone::Air { interface, .. }) => interface
+                .thermo
+                .as_ref()
+                .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Air, id: zid })?
+                .read(),
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Air, id: zid }),
+        }
+    }
+    pub fn tank_status(&mut self, zid: u8) -> Option<zone::tank::Status> {
+        match self.zones.get(ZoneKind::Tank, zid) {
+            Some(Zone::Tank { status, .. }) => Some(status.read().clone()),
+            _ => None,
+        }
+    }
+    /// Start or stop refilling the tank, starting clears a refill fault
+    pub fn tank_refill(&mut self, zid: u8, on: bool) -> Result<(), GrowError> {
+        match self.zones.get(ZoneKind::Tank, zid) {
+            Some(Zone::Tank { interface, runner, .. }) => {
+                if interface.refill.is_none() {
+                    return Err(GrowError::InterfaceMissing { kind: ZoneKind::Tank, id: zid });
                 }
-                _ => continue,
+                runner
+                    .refill_request_sender()
+                    .send(on)
+                    .map(|_| ())
+                    .map_err(|_| GrowError::RunnerStopped { kind: ZoneKind::Tank, id: zid })
             }
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Tank, id: zid }),
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
     }
     pub fn read_tank_level(
         &mut self,
         zid: u8,
-    ) -> Result<TankLevel, Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Tank { id, interface, .. } if id == &zid => {
-                    return Ok(interface
-                        .tank_sensor
-                        .as_ref()
-                        .expect("Interface not found")
-                        .read()?)
-                }
-                _ => continue,
-            }
+    ) -> Result<TankLevel, GrowError> {
+        match self.zones.get(ZoneKind::Tank, zid) {
+            Some(Zone::Tank { interface, .. }) => interface
+                .tank_sensor
+                .as_ref()
+                .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Tank, id: zid })?
+                .read(),
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Tank, id: zid }),
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
     }
     pub fn read_fan_speed(
         &mut self,
         zid: u8,
-    ) -> Result<Option<f32>, Box<dyn Error + '_>> {
-        for z in self.zones_mut() {
-            match z {
-                Zone::Air { id, interface, .. } if id == &zid => {
-                    return Ok(interface
-                        .fan
-                        .as_mut()
-                        .expect("Interface not found")
-                        .read()?)
-                }
-                _ => continue,
-            }
+    ) -> Result<Option<f32>, GrowError> {
+        match self.zones.get_mut(ZoneKind::Air, zid) {
+            Some(Zone::Air { interface, .. }) => interface
+                .fan
+                .as_mut()
+                .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Air, id: zid })?
+                .read(),
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Air, id: zid }),
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
     }
 
     /// General action commands
@@ -452,251 +767,530 @@ impl House {
         &mut self,
         zid: u8,
         state: LampState,
-    ) -> Result<(), Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Light {
-                    id,
-                    interface,
-                    status,
-                    ..
-                } if id == &zid => {
-                    status.write().lamp_state = Some(state);
-                    return Ok(interface
-                        .lamp
-                        .as_ref()
-                        .expect("Interface not found")
-                        .set_state(state)?);
-                }
-                _ => continue,
+    ) -> Result<(), GrowError> {
+        match self.zones.get(ZoneKind::Light, zid) {
+            Some(Zone::Light { interface, status, .. }) => {
+                let lamp = interface
+                    .lamp
+                    .as_ref()
+                    .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Light, id: zid })?;
+                status.write().lamp_state = Some(state);
+                lamp.set_state(state)?;
+                self.overrides.lamps.insert(zid, state);
+                Ok(())
             }
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Light, id: zid }),
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
     }
     pub fn set_fan_duty_cycle(
         &mut self,
         zid: u8,
         duty_cycle: f64,
-    ) -> Result<(), Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Air { id, interface, .. } if id == &zid => {
-                    return Ok(interface
-                        .fan
-                        .as_ref()
-                        .expect("Interface not found")
-                        .set_duty_cycle(duty_cycle)?)
+    ) -> Result<(), GrowError> {
+        match self.zones.get(ZoneKind::Air, zid) {
+            Some(Zone::Air { interface, .. }) => interface
+                .fan
+                .as_ref()
+                .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Air, id: zid })?
+                .set_duty_cycle(duty_cycle)?,
+            _ => return Err(GrowError::ZoneNotFound { kind: ZoneKind::Air, id: zid }),
+        }
+        self.overrides.fan_duty_cycles.insert(zid, duty_cycle);
+        Ok(())
+    }
+    /// Hold the fan mode over the runner's temperature control, `None` releases it
+    pub fn force_fan(&mut self, zid: u8, setting: Option<FanSetting>) -> Result<(), GrowError> {
+        match self.zones.get(ZoneKind::Air, zid) {
+            Some(Zone::Air { interface, status, runner, .. }) => {
+                if interface.fan.is_none() {
+                    return Err(GrowError::InterfaceMissing { kind: ZoneKind::Air, id: zid });
                 }
-                _ => continue,
+                // Kept in the status for a restarted runner
+                status.write().fan_forced = setting;
+                let _ = runner.fan_force_sender().send(setting);
+                Ok(())
             }
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Air, id: zid }),
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
     }
 
     /// Pump commands
-    pub async fn pump_run(&mut self, zid: u8) -> Result<(), Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Pump { id, interface, .. } if id == &zid => {
-                    return interface
-                        .pump
-                        .as_ref()
-                        .expect("Interface not found")
-                        .run().await
-                }
-                _ => continue,
+    fn tank_level(&self, tid: u8) -> Option<TankLevel> {
+        match self.zones.get(ZoneKind::Tank, tid) {
+            Some(Zone::Tank { status, .. }) => status.read().level,
+            _ => None,
+        }
+    }
+    /// Pump for use after releasing the House lock, e.g. for a run lasting a watering cycle
+    pub fn pump_handle(&self, zid: u8) -> Result<zone::pump::PumpHandle, GrowError> {
+        match self.zones.get(ZoneKind::Pump, zid) {
+            Some(Zone::Pump { settings, status, interface, .. }) => {
+                let pump = interface
+                    .pump
+                    .clone()
+                    .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Pump, id: zid })?;
+                let tank = settings.tank_id.and_then(|tid| self.zones.view().tank_status(tid));
+                Ok(zone::pump::PumpHandle::new(zid, *settings, status.clone(), tank, pump, self.zone_tx.clone()))
             }
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Pump, id: zid }),
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
-    }
-    pub async fn pump_stop(&mut self, zid: u8) -> Result<(), Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Pump { id, interface, .. } if id == &zid => {
-                    return interface
-                        .pump
-                        .as_ref()
-                        .expect("Interface not found")
-                        .stop().await
-                }
-                _ => continue,
+    }
+    /// Check interlocks without running, e.g. before moving the arm for watering
+    pub fn pump_check(&self, zid: u8, requested: Option<Duration>) -> Result<(), GrowError> {
+        match self.zones.get(ZoneKind::Pump, zid) {
+            Some(Zone::Pump { settings, status, .. }) => {
+                let level = settings.tank_id.and_then(|tid| self.tank_level(tid));
+                let checked = status.read().check(settings, level, requested, Instant::now());
+                checked.map_err(|interlock| GrowError::Interlock {
+                    kind: ZoneKind::Pump,
+                    id: zid,
+                    reason: interlock.to_string(),
+                })
+            }
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Pump, id: zid }),
+        }
+    }
+    fn pump_stopped(&mut self, zid: u8) {
+        if let Some(Zone::Pump { status, .. }) = self.zones.get(ZoneKind::Pump, zid) {
+            zone::pump::stopped(zid, status, &self.zone_tx);
+        }
+    }
+    /// Speed for following runs, kept in settings
+    pub async fn pump_set_speed(&mut self, zid: u8, speed: i8) -> Result<(), GrowError> {
+        if !(-100..=100).contains(&speed) {
+            return Err(GrowError::Config(format!("Pump speed {} outside -100..100", speed)));
+        }
+        match self.zones.get_mut(ZoneKind::Pump, zid) {
+            Some(Zone::Pump { settings, status, interface, runner, .. }) => {
+                interface
+                    .pump
+                    .as_ref()
+                    .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Pump, id: zid })?
+                    .set_speed(speed)
+                    .await?;
+                settings.speed = speed;
+                status.write().speed = speed;
+                let _ = runner.settings_sender().send(*settings);
+                Ok(())
             }
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Pump, id: zid }),
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
+    }
+    /// Run until stopped, or until the pump's max runtime. Leased to the console for
+    /// `MANUAL_LEASE`, refused while the panel, console or remote control holds the pump.
+    pub async fn pump_run(&mut self, zid: u8) -> Result<(), GrowError> {
+        let pump = self.pump_handle(zid)?;
+        let lease = self.leases.acquire(ZoneKind::Pump, zid, LeaseHolder::Console, MANUAL_LEASE)?;
+        pump.run().await?;
+        lease.keep();
+        Ok(())
+    }
+    pub async fn pump_stop(&mut self, zid: u8) -> Result<(), GrowError> {
+        self.pump_handle(zid)?.stop().await
     }
     pub async fn pump_run_for_secs(
         &mut self,
         zid: u8,
         secs: u16,
-    ) -> Result<(), Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Pump { id, interface, .. } if id == &zid => {
-                    return interface
-                        .pump
-                        .as_ref()
-                        .expect("Interface not found")
-                        .run_for_secs(secs)
-                        .await
-                }
-                _ => continue,
-            }
-        }
-        return Err(Box::new(ZoneError::new("Zone not found")));
+    ) -> Result<(), GrowError> {
+        let pump = self.pump_handle(zid)?;
+        let duration = Duration::from_secs(secs as u64);
+        let lease = self.leases.acquire(ZoneKind::Pump, zid, LeaseHolder::Console, duration)?;
+        pump.run_for_secs(secs).await?;
+        lease.keep();
+        Ok(())
     }
 
     /// Arm commands
+    fn arm(&self, zid: u8) -> Result<(&zone::arm::Settings, &dyn zone::arm::Arm), GrowError> {
+        match self.zones.get(ZoneKind::Arm, zid) {
+            Some(Zone::Arm { settings, interface, .. }) => interface
+                .arm
+                .as_deref()
+                .map(|arm| (settings, arm))
+                .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Arm, id: zid }),
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: zid }),
+        }
+    }
+    /// Planned move run by the arm runner, leased to the console while moving.
+    /// Refused while another move is in progress or another holder has the arm.
     pub async fn arm_goto(
         &mut self,
         zid: u8,
         x: i32,
         y: i32,
         z: i32,
-    ) -> Result<(), Box<dyn Error + '_>> {
-        for zone in self.zones() {
-            match zone {
-                Zone::Arm { id, interface, .. } if id == &zid => {
-                    return interface
-                        .arm
-                        .as_ref()
-                        .expect("Interface not found")
-                        .goto(x, y, z).await;
-                }
-                _ => continue,
-            }
-        }
-        return Err(Box::new(ZoneError::new("Zone not found")));
+    ) -> Result<(), GrowError> {
+        let arm_move = self.arm_plan(zid, x, y, z)?;
+        let _lease = self.leases.acquire(ZoneKind::Arm, zid, LeaseHolder::Console, MANUAL_LEASE)?;
+        arm_move.run(zone::arm::STEP_TIMEOUT).await.map(|_| ())
     }
+    /// Planned move along x, keeping y and z
     pub async fn arm_goto_x(
         &mut self,
         zid: u8,
         x: i32,
-    ) -> Result<(), Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Arm { id, interface, .. } if id == &zid => {
-                    return interface
-                        .arm
-                        .as_ref()
-                        .expect("Interface not found")
-                        .goto_x(x).await
-                }
-                _ => continue,
-            }
-        }
-        return Err(Box::new(ZoneError::new("Zone not found")));
+    ) -> Result<(), GrowError> {
+        let (_, y, z) = self.arm_position(zid)?;
+        self.arm_goto(zid, x, y, z).await
     }
+    /// Planned move along y, keeping x and z
     pub async fn arm_goto_y(
         &mut self,
         zid: u8,
         y: i32,
-    ) -> Result<(), Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Arm { id, interface, .. } if id == &zid => {
-                    return interface
-                        .arm
-                        .as_ref()
-                        .expect("Interface not found")
-                        .goto_y(y).await
-                }
-                _ => continue,
-            }
+    ) -> Result<(), GrowError> {
+        let (x, _, z) = self.arm_position(zid)?;
+        self.arm_goto(zid, x, y, z).await
+    }
+    /// Plan a move from the current position, run it after releasing the house lock
+    pub fn arm_plan(
+        &mut self,
+        zid: u8,
+        x: i32,
+        y: i32,
+        z: i32,
+    ) -> Result<ArmMove, GrowError> {
+        let (settings, arm) = self.arm(zid)?;
+        let from = arm.position()?;
+        let steps = settings.plan(from, (x, y, z))?;
+        let handle = self.arm_handle(zid).ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: zid })?;
+        Ok(ArmMove::new(steps, (x, y, z), handle))
+    }
+    /// Homing of the arm, run it after releasing the house lock
+    pub fn arm_home(&mut self, zid: u8) -> Result<ArmHoming, GrowError> {
+        let handle = self.arm_handle(zid).ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: zid })?;
+        match self.zones.get(ZoneKind::Arm, zid) {
+            Some(Zone::Arm { settings, interface, .. }) => interface
+                .arm
+                .clone()
+                .map(|arm| ArmHoming::new(arm, settings.clone(), handle))
+                .ok_or(GrowError::InterfaceMissing { kind: ZoneKind::Arm, id: zid }),
+            _ => Err(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: zid }),
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
     }
-    pub async fn arm_update(
+    pub fn arm_handle(&mut self, zid: u8) -> Option<zone::arm::ArmHandle> {
+        match self.zones.get(ZoneKind::Arm, zid) {
+            Some(Zone::Arm { runner, .. }) => Some(runner.handle()),
+            _ => None,
+        }
+    }
+    /// Plan a straight x/y move at the current height, e.g. for watering along a row
+    pub fn arm_plan_line(
         &mut self,
         zid: u8,
-    ) -> Result<(), Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Arm { id, interface, .. } if id == &zid => {
-                    return interface
-                        .arm
-                        .as_ref()
-                        .expect("Interface not found")
-                        .update_pos()
-                        .await;
+        x: i32,
+        y: i32,
+        z: i32,
+    ) -> Result<ArmMove, GrowError> {
+        let (settings, arm) = self.arm(zid)?;
+        let from = arm.position()?;
+        let steps = settings.plan_line(from, (x, y, z))?;
+        let handle = self.arm_handle(zid).ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: zid })?;
+        Ok(ArmMove::new(steps, (x, y, z), handle))
+    }
+    /// Plan a move to the park position, or home if none is taught
+    pub fn arm_plan_park(&mut self, zid: u8) -> Result<ArmMove, GrowError> {
+        let (x, y, z) = self
+            .arm_settings(zid)
+            .ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: zid })?
+            .park();
+        self.arm_plan(zid, x, y, z)
+    }
+    /// Plan a move to a named position from the arm's library
+    pub fn arm_plan_named(&mut self, zid: u8, name: &str) -> Result<ArmMove, GrowError> {
+        let (x, y, z) = self
+            .arm_settings(zid)
+            .ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: zid })?
+            .position(name)
+            .ok_or_else(|| GrowError::Config(format!("Position {} not found on arm {}", name, zid)))?;
+        self.arm_plan(zid, x, y, z)
+    }
+    pub fn arm_positions(&mut self, zid: u8) -> Option<BTreeMap<String, zone::arm::Point>> {
+        self.arm_settings(zid).map(|s| s.positions)
+    }
+    /// Store current arm position under `name` and save config
+    pub fn teach_position(&mut self, zid: u8, name: &str) -> Result<(i32, i32, i32), GrowError> {
+        let pos = self.arm_position(zid)?;
+        match self.zones.get_mut(ZoneKind::Arm, zid) {
+            Some(Zone::Arm { settings, .. }) => settings.teach(name, pos)?,
+            _ => return Err(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: zid }),
+        }
+        self.save_settings()?;
+        Ok(pos)
+    }
+    /// Remove a named position, fails while a water zone still uses it
+    pub fn forget_position(&mut self, zid: u8, name: &str) -> Result<(), GrowError> {
+        for zone in self.zones() {
+            match zone {
+                Zone::Water { id, settings, .. }
+                    if (settings.position.arm_id == zid) & settings.positions.iter().any(|n| n == name) =>
+                {
+                    return Err(GrowError::Config(format!("Position {} used by water zone {}", name, id)));
                 }
                 _ => continue,
             }
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
+        let removed = match self.zones.get_mut(ZoneKind::Arm, zid) {
+            Some(Zone::Arm { settings, .. }) => settings.positions.remove(name).is_some(),
+            _ => return Err(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: zid }),
+        };
+        if !removed {
+            return Err(GrowError::Config(format!("Position {} not found on arm {}", name, zid)));
+        }
+        self.save_settings()
+    }
+    pub fn arm_settings(&mut self, zid: u8) -> Option<zone::arm::Settings> {
+        match self.zones.get(ZoneKind::Arm, zid) {
+            Some(Zone::Arm { settings, .. }) => Some(settings.clone()),
+            _ => None,
+        }
+    }
+    pub async fn arm_update(
+        &mut self,
+        zid: u8,
+    ) -> Result<(), GrowError> {
+        self.arm(zid)?.1.update_pos().await
     }
     pub fn arm_position(
         &mut self,
         zid: u8,
-    ) -> Result<(i32, i32, i32), Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Arm { id, interface, .. } if id == &zid => {
-                    return interface
-                        .arm
-                        .as_ref()
-                        .expect("Interface not found")
-                        .position();
-                }
-                _ => continue,
-            }
-        }
-        return Err(Box::new(ZoneError::new("Zone not found")));
+    ) -> Result<(i32, i32, i32), GrowError> {
+        self.arm(zid)?.1.position()
     }
     pub async fn arm_calibrate(
         &mut self,
         zid: u8,
-    ) -> Result<(i32, i32, i32), Box<dyn Error + '_>> {
-        for z in self.zones() {
-            match z {
-                Zone::Arm { id, interface, .. } if id == &zid => {
-                    return interface
-                        .arm
-                        .as_ref()
-                        .expect("Interface not found")
-                        .calibrate()
-                        .await;
-                }
-                _ => continue,
-            }
+    ) -> Result<(i32, i32, i32), GrowError> {
+        let offset = self.arm(zid)?.1.calibrate().await?;
+        if let Some(Zone::Arm { status, .. }) = self.zones.get(ZoneKind::Arm, zid) {
+            status.write().calibration = Some(offset);
         }
-        return Err(Box::new(ZoneError::new("Zone not found")));
+        Ok(offset)
     }
 
     /// Alternative command model
     pub fn collect_cmd_senders(&mut self) -> Vec<ZoneCmd> {
-        let mut r: Vec<ZoneCmd> = Vec::new();
-        for zone in self.zones() {
-            match zone {
-                Zone::Arm { id, runner, .. } => r.push(ZoneCmd::Arm {
-                    id: *id,
-                    sender: runner.cmd_sender(),
-                }),
-                Zone::Pump { id, runner, .. } => r.push(ZoneCmd::Pump {
-                    id: *id,
-                    sender: runner.cmd_sender(),
-                }),
-                Zone::Light { id, runner, .. } => r.push(ZoneCmd::Light {
-                    id: *id,
-                    sender: runner.lamp_cmd_sender(),
-                }),
-                _ => {}
+        self.zones().iter().map(ZoneCmd::from).collect()
+    }
+}
+
+type TankLink = (u8, Arc<parking_lot::RwLock<zone::tank::Status>>);
+type PumpLink = (u8, Arc<parking_lot::RwLock<zone::pump::Status>>, f32);
+
+/// Bind the hardware interface of a zone to its runner and start the runner
+async fn init_zone(
+    zone: &mut Zone,
+    tanks: &[TankLink],
+    pumps: &[PumpLink],
+    zone_channels: &ZoneChannelsTx,
+    ops_channels: &OpsChannelsTx,
+) {
+    match zone {
+        Zone::Air {
+            interface,
+            runner,
+            ..
+        } => {
+            let channels = runner.fan_channels();
+            if let Some(fan) = interface.fan.as_mut() {
+                let _ = fan.init(channels.0, channels.1);
+            }
+            if let Some(device) = interface.thermo.as_mut() {
+                let _ = device
+                    .init(runner.thermo_feedback_sender()).await;
+            }
+        }
+        Zone::Aux {
+            interface,
+            runner,
+            ..
+        } => {
+            if let Some(device) = interface.auxiliary_device.as_mut() {
+                let _ = device
+                    .init(runner.auxiliary_feedback_sender())
+                    .await;
+            }
+        }
+        Zone::Light {
+            interface,
+            runner,
+            ..
+        } => {
+            if let Some(device) = interface.lightmeter.as_mut() {
+                let _ = device
+                    .init(runner.lightmeter_feedback_sender());
+            }
+            if let Some(device) = interface.lamp.as_mut() {
+                let _ = device
+                    .init(runner.lamp_cmd_receiver());
+            }
+        }
+        Zone::Water {
+            interface,
+            runner,
+            ..
+        } => {
+            if let Some(device) = interface.moist.as_mut() {
+                let _ = device
+                    .init(runner.moisture_feedback_sender());
             }
         }
-        dbg!(&r);
-        r
+        Zone::Tank {
+            interface,
+            runner,
+            ..
+        } => {
+            if let Some(sensor) = interface.tank_sensor.as_mut() {
+                let _ = sensor
+                    .init(runner.tank_feedback_sender())
+                    .await;
+                // Continuous level is optional
+                let _ = sensor.init_level(runner.level_feedback_sender());
+            }
+            if let Some(valve) = interface.refill.as_mut() {
+                let _ = valve.init(runner.refill_cmd_receiver());
+            }
+        }
+        Zone::Pump {
+            id,
+            settings,
+            interface,
+            runner,
+            ..
+        } => match interface.pump.as_mut().map(Arc::get_mut) {
+            Some(Some(device)) => {
+                let _ = device
+                    .init(runner.cmd_receiver(), runner.feedback_sender())
+                    .await;
+                let _ = device
+                    .set_speed(settings.speed)
+                    .await;
+            }
+            Some(None) => driver_shared(ZoneKind::Pump, *id, ops_channels).await,
+            None => {}
+        },
+        Zone::Arm {
+            id,
+            interface,
+            runner,
+            ..
+        } => match interface.arm.as_mut().map(Arc::get_mut) {
+            Some(Some(device)) => {
+                let _ = device
+                    .init(
+                        runner.pos_feedback_sender().0,
+                        runner.pos_feedback_sender().1,
+                        runner.pos_feedback_sender().2,
+                        runner.control_feedback_sender(),
+                        runner.cmd_receiver(),
+                    )
+                    .await;
+            }
+            Some(None) => driver_shared(ZoneKind::Arm, *id, ops_channels).await,
+            None => {}
+        }, // _ => ()
+    }
+    run_zone(zone, tanks, pumps, zone_channels, ops_channels);
+}
+
+/// Init takes the driver mutably, one still held by a handle is not bound to the new runner
+async fn driver_shared(kind: ZoneKind, id: u8, ops_channels: &OpsChannelsTx) {
+    let _ = ops_channels
+        .syslog
+        .send(
+            SysLog::with_level(LogLevel::Error, format!("{:?} zone {} driver in use, not initialised", kind, id))
+                .source(&format!("{}/{}", format!("{:?}", kind).to_lowercase(), id)),
+        )
+        .await;
+}
+
+/// Spawn the runner task of a zone, also used to restart it with the interface already bound
+fn run_zone(
+    zone: &mut Zone,
+    tanks: &[TankLink],
+    pumps: &[PumpLink],
+    zone_channels: &ZoneChannelsTx,
+    ops_channels: &OpsChannelsTx,
+) {
+    match zone {
+        Zone::Air { settings, interface, runner, .. } => {
+            runner.run(
+                settings.clone(),
+                zone_channels.clone(),
+                ops_channels.clone(),
+                interface.fan.is_some(),
+            );
+        }
+        Zone::Aux { settings, runner, .. } => {
+            runner.run(settings.clone(), zone_channels.clone(), ops_channels.clone());
+        }
+        Zone::Light { settings, runner, .. } => {
+            runner.run(settings.clone(), zone_channels.clone(), ops_channels.clone());
+        }
+        Zone::Water { settings, runner, .. } => {
+            runner.run(settings.clone(), zone_channels.clone(), ops_channels.clone());
+        }
+        Zone::Tank { id, settings, runner, .. } => {
+            let feeding = pumps
+                .iter()
+                .filter(|p| p.0 == *id)
+                .map(|p| (p.1.clone(), p.2))
+                .collect();
+            runner.run(
+                settings.clone(),
+                feeding,
+                zone_channels.clone(),
+                ops_channels.clone(),
+            );
+        }
+        Zone::Pump { settings, runner, .. } => {
+            let tank = settings.tank_id.and_then(|tid| tanks.iter().find(|t| t.0 == tid).map(|t| t.1.clone()));
+            runner.run(settings.clone(), tank, zone_channels.clone(), ops_channels.clone());
+        }
+        Zone::Arm { settings, runner, .. } => {
+            runner.run(settings.clone(), zone_channels.clone(), ops_channels.clone());
+        }
     }
 }
 
+/// Leave actuators safe and stop the runner of a zone taken out of the house
+async fn release_zone(zone: &Zone) {
+    match zone {
+        Zone::Pump { interface, .. } => {
+            if let Some(pump) = interface.pump.as_deref() {
+                let _ = pump.stop().await;
+            }
+        }
+        Zone::Tank { interface, .. } => {
+            if let Some(valve) = interface.refill.as_deref() {
+                let _ = valve.set_open(false);
+            }
+        }
+        _ => {}
+    }
+    zone.abort();
+}
+
 /// Alternative command model
 #[derive(Clone, Debug)]
 pub enum ZoneCmd {
-    // Air {id: u8, info: DisplayStatus},
-    // Aux {id: u8, info: DisplayStatus},
+    Air {
+        id: u8,
+        sender: broadcast::Sender<zone::air::FanSetting>,
+    },
+    Aux {
+        id: u8,
+        sender: broadcast::Sender<(u8, DisplayStatus)>,
+    },
     Light {
         id: u8,
         sender: broadcast::Sender<(u8, bool)>,
     },
-    // Water {id: u8, info: DisplayStatus},
+    Water {
+        id: u8,
+        sender: broadcast::Sender<zone::water::Settings>,
+    },
     Arm {
         id: u8,
         sender: broadcast::Sender<ArmCmd>,
@@ -705,7 +1299,44 @@ pub enum ZoneCmd {
         id: u8,
         sender: broadcast::Sender<(u8, PumpCmd)>,
     },
-    // Tank {id: u8, info: DisplayStatus},
+    Tank {
+        id: u8,
+        sender: broadcast::Sender<bool>,
+    },
+}
+impl From<&Zone> for ZoneCmd {
+    fn from(zone: &Zone) -> Self {
+        match zone {
+            Zone::Air { id, runner, .. } => ZoneCmd::Air {
+                id: *id,
+                sender: runner.fan_cmd_sender(),
+            },
+            Zone::Aux { id, runner, .. } => ZoneCmd::Aux {
+                id: *id,
+                sender: runner.auxiliary_feedback_sender(),
+            },
+            Zone::Light { id, runner, .. } => ZoneCmd::Light {
+                id: *id,
+                sender: runner.lamp_cmd_sender(),
+            },
+            Zone::Water { id, runner, .. } => ZoneCmd::Water {
+                id: *id,
+                sender: runner.settings_sender(),
+            },
+            Zone::Arm { id, runner, .. } => ZoneCmd::Arm {
+                id: *id,
+                sender: runner.cmd_sender(),
+            },
+            Zone::Pump { id, runner, .. } => ZoneCmd::Pump {
+                id: *id,
+                sender: runner.cmd_sender(),
+            },
+            Zone::Tank { id, runner, .. } => ZoneCmd::Tank {
+                id: *id,
+                sender: runner.refill_request_sender(),
+            },
+        }
+    }
 }
 
 // pub fn get_tank_status(&self, zid: u8) -> Option<Arc<RwLock<zone::tank::Status>>> {
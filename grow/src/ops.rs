@@ -17,27 +17,70 @@ use core::fmt::Debug;
 use tokio::task::JoinHandle;
 use time::OffsetDateTime;
 
+pub mod alert;
 pub mod display;
+pub mod heartbeat;
 pub mod io;
+pub mod logging;
 pub mod manager;
+pub mod menu;
+pub mod metrics;
+pub mod profile;
 pub mod remote;
+pub mod rules;
+pub mod state;
 pub mod xymon;
 use zone::ZoneStatusRx;
 use crate::TIME_OFFSET;
+pub use logging::LogLevel;
 
 // }
 #[derive(Clone, Debug)]
 pub struct SysLog {
     msg: String,
     dt: OffsetDateTime,
+    level: LogLevel,
+    source: Option<String>,
+    fields: Vec<(String, String)>,
 }
 impl SysLog {
     pub fn new(msg: String) -> Self {
+        Self::with_level(LogLevel::Info, msg)
+    }
+    pub fn with_level(level: LogLevel, msg: String) -> Self {
         Self { 
             msg,
             dt: OffsetDateTime::now_utc().to_offset(TIME_OFFSET),
+            level,
+            source: None,
+            fields: Vec::new(),
          }
     }
+    /// Module or zone the entry comes from, e.g. "water/1" or "xymon"
+    pub fn source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+    pub fn field(mut self, key: &str, value: impl core::fmt::Display) -> Self {
+        self.fields.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+    pub fn dt(&self) -> OffsetDateTime {
+        self.dt
+    }
+    pub fn level(&self) -> LogLevel {
+        self.level
+    }
+    pub fn get_source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+    pub fn fields(&self) -> &Vec<(String, String)> {
+        &self.fields
+    }
 }
 
 
@@ -47,7 +90,7 @@ pub type SysLogTx = tokio::sync::mpsc::Sender<SysLog>;
 pub fn ops_channels() -> (OpsChannelsTx, OpsChannelsRx) {
     let (syslog_tx, syslog_rx) = mpsc::channel::<SysLog>(128);
     let rx = OpsChannelsRx { syslog: syslog_rx };
-    let tx = OpsChannelsTx { syslog: syslog_tx };
+    let tx = OpsChannelsTx { syslog: syslog_tx, heartbeats: heartbeat::Heartbeats::new() };
 
     (tx, rx)
 }
@@ -59,6 +102,7 @@ pub struct OpsChannelsRx {
 #[derive(Clone, Debug)]
 pub struct OpsChannelsTx {
     pub syslog: SysLogTx,
+    pub heartbeats: heartbeat::Heartbeats,
 }
 
 
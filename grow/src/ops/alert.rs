@@ -0,0 +1,782 @@
+use async_trait::async_trait;
+use core::error::Error;
+use core::fmt::Debug;
+use core::future::Future;
+use core::time::Duration;
+use parking_lot::RwLock;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use super::display::{format_time, Indicator};
+use super::LogLevel;
+use super::SysLog;
+use super::SysLogTx;
+use crate::zone::{ZoneDisplay, ZoneKind, ZoneStatusRx};
+use crate::GrowError;
+use crate::TIME_OFFSET;
+
+/// Alert rules and notifiers, read from grow-alert.js
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlertSettings {
+    pub enabled: bool,
+    pub rules: Vec<AlertRule>,
+    pub notifiers: Vec<NotifierSettings>,
+}
+
+/// Raise an alert when a zone has shown at least `indicator` for `hold`.
+/// `id: None` matches every zone of the kind. First matching rule applies.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub kind: ZoneKind,
+    pub id: Option<u8>,
+    pub indicator: Indicator,
+    pub hold: Duration,
+    pub repeat: Option<Duration>,
+    pub escalate_after: Option<Duration>,
+    pub notify: Vec<String>,
+    pub escalate_to: Vec<String>,
+}
+impl AlertRule {
+    fn matches(&self, kind: ZoneKind, id: u8, indicator: Indicator) -> bool {
+        (self.kind == kind)
+            & self.id.is_none_or(|rule_id| rule_id == id)
+            & (indicator >= self.indicator)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NotifierSettings {
+    Smtp {
+        name: String,
+        host: String,
+        port: u16,
+        helo: String,
+        from: String,
+        to: Vec<String>,
+    },
+    Webhook {
+        name: String,
+        host: String,
+        port: u16,
+        path: String,
+    },
+    Command {
+        name: String,
+        program: String,
+        args: Vec<String>,
+    },
+}
+impl NotifierSettings {
+    pub fn name(&self) -> &str {
+        match self {
+            NotifierSettings::Smtp { name, .. } => name,
+            NotifierSettings::Webhook { name, .. } => name,
+            NotifierSettings::Command { name, .. } => name,
+        }
+    }
+    pub fn build(&self) -> Box<dyn Notifier> {
+        match self.clone() {
+            NotifierSettings::Smtp { name, host, port, helo, from, to } => {
+                Box::new(SmtpNotifier { name, host, port, helo, from, to })
+            }
+            NotifierSettings::Webhook { name, host, port, path } => {
+                Box::new(WebhookNotifier { name, host, port, path })
+            }
+            NotifierSettings::Command { name, program, args } => {
+                Box::new(CommandNotifier { name, program, args })
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertEvent {
+    Raised,
+    Repeated,
+    Escalated,
+    Resolved,
+}
+
+/// What gets sent to notifiers
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlertMsg {
+    pub event: AlertEvent,
+    pub kind: ZoneKind,
+    pub id: u8,
+    pub indicator: Indicator,
+    pub msg: Option<String>,
+    pub since: OffsetDateTime,
+    pub sent: OffsetDateTime,
+}
+impl AlertMsg {
+    pub fn subject(&self) -> String {
+        format!("[grow] {:?} {:?} {} {:?}", self.event, self.kind, self.id, self.indicator)
+    }
+    pub fn body(&self) -> String {
+        format!(
+            "{:?} zone {} is {:?} since {}\nMessage: {}\nSent: {}",
+            self.kind,
+            self.id,
+            self.indicator,
+            format_time(self.since),
+            self.msg.as_deref().unwrap_or("No message"),
+            format_time(self.sent),
+        )
+    }
+}
+
+/// Time limit for each connect, read or write of a notifier
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A server that stops answering fails the notifier instead of stalling the alerter
+async fn io_step<T, E>(step: &str, io: impl Future<Output = Result<T, E>>) -> Result<T, Box<dyn Error + Send + Sync>>
+where
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    match tokio::time::timeout(IO_TIMEOUT, io).await {
+        Ok(result) => result.map_err(|e| e.into()),
+        Err(_) => Err(Box::new(GrowError::Timeout(step.to_string()))),
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &str;
+    async fn notify(&self, alert: &AlertMsg) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+impl Debug for dyn Notifier {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Notifier {{{}}}", self.name())
+    }
+}
+
+/// Plain SMTP without auth or TLS, intended for a local relay
+#[derive(Clone, Debug)]
+pub struct SmtpNotifier {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub helo: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+impl SmtpNotifier {
+    async fn expect_reply(
+        reader: &mut BufReader<tokio::net::tcp::ReadHalf<'_>>,
+        code: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // Multiline replies have a dash after the code on all lines but the last
+        loop {
+            let mut line = String::new();
+            if io_step("SMTP read", reader.read_line(&mut line)).await? == 0 {
+                return Err(Box::new(io::Error::new(io::ErrorKind::UnexpectedEof, "SMTP connection closed")));
+            }
+            if !line.starts_with(code) {
+                return Err(Box::new(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("SMTP expected {}, got: {}", code, line.trim_end()),
+                )));
+            }
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    async fn notify(&self, alert: &AlertMsg) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut stream = io_step("SMTP connect", TcpStream::connect((self.host.as_str(), self.port))).await?;
+        let (rd, mut wr) = stream.split();
+        let mut reader = BufReader::new(rd);
+        Self::expect_reply(&mut reader, "220").await?;
+        io_step("SMTP write", wr.write_all(format!("HELO {}\r\n", self.helo).as_bytes())).await?;
+        Self::expect_reply(&mut reader, "250").await?;
+        io_step("SMTP write", wr.write_all(format!("MAIL FROM:<{}>\r\n", self.from).as_bytes())).await?;
+        Self::expect_reply(&mut reader, "250").await?;
+        for rcpt in &self.to {
+            io_step("SMTP write", wr.write_all(format!("RCPT TO:<{}>\r\n", rcpt).as_bytes())).await?;
+            Self::expect_reply(&mut reader, "250").await?;
+        }
+        io_step("SMTP write", wr.write_all(b"DATA\r\n")).await?;
+        Self::expect_reply(&mut reader, "354").await?;
+        let mut data = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n",
+            self.from,
+            self.to.join(", "),
+            alert.subject()
+        );
+        for line in alert.body().lines() {
+            // Dot-stuffing
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push_str(".\r\n");
+        io_step("SMTP write", wr.write_all(data.as_bytes())).await?;
+        Self::expect_reply(&mut reader, "250").await?;
+        io_step("SMTP write", wr.write_all(b"QUIT\r\n")).await?;
+        let _ = Self::expect_reply(&mut reader, "221").await;
+
+        Ok(())
+    }
+}
+
+/// POST the alert as JSON over plain HTTP/1.1
+#[derive(Clone, Debug)]
+pub struct WebhookNotifier {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    async fn notify(&self, alert: &AlertMsg) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let body = serde_json::to_string(alert)?;
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            self.port,
+            body.len(),
+            body
+        );
+        let mut stream = io_step("Webhook connect", TcpStream::connect((self.host.as_str(), self.port))).await?;
+        io_step("Webhook write", stream.write_all(request.as_bytes())).await?;
+        let mut response = String::new();
+        io_step("Webhook read", stream.read_to_string(&mut response)).await?;
+        let status = response
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse::<u16>().ok());
+        match status {
+            Some(code) if (200..300).contains(&code) => Ok(()),
+            _ => Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Webhook {} response: {}", self.name, response.lines().next().unwrap_or("None")),
+            ))),
+        }
+    }
+}
+
+/// Run a local program. Alert fields are passed as GROW_ALERT_* env vars.
+#[derive(Clone, Debug)]
+pub struct CommandNotifier {
+    pub name: String,
+    pub program: String,
+    pub args: Vec<String>,
+}
+#[async_trait]
+impl Notifier for CommandNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    async fn notify(&self, alert: &AlertMsg) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let status = tokio::process::Command::new(&self.program)
+            .args(&self.args)
+            .env("GROW_ALERT_EVENT", format!("{:?}", alert.event))
+            .env("GROW_ALERT_KIND", format!("{:?}", alert.kind))
+            .env("GROW_ALERT_ID", alert.id.to_string())
+            .env("GROW_ALERT_INDICATOR", format!("{:?}", alert.indicator))
+            .env("GROW_ALERT_SUBJECT", alert.subject())
+            .env("GROW_ALERT_BODY", alert.body())
+            .status()
+            .await?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Box::new(io::Error::other(format!("Command {} exited with {}", self.name, status))))
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AlertCmd {
+    Ack { kind: ZoneKind, id: u8 },
+    Snooze { kind: ZoneKind, id: u8, duration: Duration },
+}
+
+/// Active alert as shown to operators
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlertInfo {
+    pub kind: ZoneKind,
+    pub id: u8,
+    pub indicator: Indicator,
+    pub msg: Option<String>,
+    pub since: OffsetDateTime,
+    pub notified: bool,
+    pub escalated: bool,
+    pub acknowledged: bool,
+    pub snoozed: bool,
+}
+
+/// Active alert kept in the state file over a restart
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedAlert {
+    pub kind: ZoneKind,
+    pub id: u8,
+    pub rule: usize,
+    pub indicator: Indicator,
+    pub msg: Option<String>,
+    pub since: OffsetDateTime,
+    pub notified: bool,
+    pub escalated: bool,
+    pub acknowledged: bool,
+    #[serde(default)]
+    pub snoozed_for: Option<Duration>,
+}
+
+#[derive(Clone, Debug)]
+struct ActiveAlert {
+    rule: usize,
+    indicator: Indicator,
+    msg: Option<String>,
+    since: OffsetDateTime,
+    seen: Instant,
+    first_sent: Option<Instant>,
+    last_sent: Option<Instant>,
+    escalated: bool,
+    acknowledged: bool,
+    snoozed_until: Option<Instant>,
+    /// From the state file, kept until the zone reports other than Blue
+    restored: bool,
+}
+impl ActiveAlert {
+    fn info(&self, key: &(ZoneKind, u8)) -> AlertInfo {
+        AlertInfo {
+            kind: key.0,
+            id: key.1,
+            indicator: self.indicator,
+            msg: self.msg.clone(),
+            since: self.since,
+            notified: self.first_sent.is_some(),
+            escalated: self.escalated,
+            acknowledged: self.acknowledged,
+            snoozed: self.snoozed_until.is_some_and(|t| t > Instant::now()),
+        }
+    }
+    fn msg(&self, key: &(ZoneKind, u8), event: AlertEvent) -> AlertMsg {
+        AlertMsg {
+            event,
+            kind: key.0,
+            id: key.1,
+            indicator: self.indicator,
+            msg: self.msg.clone(),
+            since: self.since,
+            sent: OffsetDateTime::now_utc().to_offset(TIME_OFFSET),
+        }
+    }
+}
+
+type Alerts = Arc<RwLock<BTreeMap<(ZoneKind, u8), ActiveAlert>>>;
+
+#[derive(Debug)]
+pub struct Alerter {
+    settings: AlertSettings,
+    alerts: Alerts,
+    tx_cmd: mpsc::Sender<AlertCmd>,
+    rx_cmd: Option<mpsc::Receiver<AlertCmd>>,
+    task: Option<JoinHandle<()>>,
+}
+impl Alerter {
+    pub fn new(settings: AlertSettings) -> Self {
+        let (tx_cmd, rx_cmd) = mpsc::channel(16);
+        Self {
+            settings,
+            alerts: Arc::new(RwLock::new(BTreeMap::new())),
+            tx_cmd,
+            rx_cmd: Some(rx_cmd),
+            task: None,
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+    pub fn cmd_sender(&self) -> mpsc::Sender<AlertCmd> {
+        self.tx_cmd.clone()
+    }
+
+    pub fn list(&self) -> Vec<AlertInfo> {
+        self.alerts
+            .read()
+            .iter()
+            .map(|(key, alert)| alert.info(key))
+            .collect()
+    }
+
+    pub fn saved(&self) -> Vec<SavedAlert> {
+        let now = Instant::now();
+        self.alerts
+            .read()
+            .iter()
+            .map(|(key, alert)| SavedAlert {
+                kind: key.0,
+                id: key.1,
+                rule: alert.rule,
+                indicator: alert.indicator,
+                msg: alert.msg.clone(),
+                since: alert.since,
+                notified: alert.first_sent.is_some(),
+                escalated: alert.escalated,
+                acknowledged: alert.acknowledged,
+                snoozed_for: alert.snoozed_until.and_then(|t| t.checked_duration_since(now)),
+            })
+            .collect()
+    }
+    /// Alerts from before a restart, before `run`. Notified alerts are not raised again.
+    pub fn restore(&mut self, saved: Vec<SavedAlert>) {
+        let now = Instant::now();
+        let mut lock = self.alerts.write();
+        for alert in saved.into_iter().filter(|a| a.rule < self.settings.rules.len()) {
+            lock.insert((alert.kind, alert.id), ActiveAlert {
+                rule: alert.rule,
+                indicator: alert.indicator,
+                msg: alert.msg,
+                since: alert.since,
+                seen: now,
+                first_sent: alert.notified.then_some(now),
+                last_sent: alert.notified.then_some(now),
+                escalated: alert.escalated,
+                acknowledged: alert.acknowledged,
+                snoozed_until: alert.snoozed_for.map(|d| now + d),
+                restored: true,
+            });
+        }
+    }
+
+    pub fn run(&mut self, mut from_zones: ZoneStatusRx, to_syslog: SysLogTx) {
+        let mut rx_cmd = match self.rx_cmd.take() {
+            Some(rx) => rx,
+            None => return,
+        };
+        let rules = self.settings.rules.clone();
+        let notifiers: Arc<BTreeMap<String, Box<dyn Notifier>>> = Arc::new(
+            self.settings
+                .notifiers
+                .iter()
+                .map(|n| (n.name().to_string(), n.build()))
+                .collect(),
+        );
+        let alerts = self.alerts.clone();
+        let mut interval = tokio::time::interval(Duration::from_secs(5));
+
+        self.task = Some(tokio::spawn(async move {
+            let _ = to_syslog
+                .send(SysLog::new(String::from("Spawned alerter")))
+                .await;
+            let send = |names: &Vec<String>, msg: AlertMsg| {
+                for name in names {
+                    let notifiers = notifiers.clone();
+                    let to_syslog = to_syslog.clone();
+                    let name = name.clone();
+                    let msg = msg.clone();
+                    tokio::spawn(async move {
+                        let result = match notifiers.get(&name) {
+                            Some(notifier) => notifier
+                                .notify(&msg)
+                                .await
+                                .map_err(|e| format!("{}", e)),
+                            None => Err(String::from("Notifier not found")),
+                        };
+                        if let Err(e) = result {
+                            let _ = to_syslog
+                                .send(SysLog::with_level(LogLevel::Error, format!(
+                                    "Alert notifier {} failed: {}",
+                                    &name, e
+                                )).source("alert"))
+                                .await;
+                        }
+                    });
+                }
+            };
+            loop {
+                tokio::select! {
+                    Ok(data) = from_zones.recv() => {
+                        let key = data.kind();
+                        let info = data.info();
+                        let rule = rules.iter().position(|r| r.matches(key.0, key.1, info.indicator));
+                        let mut lock = alerts.write();
+                        match (rule, lock.get_mut(&key)) {
+                            (None, Some(alert)) if alert.restored & (info.indicator == Indicator::Blue) => {}
+                            (Some(rule), Some(alert)) => {
+                                alert.restored = false;
+                                alert.rule = rule;
+                                alert.indicator = info.indicator;
+                                alert.msg = info.msg.clone();
+                            }
+                            (Some(rule), None) => {
+                                lock.insert(key, ActiveAlert {
+                                    rule,
+                                    indicator: info.indicator,
+                                    msg: info.msg.clone(),
+                                    since: info.changed,
+                                    seen: Instant::now(),
+                                    first_sent: None,
+                                    last_sent: None,
+                                    escalated: false,
+                                    acknowledged: false,
+                                    snoozed_until: None,
+                                    restored: false,
+                                });
+                            }
+                            (None, Some(_)) => {
+                                let alert = lock.remove(&key).unwrap();
+                                if alert.first_sent.is_some() {
+                                    let mut msg = alert.msg(&key, AlertEvent::Resolved);
+                                    msg.indicator = info.indicator;
+                                    msg.msg = info.msg.clone();
+                                    send(&rules[alert.rule].notify, msg);
+                                }
+                            }
+                            (None, None) => {}
+                        }
+                    }
+                    Some(cmd) = rx_cmd.recv() => {
+                        let mut lock = alerts.write();
+                        match cmd {
+                            AlertCmd::Ack { kind, id } => {
+                                if let Some(alert) = lock.get_mut(&(kind, id)) {
+                                    alert.acknowledged = true;
+                                }
+                            }
+                            AlertCmd::Snooze { kind, id, duration } => {
+                                if let Some(alert) = lock.get_mut(&(kind, id)) {
+                                    alert.snoozed_until = Some(Instant::now() + duration);
+                                }
+                            }
+                        }
+                    }
+                    _ = interval.tick() => {
+                        let now = Instant::now();
+                        let mut lock = alerts.write();
+                        for (key, alert) in lock.iter_mut() {
+                            let rule = &rules[alert.rule];
+                            if alert.acknowledged | alert.snoozed_until.is_some_and(|t| t > now) {
+                                continue;
+                            }
+                            match (alert.first_sent, alert.last_sent) {
+                                (None, _) => {
+                                    if now.duration_since(alert.seen) >= rule.hold {
+                                        send(&rule.notify, alert.msg(key, AlertEvent::Raised));
+                                        alert.first_sent = Some(now);
+                                        alert.last_sent = Some(now);
+                                    }
+                                }
+                                (Some(first), Some(last)) => {
+                                    if !alert.escalated & rule.escalate_after.is_some_and(|d| now.duration_since(first) >= d) {
+                                        send(&rule.escalate_to, alert.msg(key, AlertEvent::Escalated));
+                                        alert.escalated = true;
+                                        alert.last_sent = Some(now);
+                                    }
+                                    else if rule.repeat.is_some_and(|d| now.duration_since(last) >= d) {
+                                        let event = AlertEvent::Repeated;
+                                        if alert.escalated {
+                                            send(&rule.escalate_to, alert.msg(key, event));
+                                        }
+                                        send(&rule.notify, alert.msg(key, event));
+                                        alert.last_sent = Some(now);
+                                    }
+                                }
+                                (Some(_), None) => {}
+                            }
+                        }
+                    }
+                    else => { break }
+                };
+            }
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn alert() -> AlertMsg {
+        let now = OffsetDateTime::now_utc();
+        AlertMsg {
+            event: AlertEvent::Raised,
+            kind: ZoneKind::Air,
+            id: 1,
+            indicator: Indicator::Red,
+            msg: Some(String::from(".hot")),
+            since: now,
+            sent: now,
+        }
+    }
+
+    /// Answers one SMTP session and returns the lines it received
+    async fn smtp_server(listener: TcpListener) -> Vec<String> {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let (rd, mut wr) = stream.split();
+        let mut reader = BufReader::new(rd);
+        let mut received = Vec::new();
+        let mut in_data = false;
+        wr.write_all(b"220 test\r\n").await.unwrap();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end().to_string();
+            let reply: Option<&[u8]> = match line.as_str() {
+                "." if in_data => {
+                    in_data = false;
+                    Some(b"250 queued\r\n")
+                }
+                _ if in_data => None,
+                "DATA" => {
+                    in_data = true;
+                    Some(b"354 go ahead\r\n")
+                }
+                "QUIT" => Some(b"221 bye\r\n"),
+                _ => Some(b"250-ok\r\n250 ok\r\n"),
+            };
+            received.push(line);
+            if let Some(reply) = reply {
+                wr.write_all(reply).await.unwrap();
+            }
+        }
+        received
+    }
+
+    /// Reads one request and replies with `status`, returns the request
+    async fn http_server(listener: TcpListener, status: &str) -> String {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("Content-Length: "))
+                    .and_then(|l| l.parse::<usize>().ok())
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    break;
+                }
+            }
+            if n == 0 {
+                break;
+            }
+        }
+        let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+        stream.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8(request).unwrap()
+    }
+
+    #[tokio::test]
+    async fn smtp_sends_mail() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(smtp_server(listener));
+        let notifier = SmtpNotifier {
+            name: String::from("mail"),
+            host: String::from("127.0.0.1"),
+            port,
+            helo: String::from("grow"),
+            from: String::from("grow@example.com"),
+            to: vec![String::from("a@example.com"), String::from("b@example.com")],
+        };
+        notifier.notify(&alert()).await.unwrap();
+        let received = server.await.unwrap();
+        assert_eq!(received[0], "HELO grow");
+        assert_eq!(received[1], "MAIL FROM:<grow@example.com>");
+        assert_eq!(received[2], "RCPT TO:<a@example.com>");
+        assert_eq!(received[3], "RCPT TO:<b@example.com>");
+        assert!(received.contains(&alert().subject().replace("[grow]", "Subject: [grow]")));
+        assert!(received.contains(&String::from("Message: .hot")));
+        assert_eq!(received.last().unwrap(), "QUIT");
+    }
+
+    #[tokio::test]
+    async fn smtp_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(b"554 no service\r\n").await.unwrap();
+        });
+        let notifier = SmtpNotifier {
+            name: String::from("mail"),
+            host: String::from("127.0.0.1"),
+            port,
+            helo: String::from("grow"),
+            from: String::from("grow@example.com"),
+            to: vec![String::from("a@example.com")],
+        };
+        assert!(notifier.notify(&alert()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(http_server(listener, "200 OK"));
+        let notifier = WebhookNotifier {
+            name: String::from("hook"),
+            host: String::from("127.0.0.1"),
+            port,
+            path: String::from("/alerts"),
+        };
+        notifier.notify(&alert()).await.unwrap();
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /alerts HTTP/1.1\r\n"));
+        let body = request.split_once("\r\n\r\n").unwrap().1;
+        let sent: AlertMsg = serde_json::from_str(body).unwrap();
+        assert_eq!((sent.event, sent.kind, sent.id, sent.indicator), (AlertEvent::Raised, ZoneKind::Air, 1, Indicator::Red));
+    }
+
+    #[tokio::test]
+    async fn webhook_error_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(http_server(listener, "500 Internal Server Error"));
+        let notifier = WebhookNotifier {
+            name: String::from("hook"),
+            host: String::from("127.0.0.1"),
+            port,
+            path: String::from("/alerts"),
+        };
+        assert!(notifier.notify(&alert()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn command_gets_alert_env() {
+        let notifier = CommandNotifier {
+            name: String::from("cmd"),
+            program: String::from("sh"),
+            args: vec![
+                String::from("-c"),
+                String::from("test \"$GROW_ALERT_KIND $GROW_ALERT_ID $GROW_ALERT_INDICATOR\" = \"Air 1 Red\""),
+            ],
+        };
+        notifier.notify(&alert()).await.unwrap();
+        let failing = CommandNotifier { args: vec![String::from("-c"), String::from("exit 3")], ..notifier };
+        assert!(failing.notify(&alert()).await.is_err());
+    }
+}
@@ -2,6 +2,7 @@
 
 use super::House;
 use crate::ops::OpsChannelsTx;
+use crate::driver::Binding;
 use crate::zone::{self, Zone, ZoneChannelsTx, ZoneSave };
 
 use core::error::Error;
@@ -14,31 +15,38 @@ use tokio::sync::Mutex;
 use time::Time;
 
 
-    fn load_settings(path: &str) -> Result<Vec<Zone>, Box<dyn Error>> {
+    type Bindings = BTreeMap<(zone::ZoneKind, u8), Vec<Binding>>;
+
+    fn load_settings(path: &str) -> Result<(Vec<Zone>, Bindings), Box<dyn Error>> {
         let readdata = std::fs::read_to_string(path)?;
         let loaddata: Vec<ZoneSave> = serde_json::from_str(&readdata)?;
         let mut zones: Vec<Zone> = Vec::new();
+        let mut bindings = Bindings::new();
         for zone in loaddata {
+            let (key, hardware) = zone.bindings();
+            if !hardware.is_empty() {
+                bindings.insert(key, hardware.clone());
+            }
             match zone {
-                ZoneSave::Air { id, settings } => 
+                ZoneSave::Air { id, settings, .. } => 
                     zones.push(zone::air::new(id, settings)),
-                ZoneSave::Water { id, settings } => 
+                ZoneSave::Water { id, settings, .. } => 
                     zones.push(zone::water::new(id, settings)),
-                ZoneSave::Light { id, settings } => 
+                ZoneSave::Light { id, settings, .. } => 
                     zones.push(zone::light::new(id, settings)),
-                ZoneSave::Arm { id, settings } => 
+                ZoneSave::Arm { id, settings, .. } => 
                     zones.push(zone::arm::new(id, settings)),
-                ZoneSave::Pump { id, settings } => 
+                ZoneSave::Pump { id, settings, .. } => 
                     zones.push(zone::pump::new(id, settings)),
-                ZoneSave::Tank { id, settings } => 
+                ZoneSave::Tank { id, settings, .. } => 
                     zones.push(zone::tank::new(id, settings)),
-                ZoneSave::Aux { id, settings } => 
+                ZoneSave::Aux { id, settings, .. } => 
                     zones.push(zone::auxiliary::new(id, settings)),
                 _ => ()
             }
         }
         
-        Ok(zones)
+        Ok((zones, bindings))
     }
 
     pub fn read_file_into_house( 
@@ -47,9 +55,13 @@ use time::Time;
         ops_tx: OpsChannelsTx
     ) -> House {
         match load_settings(path) {
-            Ok(zones) => {
+            Ok((zones, bindings)) => {
                 eprintln!("Load settings from: {}", &path);
-                House::new2(zones, zone_tx, ops_tx)
+                let mut house = House::new2(zones, zone_tx, ops_tx);
+                for ((kind, id), hardware) in bindings {
+                    house.set_bindings(kind, id, hardware);
+                }
+                house
             }
             Err(e) => {
                 eprintln!("Load settings error: {}\nLoading demo settings", e);
@@ -73,6 +85,7 @@ use time::Time;
                 temp_fan_low: 25.0,
                 temp_fan_high: 30.0,
                 fan_rpm_low_red_alert: 10.0,
+                safe_duty_cycle: 0.0,
             },
         ));
         h.zones.push(zone::air::new(
@@ -83,6 +96,7 @@ use time::Time;
                 temp_fan_low: 25.0,
                 temp_fan_high: 30.0,
                 fan_rpm_low_red_alert: 10.0,
+                safe_duty_cycle: 0.0,
             },
         ));
         h.zones.push(zone::water::new(
@@ -103,6 +117,8 @@ use time::Time;
                     y: 3872,
                     z: 0,
                 },
+                positions: Vec::new(),
+                route: Vec::new(),
             },
         ));
         h.zones.push(zone::water::new(
@@ -123,6 +139,8 @@ use time::Time;
                     y: 3653,
                     z: 0,
                 },
+                positions: Vec::new(),
+                route: Vec::new(),
             },
         ));
         h.zones.push(zone::light::new(
@@ -135,11 +153,14 @@ use time::Time;
             },
         ));
         h.zones
-            .push(zone::water::arm::new(1, zone::water::arm::Settings {}));
+            .push(zone::water::arm::new(1, Default::default()));
         h.zones
-            .push(zone::water::pump::new(1, zone::water::pump::Settings {}));
+            .push(zone::water::pump::new(1, zone::water::pump::Settings {
+                tank_id: Some(1),
+                ..Default::default()
+            }));
         h.zones
-            .push(zone::water::tank::new(1, zone::water::tank::Settings {}));
+            .push(zone::water::tank::new(1, Default::default()));
         h.zones
             .push(zone::auxiliary::new(1, zone::auxiliary::Settings {}));
         // h.zones
@@ -3,8 +3,9 @@ use crate::TIME_OFFSET;
 use core::fmt;
 use time::format_description::well_known::{Rfc2822, Rfc3339};
 use time::OffsetDateTime;
+use serde::{Serialize, Deserialize};
 
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Default)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Default, Hash, Serialize, Deserialize)]
 pub enum Indicator {
     #[default]
     Blue,
@@ -33,6 +34,8 @@ pub struct DisplayStatus {
     pub indicator: Indicator,
     pub msg: Option<String>,
     pub changed: OffsetDateTime,
+    /// Holder of a lease on the zone's actuator, e.g. "water/1"
+    pub lease: Option<String>,
 }
 impl DisplayStatus {
     pub fn new(indicator: Indicator, msg: Option<String>) -> Self {
@@ -40,6 +43,7 @@ impl DisplayStatus {
             indicator,
             msg,
             changed: OffsetDateTime::now_utc().to_offset(TIME_OFFSET),
+            lease: None,
         }
     }
 }
@@ -47,12 +51,16 @@ impl fmt::Display for DisplayStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.msg {
             None => {
-                write!(f, "{:>5} No message", self.indicator,)
+                write!(f, "{:>5} No message", self.indicator,)?;
             }
             Some(inner) => {
-                write!(f, "{:>5} {}", self.indicator, inner,)
+                write!(f, "{:>5} {}", self.indicator, inner,)?;
             }
         }
+        match &self.lease {
+            Some(holder) => write!(f, " [leased by {}]", holder),
+            None => Ok(()),
+        }
     }
 }
 
@@ -67,6 +75,7 @@ impl fmt::Display for ZoneDisplay {
             ZoneDisplay::Arm { id, info } =>        { write!(f, "{}    Arm {} {}", format_time(info.changed), id, info,  ) },
             ZoneDisplay::Pump { id, info } =>       { write!(f, "{}   Pump {} {}", format_time(info.changed), id, info,  ) },
             ZoneDisplay::Tank { id, info } =>       { write!(f, "{}   Tank {} {}", format_time(info.changed), id, info,  ) },
+            ZoneDisplay::House { id, info } =>      { write!(f, "{}  House {} {}", format_time(info.changed), id, info,  ) },
         }
     }
 }
@@ -208,7 +217,7 @@ impl fmt::Display for ZoneLog {
                     id, status_text
                 )
             }
-            ZoneLog::Pump { id, changed_status } => {
+            ZoneLog::Pump { id, state, speed, changed_status } => {
                 let status_text = match changed_status {
                     None => {
                         format!("None")
@@ -219,11 +228,11 @@ impl fmt::Display for ZoneLog {
                 };
                 write!(
                     f,
-                    "ZoneLog Pump {} {{Status change: {} }}",
-                    id, status_text
+                    "ZoneLog Pump {} {{State: {:?}, Speed: {:?}, Status change: {} }}",
+                    id, state, speed, status_text
                 )
             }
-            ZoneLog::Tank { id, changed_status } => {
+            ZoneLog::Tank { id, level, percent, days_left, refilling, changed_status } => {
                 let status_text = match changed_status {
                     None => {
                         format!("None")
@@ -234,8 +243,8 @@ impl fmt::Display for ZoneLog {
                 };
                 write!(
                     f,
-                    "ZoneLog Tank {} {{Status change: {} }}",
-                    id, status_text
+                    "ZoneLog Tank {} {{Level {:?}, Percent {:?}, Days left {:?}, Refilling {}, Status change: {} }}",
+                    id, level, percent, days_left, refilling, status_text
                 )
             }
         }
@@ -250,7 +259,11 @@ pub fn format_time(dt: OffsetDateTime) -> String {
 
 impl fmt::Display for super::SysLog {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} SysLog: {}", format_time(self.dt), self.msg ) 
+        write!(f, "{} {} {}: {}", format_time(self.dt()), self.level(), self.get_source().unwrap_or("SysLog"), self.msg())?;
+        for (k, v) in self.fields() {
+            write!(f, " {}={}", k, v)?;
+        }
+        Ok(())
     }
 }
 
@@ -0,0 +1,329 @@
+//! Heartbeats from runner and manager loops, and a monitor that reports stalled loops,
+//! a House lock held too long, and keeps a watchdog device fed while all is well
+use alloc::collections::BTreeMap;
+use core::error::Error;
+use core::fmt::Debug;
+use core::future::Future;
+use core::time::Duration;
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Instant;
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use super::display::{DisplayStatus, Indicator};
+use super::{LogLevel, SysLog, SysLogTx};
+use crate::zone::{ZoneDisplay, ZoneStatusTx};
+use crate::HouseMutex;
+
+/// How often loops beat, well below `missed_after`
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Monitor settings, read from grow-heartbeat.js
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatSettings {
+    pub enabled: bool,
+    /// How often heartbeats are checked and the watchdog is kicked
+    pub check_interval: Duration,
+    /// A loop is stalled when it has not beaten for this long
+    pub missed_after: Duration,
+    /// The House lock not acquired within this is reported as held
+    pub lock_threshold: Duration,
+    pub watchdog: Option<WatchdogSettings>,
+}
+impl Default for HeartbeatSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            check_interval: Duration::from_secs(5),
+            missed_after: Duration::from_secs(60),
+            lock_threshold: Duration::from_secs(30),
+            watchdog: None,
+        }
+    }
+}
+impl HeartbeatSettings {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let readdata = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&readdata)?)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WatchdogSettings {
+    /// Linux watchdog device, e.g. "/dev/watchdog"
+    Device(String),
+    /// Plain file rewritten with the time of each kick
+    File(String),
+}
+impl WatchdogSettings {
+    pub fn open(&self) -> Result<Box<dyn Watchdog>, Box<dyn Error>> {
+        Ok(match self {
+            WatchdogSettings::Device(path) => Box::new(DeviceWatchdog::open(path)?),
+            WatchdogSettings::File(path) => Box::new(FileWatchdog::new(path)),
+        })
+    }
+}
+
+/// Resets the controller unless kicked in time
+pub trait Watchdog: Send {
+    fn kick(&mut self) -> Result<(), Box<dyn Error>>;
+    /// Stop the countdown on an orderly shutdown
+    fn disarm(&mut self) -> Result<(), Box<dyn Error>>;
+}
+impl Debug for dyn Watchdog {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Watchdog")
+    }
+}
+
+/// Any write kicks, the magic character 'V' before closing disarms
+#[derive(Debug)]
+pub struct DeviceWatchdog {
+    file: Option<File>,
+}
+impl DeviceWatchdog {
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = std::fs::OpenOptions::new().write(true).open(path)?;
+        Ok(Self { file: Some(file) })
+    }
+}
+impl Watchdog for DeviceWatchdog {
+    fn kick(&mut self) -> Result<(), Box<dyn Error>> {
+        match self.file.as_mut() {
+            Some(file) => Ok(file.write_all(b"1")?),
+            None => Err("Watchdog disarmed".into()),
+        }
+    }
+    fn disarm(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(mut file) = self.file.take() {
+            file.write_all(b"V")?;
+        }
+        Ok(())
+    }
+}
+
+/// Stand-in for a watchdog device, e.g. for a service manager watching the file age
+#[derive(Debug)]
+pub struct FileWatchdog {
+    path: String,
+}
+impl FileWatchdog {
+    pub fn new(path: &str) -> Self {
+        Self { path: path.to_string() }
+    }
+}
+impl Watchdog for FileWatchdog {
+    fn kick(&mut self) -> Result<(), Box<dyn Error>> {
+        std::fs::write(&self.path, format!("{}\n", OffsetDateTime::now_utc()))?;
+        Ok(())
+    }
+    fn disarm(&mut self) -> Result<(), Box<dyn Error>> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+type Beats = Arc<RwLock<BTreeMap<String, Arc<RwLock<Instant>>>>>;
+
+/// Last beat of each registered loop, shared through `OpsChannelsTx`
+#[derive(Clone, Debug, Default)]
+pub struct Heartbeats {
+    beats: Beats,
+}
+impl Heartbeats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Beat as `name`, e.g. "water/1", taking over from an earlier loop with the same name.
+    /// The loop is forgotten when the returned heartbeat is dropped.
+    pub fn register(&self, name: &str) -> Heartbeat {
+        let last = Arc::new(RwLock::new(Instant::now()));
+        self.beats.write().insert(name.to_string(), last.clone());
+        Heartbeat {
+            name: name.to_string(),
+            last,
+            beats: self.beats.clone(),
+        }
+    }
+    /// Time since the last beat by loop name
+    pub fn ages(&self) -> Vec<(String, Duration)> {
+        self.beats
+            .read()
+            .iter()
+            .map(|(name, last)| (name.clone(), last.read().elapsed()))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct Heartbeat {
+    name: String,
+    last: Arc<RwLock<Instant>>,
+    beats: Beats,
+}
+impl Heartbeat {
+    pub fn beat(&self) {
+        *self.last.write() = Instant::now();
+    }
+    /// Keep beating while `work` runs, for sessions awaited inside the loop, e.g. a watering
+    pub async fn beat_while<F: Future>(&self, work: F) -> F::Output {
+        let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        tokio::pin!(work);
+        loop {
+            tokio::select! {
+                output = &mut work => return output,
+                _ = beat.tick() => self.beat(),
+            }
+        }
+    }
+}
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        let mut beats = self.beats.write();
+        if beats.get(&self.name).is_some_and(|last| Arc::ptr_eq(last, &self.last)) {
+            beats.remove(&self.name);
+        }
+    }
+}
+
+/// Result of one check, a manager loop or the House lock stalled is critical
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Health {
+    pub stalled: Vec<(String, Duration)>,
+    pub lock_held: Option<Duration>,
+}
+impl Health {
+    pub fn is_critical(&self) -> bool {
+        self.lock_held.is_some() || self.stalled.iter().any(|(name, _)| name.starts_with("manager/"))
+    }
+    pub fn display_status(&self) -> DisplayStatus {
+        if self.stalled.is_empty() && self.lock_held.is_none() {
+            return DisplayStatus::new(Indicator::Green, Some(String::from("Controller healthy")));
+        }
+        let mut problems: Vec<String> = self.stalled.iter().map(|(name, _)| format!("{} stalled", name)).collect();
+        if self.lock_held.is_some() {
+            problems.insert(0, String::from("House lock held"));
+        }
+        let indicator = if self.is_critical() { Indicator::Red } else { Indicator::Yellow };
+        DisplayStatus::new(indicator, Some(problems.join(", ")))
+    }
+}
+
+#[derive(Debug)]
+pub struct HeartbeatMonitor {
+    settings: HeartbeatSettings,
+    health: Arc<RwLock<Health>>,
+    stop: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+impl HeartbeatMonitor {
+    pub fn new(settings: HeartbeatSettings) -> Self {
+        Self {
+            settings,
+            health: Arc::new(RwLock::new(Health::default())),
+            stop: None,
+            task: None,
+        }
+    }
+
+    pub fn health(&self) -> Health {
+        self.health.read().clone()
+    }
+
+    /// Check heartbeats and probe the House lock, publish changes as the house status
+    pub fn run(&mut self, heartbeats: Heartbeats, house: HouseMutex, to_status_subscribers: ZoneStatusTx, to_syslog: SysLogTx) {
+        let settings = self.settings.clone();
+        let health = self.health.clone();
+        let (stop, mut stop_rx) = oneshot::channel();
+        self.stop = Some(stop);
+        self.task = Some(tokio::spawn(async move {
+            let _ = to_syslog.send(SysLog::new(String::from("Spawned heartbeat monitor"))).await;
+            let mut watchdog = match settings.watchdog.as_ref().map(|w| w.open().map_err(|e| e.to_string())) {
+                Some(Ok(watchdog)) => Some(watchdog),
+                Some(Err(e)) => {
+                    let _ = to_syslog
+                        .send(SysLog::with_level(LogLevel::Error, format!("Watchdog not opened: {}", e)).source("house"))
+                        .await;
+                    None
+                }
+                None => None,
+            };
+            let mut check = tokio::time::interval(settings.check_interval);
+            // Lock attempt in its own task, so the monitor goes on while it waits
+            let mut probe: Option<(Instant, JoinHandle<()>)> = None;
+            let mut published: Option<DisplayStatus> = None;
+            loop {
+                tokio::select! {
+                    _ = &mut stop_rx => {
+                        if let Some(watchdog) = watchdog.as_mut() {
+                            let _ = watchdog.disarm();
+                        }
+                        break;
+                    }
+                    _ = check.tick() => {}
+                }
+                let lock_held = match &probe {
+                    Some((_, task)) if task.is_finished() => {
+                        probe = None;
+                        None
+                    }
+                    Some((started, _)) if started.elapsed() > settings.lock_threshold => Some(started.elapsed()),
+                    Some(_) => None,
+                    None => {
+                        let house = house.clone();
+                        probe = Some((Instant::now(), tokio::spawn(async move {
+                            drop(house.lock().await);
+                        })));
+                        None
+                    }
+                };
+                let stalled = heartbeats
+                    .ages()
+                    .into_iter()
+                    .filter(|(_, age)| *age > settings.missed_after)
+                    .collect();
+                let now = Health { stalled, lock_held };
+
+                if !now.is_critical() {
+                    if let Some(watchdog) = watchdog.as_mut() {
+                        if let Err(e) = watchdog.kick().map_err(|e| e.to_string()) {
+                            let _ = to_syslog
+                                .send(SysLog::with_level(LogLevel::Warning, format!("Watchdog kick: {}", e)).source("house"))
+                                .await;
+                        }
+                    }
+                }
+
+                let ds = now.display_status();
+                if published.as_ref().map(|p| (&p.indicator, &p.msg)) != Some((&ds.indicator, &ds.msg)) {
+                    let level = if now.is_critical() { LogLevel::Error } else { LogLevel::Info };
+                    let _ = to_syslog
+                        .send(SysLog::with_level(level, ds.msg.clone().unwrap_or_default()).source("house"))
+                        .await;
+                    let _ = to_status_subscribers.send(ZoneDisplay::House { id: 0, info: ds.clone() });
+                    published = Some(ds);
+                }
+                *health.write() = now;
+            }
+        }));
+    }
+
+    /// Disarm the watchdog and stop checking
+    pub async fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(task) = self.task.take() {
+            let _ = tokio::time::timeout(Duration::from_secs(1), task).await;
+        }
+    }
+}
@@ -14,6 +14,7 @@ use core::fmt::Debug;
 use tokio::task::JoinHandle;
 use zone::ZoneStatusRx;
 use super::SysLogTx;
+use super::menu::MenuView;
 
 /// Indicator lights
 #[async_trait]
@@ -37,7 +38,7 @@ impl Debug for dyn Board {
 
 /// Text display panel
 #[async_trait]
-pub trait TextDisplay: Send {
+pub trait TextDisplay: Send + Sync {
     fn init(
         &self,
         from_zones: ZoneStatusRx,
@@ -47,6 +48,8 @@ pub trait TextDisplay: Send {
         &mut self,
         status_all: Vec<ZoneDisplay>,
     ) -> Result<(), Box<dyn Error>>;
+    /// Show a menu page instead of zone statuses, `None` returns to zone statuses
+    fn render(&self, view: Option<MenuView>) -> Result<(), Box<dyn Error>>;
 }
 impl Debug for dyn TextDisplay {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -0,0 +1,546 @@
+use core::error::Error;
+use core::fmt;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use time::format_description::well_known::Rfc3339;
+
+use super::display::Indicator;
+use super::SysLog;
+use crate::zone::{ZoneDisplay, ZoneLog};
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+}
+impl LogLevel {
+    /// RFC 5424 severity
+    fn severity(&self) -> u8 {
+        match self {
+            LogLevel::Error => 3,
+            LogLevel::Warning => 4,
+            LogLevel::Notice => 5,
+            LogLevel::Info => 6,
+            LogLevel::Debug | LogLevel::Trace => 7,
+        }
+    }
+}
+#[rustfmt::skip]
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let variant = match self  {
+            LogLevel::Trace =>   "  TRACE",
+            LogLevel::Debug =>   "  DEBUG",
+            LogLevel::Info =>    "   INFO",
+            LogLevel::Notice =>  " NOTICE",
+            LogLevel::Warning => "WARNING",
+            LogLevel::Error =>   "  ERROR",
+        };
+        write!(f, "{}", variant)
+    }
+}
+
+/// Zone log entries as structured log entries, source "zonelog/<zone>"
+pub fn from_zonelog(data: &ZoneLog) -> SysLog {
+    let (zone, changed) = match data {
+        ZoneLog::Air { id, changed_status, .. } => (format!("air/{}", id), changed_status),
+        ZoneLog::Light { id, changed_status, .. } => (format!("light/{}", id), changed_status),
+        ZoneLog::Water { id, changed_status, .. } => (format!("water/{}", id), changed_status),
+        ZoneLog::Tank { id, changed_status, .. } => (format!("tank/{}", id), changed_status),
+        ZoneLog::Pump { id, changed_status, .. } => (format!("pump/{}", id), changed_status),
+        ZoneLog::Arm { id, changed_status, .. } => (format!("arm/{}", id), changed_status),
+        ZoneLog::Aux { id, changed_status, .. } => (format!("aux/{}", id), changed_status),
+    };
+    let mut entry = SysLog::with_level(LogLevel::Debug, format!("{}", data))
+        .source(&format!("zonelog/{}", zone));
+    for (name, value) in super::xymon::log_values(data) {
+        entry = entry.field(&name, value);
+    }
+    if let Some(ds) = changed {
+        entry = entry.field("indicator", format!("{:?}", ds.indicator));
+    }
+
+    entry
+}
+
+/// Status changes as structured log entries, source "zonestatus/<zone>"
+pub fn from_zonestatus(data: &ZoneDisplay) -> SysLog {
+    let (kind, id) = data.kind();
+    let info = data.info();
+    let level = match info.indicator {
+        Indicator::Red => LogLevel::Warning,
+        Indicator::Yellow => LogLevel::Notice,
+        Indicator::Green | Indicator::Blue => LogLevel::Info,
+    };
+    SysLog::with_level(level, info.msg.clone().unwrap_or(String::from("No message")))
+        .source(&format!("zonestatus/{}/{}", format!("{:?}", kind).to_lowercase(), id))
+        .field("indicator", format!("{:?}", info.indicator))
+}
+
+/// Runtime filter applied before entries reach the sinks.
+/// The longest source prefix in `sources` decides, `None` silences the source.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LogFilter {
+    pub level: LogLevel,
+    pub sources: BTreeMap<String, Option<LogLevel>>,
+}
+impl Default for LogFilter {
+    fn default() -> Self {
+        let mut sources = BTreeMap::new();
+        sources.insert(String::from("zonelog"), None);
+        sources.insert(String::from("zonestatus"), None);
+        Self {
+            level: LogLevel::Info,
+            sources,
+        }
+    }
+}
+impl LogFilter {
+    pub fn effective(&self, source: Option<&str>) -> Option<LogLevel> {
+        let source = match source {
+            Some(source) => source,
+            None => return Some(self.level),
+        };
+        self.sources
+            .iter()
+            .filter(|(prefix, _)| {
+                source
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() | rest.starts_with('/'))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(Some(self.level), |(_, level)| *level)
+    }
+    pub fn allows(&self, entry: &SysLog) -> bool {
+        self.effective(entry.get_source())
+            .is_some_and(|level| entry.level() >= level)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SinkSettings {
+    Console {
+        level: LogLevel,
+        color: bool,
+    },
+    File {
+        level: LogLevel,
+        path: String,
+        max_bytes: u64,
+        keep: u8,
+    },
+    JsonLines {
+        level: LogLevel,
+        path: String,
+        max_bytes: u64,
+        keep: u8,
+    },
+    Syslog {
+        level: LogLevel,
+        socket: String,
+        facility: u8,
+        app_name: String,
+    },
+}
+impl SinkSettings {
+    pub fn build(&self) -> Result<Box<dyn LogSink>, Box<dyn Error>> {
+        Ok(match self.clone() {
+            SinkSettings::Console { level, color } => {
+                Box::new(ConsoleSink { level, color })
+            }
+            SinkSettings::File { level, path, max_bytes, keep } => {
+                Box::new(FileSink { level, file: RotatingFile::open(path, max_bytes, keep)? })
+            }
+            SinkSettings::JsonLines { level, path, max_bytes, keep } => {
+                Box::new(JsonLinesSink { level, file: RotatingFile::open(path, max_bytes, keep)? })
+            }
+            SinkSettings::Syslog { level, socket, facility, app_name } => {
+                Box::new(SyslogSink::new(level, &socket, facility, app_name)?)
+            }
+        })
+    }
+}
+
+/// Logging pipeline settings, read from grow-log.js
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LogSettings {
+    pub filter: LogFilter,
+    pub sinks: Vec<SinkSettings>,
+}
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            filter: Default::default(),
+            sinks: vec![SinkSettings::Console { level: LogLevel::Trace, color: true }],
+        }
+    }
+}
+impl LogSettings {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let readdata = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&readdata)?)
+    }
+}
+
+pub trait LogSink: Send {
+    fn name(&self) -> &str;
+    fn level(&self) -> LogLevel;
+    fn write(&mut self, entry: &SysLog) -> Result<(), Box<dyn Error>>;
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+impl fmt::Debug for dyn LogSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LogSink {{{}}}", self.name())
+    }
+}
+
+/// Filter and fan out entries to all sinks
+#[derive(Debug)]
+pub struct LogPipeline {
+    filter: LogFilter,
+    sinks: Vec<Box<dyn LogSink>>,
+}
+impl LogPipeline {
+    pub fn new(settings: &LogSettings) -> (Self, Vec<String>) {
+        let mut sinks: Vec<Box<dyn LogSink>> = Vec::new();
+        let mut errors: Vec<String> = Vec::new();
+        for s in &settings.sinks {
+            match s.build() {
+                Ok(sink) => sinks.push(sink),
+                Err(e) => errors.push(format!("Log sink {:?} error: {}", s, e)),
+            }
+        }
+        if sinks.is_empty() {
+            sinks.push(Box::new(ConsoleSink { level: LogLevel::Trace, color: true }));
+        }
+        (Self { filter: settings.filter.clone(), sinks }, errors)
+    }
+    pub fn set_filter(&mut self, filter: LogFilter) {
+        self.filter = filter;
+    }
+    pub fn log(&mut self, entry: &SysLog) {
+        if !self.filter.allows(entry) {
+            return;
+        }
+        for sink in self.sinks.iter_mut() {
+            if entry.level() >= sink.level() {
+                if let Err(e) = sink.write(entry) {
+                    eprintln!("Log sink {} error: {}", sink.name(), e);
+                }
+            }
+        }
+    }
+    pub fn flush(&mut self) {
+        for sink in self.sinks.iter_mut() {
+            let _ = sink.flush();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConsoleSink {
+    level: LogLevel,
+    color: bool,
+}
+impl LogSink for ConsoleSink {
+    fn name(&self) -> &str {
+        "console"
+    }
+    fn level(&self) -> LogLevel {
+        self.level
+    }
+    fn write(&mut self, entry: &SysLog) -> Result<(), Box<dyn Error>> {
+        let color = match (self.color, entry.level()) {
+            (false, _) => "",
+            (true, LogLevel::Error) => "\x1b[91m",
+            (true, LogLevel::Warning) => "\x1b[93m",
+            (true, LogLevel::Notice) => "\x1b[92m",
+            (true, LogLevel::Trace | LogLevel::Debug) => "\x1b[90m",
+            (true, LogLevel::Info) => "",
+        };
+        let reset = if color.is_empty() { "" } else { "\x1b[0m" };
+        println!("{}{}{}", color, entry, reset);
+        Ok(())
+    }
+}
+
+/// Append-only file that rotates to path.1 .. path.keep when max_bytes is reached
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: String,
+    max_bytes: u64,
+    keep: u8,
+    file: File,
+    size: u64,
+}
+impl RotatingFile {
+    pub fn open(path: String, max_bytes: u64, keep: u8) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, max_bytes, keep, file, size })
+    }
+    fn rotate(&mut self) -> Result<(), Box<dyn Error>> {
+        self.file.flush()?;
+        if self.keep == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                let _ = std::fs::rename(format!("{}.{}", self.path, n), format!("{}.{}", self.path, n + 1));
+            }
+            std::fs::rename(&self.path, format!("{}.1", self.path))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+    pub fn write_line(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        let len = line.len() as u64 + 1;
+        if (self.max_bytes > 0) & (self.size + len > self.max_bytes) & (self.size > 0) {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.file.write_all(b"\n")?;
+        self.size += len;
+        Ok(())
+    }
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct FileSink {
+    level: LogLevel,
+    file: RotatingFile,
+}
+impl LogSink for FileSink {
+    fn name(&self) -> &str {
+        &self.file.path
+    }
+    fn level(&self) -> LogLevel {
+        self.level
+    }
+    fn write(&mut self, entry: &SysLog) -> Result<(), Box<dyn Error>> {
+        self.file.write_line(&format!("{}", entry))
+    }
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.file.flush()
+    }
+}
+
+#[derive(Debug)]
+pub struct JsonLinesSink {
+    level: LogLevel,
+    file: RotatingFile,
+}
+impl LogSink for JsonLinesSink {
+    fn name(&self) -> &str {
+        &self.file.path
+    }
+    fn level(&self) -> LogLevel {
+        self.level
+    }
+    fn write(&mut self, entry: &SysLog) -> Result<(), Box<dyn Error>> {
+        let fields: BTreeMap<&str, &str> = entry
+            .fields()
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let line = serde_json::json!({
+            "ts": entry.dt().format(&Rfc3339)?,
+            "level": format!("{:?}", entry.level()),
+            "source": entry.get_source(),
+            "msg": entry.msg(),
+            "fields": fields,
+        });
+        self.file.write_line(&line.to_string())
+    }
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.file.flush()
+    }
+}
+
+/// RFC 5424 messages to a local syslog datagram socket, usually /dev/log
+#[derive(Debug)]
+pub struct SyslogSink {
+    level: LogLevel,
+    socket: UnixDatagram,
+    path: String,
+    facility: u8,
+    app_name: String,
+    hostname: String,
+}
+impl SyslogSink {
+    pub fn new(level: LogLevel, path: &str, facility: u8, app_name: String) -> Result<Self, Box<dyn Error>> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        let hostname = std::fs::read_to_string("/etc/hostname")
+            .map(|h| h.trim().to_string())
+            .unwrap_or(String::from("-"));
+        Ok(Self {
+            level,
+            socket,
+            path: path.to_string(),
+            facility,
+            app_name,
+            hostname,
+        })
+    }
+
+    /// Characters '"', '\' and ']' must be escaped in SD-PARAM values
+    fn sd_escape(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('"', "\\\"").replace(']', "\\]")
+    }
+
+    pub fn format(&self, entry: &SysLog) -> Result<String, Box<dyn Error>> {
+        let pri = self.facility as u16 * 8 + entry.level().severity() as u16;
+        let msgid = entry.get_source().unwrap_or("-").replace(' ', "_");
+        let sd = match entry.fields().is_empty() {
+            true => String::from("-"),
+            false => {
+                let params: Vec<String> = entry
+                    .fields()
+                    .iter()
+                    .map(|(k, v)| format!("{}=\"{}\"", k, Self::sd_escape(v)))
+                    .collect();
+                format!("[grow@32473 {}]", params.join(" "))
+            }
+        };
+        Ok(format!(
+            "<{}>1 {} {} {} {} {} {} {}",
+            pri,
+            entry.dt().format(&Rfc3339)?,
+            self.hostname,
+            self.app_name,
+            std::process::id(),
+            msgid,
+            sd,
+            entry.msg()
+        ))
+    }
+}
+impl LogSink for SyslogSink {
+    fn name(&self) -> &str {
+        &self.path
+    }
+    fn level(&self) -> LogLevel {
+        self.level
+    }
+    fn write(&mut self, entry: &SysLog) -> Result<(), Box<dyn Error>> {
+        let msg = self.format(entry)?;
+        self.socket.send(msg.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> LogFilter {
+        let mut sources = BTreeMap::new();
+        sources.insert(String::from("zonelog"), None);
+        sources.insert(String::from("zonelog/water"), Some(LogLevel::Debug));
+        sources.insert(String::from("alert"), Some(LogLevel::Warning));
+        LogFilter { level: LogLevel::Info, sources }
+    }
+
+    #[test]
+    fn longest_prefix_decides() {
+        let filter = filter();
+        assert_eq!(filter.effective(None), Some(LogLevel::Info));
+        assert_eq!(filter.effective(Some("zonelog")), None);
+        assert_eq!(filter.effective(Some("zonelog/air/1")), None);
+        assert_eq!(filter.effective(Some("zonelog/water")), Some(LogLevel::Debug));
+        assert_eq!(filter.effective(Some("zonelog/water/2")), Some(LogLevel::Debug));
+        assert_eq!(filter.effective(Some("alert/smtp")), Some(LogLevel::Warning));
+        assert_eq!(filter.effective(Some("xymon")), Some(LogLevel::Info));
+    }
+
+    #[test]
+    fn prefix_ends_at_slash() {
+        let filter = filter();
+        // "zonelog/waterfall" is not below "zonelog/water", and "alerts" is not below "alert"
+        assert_eq!(filter.effective(Some("zonelog/waterfall")), None);
+        assert_eq!(filter.effective(Some("alerts")), Some(LogLevel::Info));
+        assert!(!filter.allows(&SysLog::with_level(LogLevel::Error, String::from("x")).source("zonelog/air/1")));
+        assert!(filter.allows(&SysLog::with_level(LogLevel::Debug, String::from("x")).source("zonelog/water/1")));
+        assert!(!filter.allows(&SysLog::with_level(LogLevel::Notice, String::from("x")).source("alert")));
+    }
+
+    #[test]
+    fn rotation_numbering() {
+        let dir = std::env::temp_dir().join(format!("grow-logging-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("grow.log").to_string_lossy().to_string();
+        let read = |p: &str| std::fs::read_to_string(p).unwrap();
+
+        // Each 5 byte line fills the file, the next one rotates
+        let mut file = RotatingFile::open(path.clone(), 5, 2).unwrap();
+        for line in ["aaaa", "bbbb", "cccc", "dddd"] {
+            file.write_line(line).unwrap();
+        }
+        file.flush().unwrap();
+        assert_eq!(read(&path), "dddd\n");
+        assert_eq!(read(&format!("{}.1", path)), "cccc\n");
+        assert_eq!(read(&format!("{}.2", path)), "bbbb\n");
+        assert!(!std::path::Path::new(&format!("{}.3", path)).exists());
+
+        // A line longer than max_bytes still goes to an empty file
+        file.write_line("too long").unwrap();
+        file.flush().unwrap();
+        assert_eq!(read(&path), "too long\n");
+        assert_eq!(read(&format!("{}.1", path)), "dddd\n");
+
+        // keep 0 truncates instead of rotating
+        let mut file = RotatingFile::open(path.clone(), 5, 0).unwrap();
+        file.write_line("eeee").unwrap();
+        file.flush().unwrap();
+        assert_eq!(read(&path), "eeee\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn syslog(facility: u8) -> SyslogSink {
+        SyslogSink {
+            level: LogLevel::Trace,
+            socket: UnixDatagram::unbound().unwrap(),
+            path: String::from("/dev/null"),
+            facility,
+            app_name: String::from("grow"),
+            hostname: String::from("host"),
+        }
+    }
+
+    #[test]
+    fn syslog_pri() {
+        let entry = SysLog::with_level(LogLevel::Warning, String::from("Tank low"));
+        assert!(syslog(1).format(&entry).unwrap().starts_with("<12>1 "));
+        let entry = SysLog::with_level(LogLevel::Trace, String::from("x"));
+        assert!(syslog(16).format(&entry).unwrap().starts_with("<135>1 "));
+    }
+
+    #[test]
+    fn syslog_structured_data() {
+        let entry = SysLog::with_level(LogLevel::Error, String::from("Pump failed"))
+            .source("zone pump/1")
+            .field("reason", r#"say "hi" [a\b]"#);
+        let msg = syslog(1).format(&entry).unwrap();
+        assert!(msg.starts_with("<11>1 "));
+        assert!(msg.contains(&format!(" host grow {} zone_pump/1 ", std::process::id())));
+        assert!(msg.ends_with(r#" [grow@32473 reason="say \"hi\" [a\\b\]"] Pump failed"#));
+
+        let msg = syslog(1).format(&SysLog::new(String::from("plain"))).unwrap();
+        assert!(msg.ends_with(" - - plain"));
+    }
+}
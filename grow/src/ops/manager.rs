@@ -1,14 +1,24 @@
 use crate::zone::water::arm::ArmCmd;
-use crate::zone::water::arm::ArmState;
+use crate::zone::water::arm::{ArmHandle, ArmMode, ArmState};
+use crate::zone::water::RouteStep;
 use crate::zone::*;
+use crate::zone::lease::MANUAL_LEASE;
 use crate::House;
 use crate::HouseMutex;
 use crate::Zone;
 use crate::TIME_OFFSET;
 use super::display::format_time;
 use super::display::Indicator;
-use super::xymon::XymonSettings;
+use super::xymon::{XymonClient, XymonMsg, XymonSettings};
+use super::metrics::{Metrics, MetricsServer, MetricsSettings};
+use super::alert::{Alerter, AlertCmd, AlertInfo, AlertSettings};
+use super::rules::{RuleInfo, RuleSettings, RulesEngine};
+use super::profile::ProfileSettings;
 use crate::error::*;
+use crate::shutdown::ShutdownReport;
+use super::state::{RuntimeState, STATE_FILE};
+use crate::supervisor::{Backoff, SupervisedTask, Supervisor, TaskHealth};
+use super::heartbeat::{Health, Heartbeat, HeartbeatMonitor, HeartbeatSettings, HEARTBEAT_INTERVAL};
 
 use core::error::Error;
 use core::fmt::Debug;
@@ -19,6 +29,7 @@ use std::sync::Mutex;
 use time::OffsetDateTime;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc;
+use tokio::sync::oneshot;
 use tokio::sync::watch;
 use tokio::task::JoinHandle;
 use tokio::time::{sleep, timeout};
@@ -29,6 +40,9 @@ use super::remote::*;
 use super::OpsChannelsRx;
 use super::OpsChannelsTx;
 use super::SysLog;
+use super::SysLogTx;
+use super::menu::{Menu, MenuAction, MenuInput, MenuView};
+use super::logging::{self, LogFilter, LogLevel, LogPipeline, LogSettings};
 use crate::zone::water::arm::Arm;
 use time::format_description::well_known::{Rfc2822, Rfc3339};
 use tokio::task::spawn_blocking;
@@ -39,17 +53,50 @@ enum RcModeExit {
     Cancel,
     SwitchFromOpsMode,
     SwitchFromPositionMode,
+    /// Arm lease past its time limit
+    LeaseLost,
     ElseExit,
 }
+/// Step time for parking the arm after an aborted watering, kept within the arm lease
+const PARK_TIME: Duration = arm::STEP_TIMEOUT;
+/// Moves tried per waypoint before its position counts as not confirmed
+const MOVE_TRIES: u32 = 3;
+/// Time limit of a remote control position session
+const RC_LEASE: Duration = Duration::from_secs(600);
+type RemoteMutex = Arc<tokio::sync::Mutex<Box<dyn RemoteControl>>>;
+type BoardMutex = Arc<tokio::sync::Mutex<Box<dyn Board>>>;
+
+/// Log handler state, kept when the handler is restarted
+struct LogReceivers {
+    log_pipeline: LogPipeline,
+    log_filter_rx: watch::Receiver<LogFilter>,
+    log_flush_rx: mpsc::Receiver<oneshot::Sender<()>>,
+    syslog: super::SysLogRx,
+    zonelog: ZoneLogRx,
+    zonestatus: ZoneStatusRx,
+}
+
 #[derive(Debug)]
 pub struct Manager {
     house: HouseMutex,
-    board: Box<dyn Board>,
-    display: Box<dyn TextDisplay>,
-    remote: Box<dyn RemoteControl>,
+    /// Zone statuses for the board, read without the House lock
+    view: ZoneView,
+    board: BoardMutex,
+    display: Arc<dyn TextDisplay>,
+    remote: RemoteMutex,
     buttons: Box<dyn ButtonPanel>,
-    zonelog_enable: Option<watch::Sender<bool>>,
-    status_enable: Option<watch::Sender<bool>>,
+    menu_open: Option<mpsc::Sender<()>>,
+    log_filter: Option<watch::Sender<LogFilter>>,
+    log_flush: Option<mpsc::Sender<oneshot::Sender<()>>>,
+    persist: Option<tokio::task::JoinHandle<()>>,
+    supervisor: Supervisor,
+    heartbeat: Option<HeartbeatMonitor>,
+    alerter: Option<Alerter>,
+    rules: Option<RulesEngine>,
+    calendar: Option<JoinHandle<()>>,
+    xymon: Option<XymonClient>,
+    metrics: Metrics,
+    metrics_server: Option<MetricsServer>,
     ops_tx: OpsChannelsTx,
     zone_tx: ZoneChannelsTx,
 }
@@ -65,12 +112,23 @@ impl Manager {
     ) -> Self {
         Self {
             house,
-            board,
-            display,
-            remote,
+            view: ZoneView::default(),
+            board: Arc::new(tokio::sync::Mutex::new(board)),
+            display: Arc::from(display),
+            remote: Arc::new(tokio::sync::Mutex::new(remote)),
             buttons,
-            zonelog_enable: None,
-            status_enable: None,
+            menu_open: None,
+            log_filter: None,
+            log_flush: None,
+            persist: None,
+            supervisor: Supervisor::new(Backoff::default()),
+            heartbeat: None,
+            alerter: None,
+            rules: None,
+            calendar: None,
+            xymon: None,
+            metrics: Metrics::new(),
+            metrics_server: None,
             ops_tx,
             zone_tx,
         }
@@ -82,405 +140,1020 @@ impl Manager {
         mut ops_rx: OpsChannelsRx,
         selfmutex: crate::ManagerMutex,
     ) -> () {
-        let (log_enable_tx, mut log_enable_rx) =
-            tokio::sync::watch::channel(false);
-        self.zonelog_enable = Some(log_enable_tx);
-        let (status_enable_tx, mut status_enable_rx) =
-            tokio::sync::watch::channel(false);
-        self.status_enable = Some(status_enable_tx);
+        let log_settings = match LogSettings::load("grow-log.js") {
+            Ok(settings) => settings,
+            Err(e) => {
+                eprintln!("Log settings: {}. Using console.", e);
+                Default::default()
+            }
+        };
+        let (mut log_pipeline, log_errors) = LogPipeline::new(&log_settings);
+        for e in log_errors {
+            let _ = self.ops_tx.syslog
+                .send(SysLog::with_level(LogLevel::Error, e).source("log"))
+                .await;
+        }
+        let (log_filter_tx, mut log_filter_rx) =
+            tokio::sync::watch::channel(log_settings.filter.clone());
+        self.log_filter = Some(log_filter_tx);
+        let (log_flush_tx, mut log_flush_rx) = mpsc::channel::<oneshot::Sender<()>>(1);
+        self.log_flush = Some(log_flush_tx);
+
+        let (view, leases) = {
+            let lock = self.house.lock().await;
+            (lock.view(), lock.leases())
+        };
+        self.view = view.clone();
 
         /// Start log messages handler
-        let manager_mutex = selfmutex.clone();
+        let board = self.board.clone();
         let to_log = self.ops_tx.syslog.clone();
 
-        // Get Xymon settings
-        let mut xymon_enabled = true;
-        let x: Arc<XymonSettings>;
-        let readdata = std::fs::read_to_string("grow-xymon.js");
-        if readdata.is_ok() {
-             x = Arc::new(serde_json::from_str(&readdata.unwrap())
-            .expect("Deserialization error"));
-        } else {
-            x = Arc::new(super::xymon::XymonSettings {
-                port: 1984,
-                host: String::from("192.168.1.81"),
-                client: String::from("greenhouse"),
-            });
-        }
-        println!("Xymon: {:?}", *x);
-
-        let log_handler = tokio::spawn(async move {
-            let mut log_enabled = *log_enable_rx.borrow();
-            let mut status_enabled = *status_enable_rx.borrow();
-            to_log
-                .send(SysLog::new(format!("Spawned log handler")))
-                .await;
-           
-            loop {
-                tokio::select! {
-                    Ok(()) = log_enable_rx.changed() => {
-                        log_enabled = *log_enable_rx.borrow();
-                    }
-                    Ok(()) = status_enable_rx.changed() => {
-                        status_enabled = *status_enable_rx.borrow();
+        // Start Xymon client if configured
+        let to_xymon = match XymonSettings::load("grow-xymon.js") {
+            Ok(x) if x.enabled => {
+                let mut client = XymonClient::new(x);
+                client.run(self.ops_tx.syslog.clone());
+                let sender = client.sender();
+                self.xymon = Some(client);
+                Some(sender)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                let _ = self.ops_tx.syslog
+                    .send(SysLog::new(format!("Xymon disabled: {}", e)))
+                    .await;
+                None
+            }
+        };
+
+        // Start metrics endpoint if configured
+        match MetricsSettings::load("grow-metrics.js") {
+            Ok(settings) if settings.enabled => {
+                let mut server = MetricsServer::new(settings);
+                match server.run(
+                    self.metrics.clone(),
+                    self.zone_tx.zonestatus.clone(),
+                    self.ops_tx.syslog.clone(),
+                ).await {
+                    Ok(()) => self.metrics_server = Some(server),
+                    Err(e) => {
+                        let _ = self.ops_tx.syslog
+                            .send(SysLog::new(format!("Metrics endpoint error: {}", e)))
+                            .await;
                     }
-                    Some(data) = ops_rx.syslog.recv() => {
-                        if true {
-                            println!("{}",  &data);
+                }
+            }
+            _ => {}
+        }
+
+        // Receivers outlive the task, a restarted handler takes them over
+        let log_rx = Arc::new(tokio::sync::Mutex::new(LogReceivers {
+            log_pipeline,
+            log_filter_rx,
+            log_flush_rx,
+            syslog: ops_rx.syslog,
+            zonelog: from_zones.zonelog,
+            zonestatus: from_zones.zonestatus,
+        }));
+        let metrics = self.metrics.clone();
+        let heartbeats = self.ops_tx.heartbeats.clone();
+        let log_handler = SupervisedTask::new("manager/log", move || {
+            let log_rx = log_rx.clone();
+            let to_log = to_log.clone();
+            let board = board.clone();
+            let view = view.clone();
+            let metrics = metrics.clone();
+            let to_xymon = to_xymon.clone();
+            let heartbeats = heartbeats.clone();
+            tokio::spawn(async move {
+                let mut receivers = log_rx.lock().await;
+                let LogReceivers { log_pipeline, log_filter_rx, log_flush_rx, syslog, zonelog, zonestatus } = &mut *receivers;
+                let heartbeat = heartbeats.register("manager/log");
+                let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
+                to_log
+                    .send(SysLog::new(format!("Spawned log handler")))
+                    .await;
+           
+                loop {
+                    tokio::select! {
+                        Ok(()) = log_filter_rx.changed() => {
+                            let filter = log_filter_rx.borrow().clone();
+                            log_pipeline.set_filter(filter);
                         }
-                    }
-                    Some(data) = from_zones.zonelog.recv() => {
-                        if log_enabled {
-                            let now = OffsetDateTime::now_utc().to_offset(TIME_OFFSET);
-                            println!("{} {}", format_time(now), &data);
+                        Some(data) = syslog.recv() => {
+                            log_pipeline.log(&data);
                         }
-                    }
-                    Ok(data) = from_zones.zonestatus.recv() => {
-                        if status_enabled {
-                            println!("{}", &data);
+                        Some(done) = log_flush_rx.recv() => {
+                            while let Ok(data) = syslog.try_recv() {
+                                log_pipeline.log(&data);
+                            }
+                            while let Ok(data) = zonelog.try_recv() {
+                                log_pipeline.log(&logging::from_zonelog(&data));
+                            }
+                            log_pipeline.flush();
+                            let _ = done.send(());
                         }
-                        {
-                            manager_mutex.lock().await.update_board().await;
+                        Some(data) = zonelog.recv() => {
+                            log_pipeline.log(&logging::from_zonelog(&data));
+                            metrics.observe_log(&data);
+                            if let Some(to_xymon) = &to_xymon {
+                                if to_xymon.try_send(XymonMsg::Log(data)).is_err() {
+                                    metrics.channel_dropped("xymon");
+                                }
+                            }
                         }
+                        result = zonestatus.recv() => {
+                            let data = match result {
+                                Ok(data) => data,
+                                Err(broadcast::error::RecvError::Lagged(n)) => {
+                                    metrics.channel_lagged("zonestatus", n);
+                                    continue;
+                                }
+                                Err(broadcast::error::RecvError::Closed) => break,
+                            };
+                            metrics.observe_status(&data);
+                            log_pipeline.log(&logging::from_zonestatus(&data));
+                            // Not awaited here, logging goes on while the board is busy, e.g. blinking
+                            let board = board.clone();
+                            let view = view.clone();
+                            tokio::spawn(async move {
+                                update_board(&board, &view).await;
+                            });
                         
-                        if xymon_enabled {
-                            // let y = x.clone()
-                            let send = super::xymon::send_status(&data, x.clone()).await;
-                        }
-                        // xymon_enabled = false;
+                            if let Some(to_xymon) = &to_xymon {
+                                if to_xymon.try_send(XymonMsg::Status(data)).is_err() {
+                                    metrics.channel_dropped("xymon");
+                                }
+                            }
 
-                    }
-                    else => { break }
-                };
-            }
+                        }
+                        _ = beat.tick() => heartbeat.beat(),
+                        else => { break }
+                    };
+                }
+            })
         });
 
         // Indicators and textdisplay are handled with different alternatives, uncertain what is preferable.  
         // Indicators: Log messages handler catches status-updates and runs an update-method. 
         // TextDisplay: Subscribes to zonestatus and handles updates there. 
 
+        // Start alerter if configured
+        if let Ok(readdata) = std::fs::read_to_string("grow-alert.js") {
+            match serde_json::from_str::<AlertSettings>(&readdata) {
+                Ok(settings) if settings.enabled => {
+                    let mut alerter = Alerter::new(settings);
+                    if let Ok(state) = RuntimeState::load(STATE_FILE) {
+                        alerter.restore(state.alerts);
+                    }
+                    alerter.run(
+                        self.zone_tx.zonestatus.subscribe(),
+                        self.ops_tx.syslog.clone(),
+                    );
+                    self.alerter = Some(alerter);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    let _ = self.ops_tx.syslog
+                        .send(SysLog::new(format!("Alert settings error: {}", e)))
+                        .await;
+                }
+            }
+        }
+
+        // Start rules engine if configured
+        if let Ok(readdata) = std::fs::read_to_string("grow-rules.js") {
+            let loaded = serde_json::from_str::<RuleSettings>(&readdata)
+                .map_err(|e| e.to_string())
+                .and_then(|settings| settings.check().map(|_| settings).map_err(|e| e.to_string()));
+            match loaded {
+                Ok(settings) if settings.enabled => {
+                    let effects = self.house.lock().await.rule_effects();
+                    let mut rules = RulesEngine::new(settings, effects);
+                    rules.run(self.house.clone(), self.view.clone(), self.ops_tx.syslog.clone());
+                    self.rules = Some(rules);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    let _ = self.ops_tx.syslog
+                        .send(SysLog::with_level(LogLevel::Error, format!("Rule settings error: {}", e)).source("rules"))
+                        .await;
+                }
+            }
+        }
+
+        // Apply grow profiles and follow the calendar if configured
+        if let Ok(readdata) = std::fs::read_to_string("grow-profiles.js") {
+            match serde_json::from_str::<ProfileSettings>(&readdata) {
+                Ok(settings) => {
+                    let has_calendar = settings.calendar.is_some();
+                    if let Err(e) = self.house.lock().await.set_profiles(settings).await {
+                        let _ = self.ops_tx.syslog
+                            .send(SysLog::with_level(LogLevel::Warning, format!("Profile not applied: {}", e)).source("profile"))
+                            .await;
+                    }
+                    if has_calendar {
+                        let house = self.house.clone();
+                        let to_log = self.ops_tx.syslog.clone();
+                        self.calendar = Some(tokio::spawn(async move {
+                            let mut interval = tokio::time::interval(Duration::from_secs(60));
+                            // Reported once until it changes, the stage is retried each minute
+                            let mut last_error: Option<String> = None;
+                            loop {
+                                interval.tick().await;
+                                let result = house.lock().await.update_stage().await.map_err(|e| e.to_string());
+                                if let Err(e) = &result {
+                                    if last_error.as_ref() != Some(e) {
+                                        let _ = to_log
+                                            .send(SysLog::with_level(LogLevel::Warning, format!("Profile not applied: {}", e)).source("profile"))
+                                            .await;
+                                    }
+                                }
+                                last_error = result.err();
+                            }
+                        }));
+                    }
+                }
+                Err(e) => {
+                    let _ = self.ops_tx.syslog
+                        .send(SysLog::new(format!("Profile settings error: {}", e)))
+                        .await;
+                }
+            }
+        }
+
         // Start text display
         self.display.init(
             self.zone_tx.zonestatus.subscribe(),
             self.ops_tx.syslog.clone(),
         );
 
-        // Calibrate arm
-        {
-            let mut lock = self.house.lock().await;
-            let result = lock.arm_calibrate(1).await;
-            self.ops_tx
-                .syslog
-                .send(SysLog::new(format!("Calibrated X Y from: {:?}", result)))
-                .await;
-        }
+        // Home arm
+        let homing = self.house.lock().await.arm_home(1);
+        let result = match homing {
+            Ok(homing) => homing.run(arm::STEP_TIMEOUT).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        self.ops_tx
+            .syslog
+            .send(SysLog::new(format!("Arm homing: {:?}", result)).source("arm/1"))
+            .await;
         
         // Init buttons
         let (buttons_tx, mut from_buttons) =
             broadcast::channel(16);
         let _ = self.buttons.init(buttons_tx.clone());
+        let (menu_open, mut open_requests) = mpsc::channel::<()>(1);
+        self.menu_open = Some(menu_open);
         let to_log = self.ops_tx.syslog.clone();
         let house = self.house.clone();
-        let btn_handler = tokio::spawn(async move {
-            to_log
-                .send(SysLog::new(format!("Spawned button handler")))
-                .await;
-            loop {
-                tokio::select! {
-                    Ok(data) = from_buttons.recv() => {
-                        println!("{:?}", &data);
-                        match data {
-                            ButtonInput::OneUp => {
-                                house.lock().await.pump_stop(1).await;
-                            }
-                            ButtonInput::OneDown => {
-                                house.lock().await.pump_run(1).await;
-                            }
-                            ButtonInput::TwoUp => {
-                            }
-                            ButtonInput::TwoDown => {
+        let display = self.display.clone();
+        let remote = self.remote.clone();
+        let heartbeats = self.ops_tx.heartbeats.clone();
+        let button_rx = Arc::new(tokio::sync::Mutex::new((from_buttons, open_requests)));
+        let btn_handler = SupervisedTask::new("manager/buttons", move || {
+            let button_rx = button_rx.clone();
+            let to_log = to_log.clone();
+            let house = house.clone();
+            let display = display.clone();
+            let remote = remote.clone();
+            let heartbeats = heartbeats.clone();
+            tokio::spawn(async move {
+                let mut receivers = button_rx.lock().await;
+                let (from_buttons, open_requests) = &mut *receivers;
+                let heartbeat = heartbeats.register("manager/buttons");
+                let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
+                to_log
+                    .send(SysLog::new(format!("Spawned button handler")))
+                    .await;
+                loop {
+                    tokio::select! {
+                        Ok(data) = from_buttons.recv() => {
+                            println!("{:?}", &data);
+                            match data {
+                                ButtonInput::OneUp => {
+                                    let pump = house.lock().await.pump_handle(1);
+                                    if let Ok(pump) = pump {
+                                        let _ = pump.stop().await;
+                                    }
+                                }
+                                ButtonInput::OneDown => {
+                                    if let Ok((lease, pump)) = manual_pump(&house, 1, LeaseHolder::Panel, MANUAL_LEASE).await {
+                                        if pump.run().await.is_ok() {
+                                            lease.keep();
+                                        }
+                                    }
+                                }
+                                ButtonInput::TwoUp => {
+                                }
+                                ButtonInput::TwoDown => {
+                                    menu_session(house.clone(), display.clone(), remote.clone(), from_buttons, &heartbeat, to_log.clone()).await;
+                                }
                             }
                         }
-                    }
+                        Some(()) = open_requests.recv() => {
+                            menu_session(house.clone(), display.clone(), remote.clone(), from_buttons, &heartbeat, to_log.clone()).await;
+                        }
+                        _ = beat.tick() => heartbeat.beat(),
+                        else => { break }
+                    };
+                }
+            })
+        });
+
+        // Save runtime state each minute and after watering
+        let (persist_tx, mut persist_rx) = mpsc::channel::<()>(1);
+        let manager_mutex = selfmutex.clone();
+        let to_log = self.ops_tx.syslog.clone();
+        self.persist = Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    Some(()) = persist_rx.recv() => {}
                     else => { break }
-                };
+                }
+                if let Err(e) = manager_mutex.lock().await.save_state().await {
+                    let _ = to_log
+                        .send(SysLog::with_level(LogLevel::Warning, format!("Save runtime state: {}", e)).source("state"))
+                        .await;
+                }
             }
-        });
+        }));
 
         /// Start action messages handler
         let to_log = self.ops_tx.syslog.clone();
         let house = self.house.clone();
-        let zoneupdate_handler = tokio::spawn(async move {
-            to_log
-                .send(SysLog::new(format!("Spawned zoneupdate handler")))
-                .await;
-            while let Some(data) = from_zones.zoneupdate.recv().await {
-                match data {
-                    ZoneUpdate::Water {
-                        id: water_id,
-                        settings,
-                        status,
-                    } => {
-                        let mut log_msg: Option<String> = None;
-                        match timeout(Duration::from_secs(45), watering(water_id, settings, status, to_log.clone(), house.clone())).await {
-                            Ok(res) => {
-                                match res {
-                                    Ok( (true, msg) ) => {
-                                        log_msg = Some(msg);
-                                    },
-                                    Ok( (false, msg) ) => {
-                                        log_msg = Some(msg);
-                                    },
-                                    Err(e) => {
-                                        log_msg = Some(format!("{}", e));
-                                    }, 
-                                }
-                            },
-                            Err(_) => {
-                                log_msg = Some(format!("Water zone {} failed: Timed out", &water_id));
-                            }
+        let view = self.view.clone();
+        let metrics = self.metrics.clone();
+        let heartbeats = self.ops_tx.heartbeats.clone();
+        let zoneupdate_rx = Arc::new(tokio::sync::Mutex::new(from_zones.zoneupdate));
+        let zoneupdate_handler = SupervisedTask::new("manager/zoneupdate", move || {
+            let zoneupdate_rx = zoneupdate_rx.clone();
+            let to_log = to_log.clone();
+            let house = house.clone();
+            let view = view.clone();
+            let leases = leases.clone();
+            let metrics = metrics.clone();
+            let persist_tx = persist_tx.clone();
+            let heartbeats = heartbeats.clone();
+            tokio::spawn(async move {
+                let mut zoneupdate = zoneupdate_rx.lock().await;
+                let heartbeat = heartbeats.register("manager/zoneupdate");
+                let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
+                to_log
+                    .send(SysLog::new(format!("Spawned zoneupdate handler")))
+                    .await;
+                loop {
+                    let data = tokio::select! {
+                        Some(data) = zoneupdate.recv() => data,
+                        _ = beat.tick() => {
+                            heartbeat.beat();
+                            continue;
                         }
-                        if log_msg.is_some() {
-                            to_log.send(SysLog::new(log_msg.unwrap())).await;
+                        else => { break }
+                    };
+                    match data {
+                        ZoneUpdate::Water {
+                            id: water_id,
+                            settings,
+                            status,
+                        } => {
+                            let mut log_msg: Option<String> = None;
+                            let mut level = LogLevel::Info;
+                            // Bounded by the route's budget inside, so the abort path is not dropped
+                            let watered = heartbeat
+                                .beat_while(watering(water_id, settings, status, to_log.clone(), house.clone(), view.clone(), leases.clone()))
+                                .await;
+                            match watered {
+                                Ok( (true, msg) ) => {
+                                    metrics.watering(water_id, "ok");
+                                    log_msg = Some(msg);
+                                },
+                                Ok( (false, msg) ) => {
+                                    metrics.watering(water_id, "skipped");
+                                    log_msg = Some(msg);
+                                },
+                                Err(e) => {
+                                    let result = match e {
+                                        GrowError::Timeout(_) => "timeout",
+                                        _ => "failed",
+                                    };
+                                    metrics.watering(water_id, result);
+                                    level = LogLevel::Warning;
+                                    log_msg = Some(format!("Water zone {} failed: {}", &water_id, e));
+                                },
+                            }
+                            if log_msg.is_some() {
+                                to_log.send(SysLog::with_level(level, log_msg.unwrap()).source(&format!("water/{}", water_id))).await;
+                            }
+                            let _ = persist_tx.try_send(());
                         }
+                        ZoneUpdate::Tank { .. } => {}
+                        ZoneUpdate::Arm { .. } => {}
                     }
-                    ZoneUpdate::Tank { .. } => {}
-                    ZoneUpdate::Arm { .. } => {}
                 }
-            }
+            })
         });
+
+        self.supervisor.run(
+            vec![log_handler, btn_handler, zoneupdate_handler],
+            self.house.clone(),
+            self.ops_tx.syslog.clone(),
+        );
+
+        // Start heartbeat monitor, on by default
+        let heartbeat_settings = match HeartbeatSettings::load("grow-heartbeat.js") {
+            Ok(settings) => settings,
+            Err(e) => {
+                let msg = format!("Heartbeat settings: {}. Using defaults.", e);
+                let _ = self.ops_tx.syslog.send(SysLog::new(msg).source("house")).await;
+                HeartbeatSettings::default()
+            }
+        };
+        if heartbeat_settings.enabled {
+            let mut monitor = HeartbeatMonitor::new(heartbeat_settings);
+            monitor.run(
+                self.ops_tx.heartbeats.clone(),
+                self.house.clone(),
+                self.zone_tx.zonestatus.clone(),
+                self.ops_tx.syslog.clone(),
+            );
+            self.heartbeat = Some(monitor);
+        }
+    }
+
+    /// Write house state and active alerts to the state file
+    pub async fn save_state(&mut self) -> Result<(), GrowError> {
+        let mut state = self.house.lock().await.runtime_state();
+        if let Some(alerter) = &self.alerter {
+            state.alerts = alerter.saved();
+        }
+        state.save(STATE_FILE)
+    }
+
+    /// Safe state for the house, save state, stop the ops tasks and flush the logs
+    pub async fn shutdown(&mut self, deadline: Duration) -> ShutdownReport {
+        let started = std::time::Instant::now();
+        let remaining = || deadline.saturating_sub(started.elapsed());
+        // Runners stopped below are not to be restarted, nor reported as stalled
+        self.supervisor.stop();
+        if let Some(mut monitor) = self.heartbeat.take() {
+            monitor.stop().await;
+        }
+        if let Some(persist) = self.persist.take() {
+            persist.abort();
+        }
+        // Watering and menu sessions hold their own pump and arm handles, ended before
+        // the house stops the pumps and parks the arms
+        let mut report = self
+            .supervisor
+            .abort_tasks(&["manager/buttons", "manager/zoneupdate"], remaining())
+            .await;
+        let house_report = self.house.lock().await.shutdown(remaining()).await;
+        report.merge(house_report);
+        if let Err(e) = self.save_state().await {
+            report.fail("state", e.to_string());
+        }
+        if let Some(mut alerter) = self.alerter.take() {
+            alerter.stop();
+        }
+        if let Some(mut rules) = self.rules.take() {
+            rules.stop();
+        }
+        if let Some(calendar) = self.calendar.take() {
+            calendar.abort();
+        }
+        if let Some(mut xymon) = self.xymon.take() {
+            xymon.stop();
+        }
+        if let Some(mut server) = self.metrics_server.take() {
+            server.stop();
+        }
+        if let Err(e) = self.board.lock().await.shutdown() {
+            report.fail("board", e.to_string());
+        }
+        if let Some(log_flush) = self.log_flush.take() {
+            let (done, flushed) = oneshot::channel();
+            let result = match log_flush.send(done).await {
+                Ok(()) => timeout(Duration::from_secs(2), flushed).await.map_err(|_| "Timed out"),
+                Err(_) => Err("Log handler not running"),
+            };
+            if let Err(e) = result {
+                report.fail("log", format!("Flush: {}", e));
+            }
+        }
+        let log_report = self.supervisor.abort_tasks(&["manager/log"], remaining()).await;
+        report.merge(log_report);
+        report
     }
 
     pub async fn update_board(&mut self) {
-        let mut lock = self.house.lock().await;
-        let all_ds = lock.collect_display_status();
-        self.board.set(all_ds).await;
+        update_board(&self.board, &self.view).await;
     }
 
     pub async fn position_from_rc(
         &mut self,
         water_id: u8,
     ) -> Option<(i32, i32, i32)> {
-        let to_log = self.ops_tx.syslog.clone();
+        position_from_rc(
+            water_id,
+            self.house.clone(),
+            self.remote.clone(),
+            self.ops_tx.syslog.clone(),
+        )
+        .await
+    }
+    /// Jog the arm with remote control and store the position under `name`
+    pub async fn teach_from_rc(
+        &mut self,
+        arm_id: u8,
+        name: &str,
+    ) -> Result<(i32, i32, i32), String> {
+        teach_from_rc(
+            arm_id,
+            name,
+            self.house.clone(),
+            self.remote.clone(),
+            self.ops_tx.syslog.clone(),
+        )
+        .await
+    }
+    /// Open the text display menu, operated with remote control or buttons
+    pub async fn open_menu(&self) -> Result<(), GrowError> {
+        let stopped = || GrowError::TaskStopped(String::from("Button handler"));
+        match &self.menu_open {
+            Some(sender) => sender.send(()).await.map_err(|_| stopped()),
+            None => Err(stopped()),
+        }
+    }
 
-        let settings = self.house.lock().await.get_water_settings(water_id);
-        if settings.is_none() {
-            to_log
-                .send(SysLog::new(format!(
-                    "Set position failed; Water id:{} not found",
-                    &water_id
-                )))
+    pub fn log_filter(&self) -> Option<LogFilter> {
+        self.log_filter.as_ref().map(|sender| sender.borrow().clone())
+    }
+    pub fn set_log_filter(&self, filter: LogFilter) -> Result<(), GrowError> {
+        let stopped = || GrowError::TaskStopped(String::from("Log handler"));
+        match &self.log_filter {
+            Some(sender) => sender.send(filter).map_err(|_| stopped()),
+            None => Err(stopped()),
+        }
+    }
+    /// Set minimum level for a source prefix, `None` silences it
+    pub fn set_log_source(
+        &self,
+        source: &str,
+        level: Option<LogLevel>,
+    ) -> Result<(), GrowError> {
+        let mut filter = self.log_filter().unwrap_or_default();
+        filter.sources.insert(source.to_string(), level);
+        self.set_log_filter(filter)
+    }
+    /// Switch a source between silenced and `level`, returns whether it is now shown
+    pub fn toggle_log_source(&self, source: &str, level: LogLevel) -> Option<bool> {
+        let filter = self.log_filter()?;
+        let show = filter.effective(Some(source)).is_none();
+        self.set_log_source(source, if show { Some(level) } else { None }).ok()?;
+        Some(show)
+    }
+    /// Zone runners and manager tasks with their restarts and last failure
+    pub fn task_health(&self) -> Vec<TaskHealth> {
+        self.supervisor.health()
+    }
+
+    /// Stalled loops and House lock, `None` if the monitor is not running
+    pub fn controller_health(&self) -> Option<Health> {
+        self.heartbeat.as_ref().map(|monitor| monitor.health())
+    }
+
+    pub fn alerts(&self) -> Option<Vec<AlertInfo>> {
+        self.alerter.as_ref().map(|a| a.list())
+    }
+    /// Automation rules and whether they are active, `None` without grow-rules.js
+    pub fn rules(&self) -> Option<Vec<RuleInfo>> {
+        self.rules.as_ref().map(|r| r.list())
+    }
+    pub async fn alert_ack(&self, kind: ZoneKind, id: u8) -> Result<(), GrowError> {
+        self.alert_cmd(AlertCmd::Ack { kind, id }).await
+    }
+    pub async fn alert_snooze(
+        &self,
+        kind: ZoneKind,
+        id: u8,
+        duration: Duration,
+    ) -> Result<(), GrowError> {
+        self.alert_cmd(AlertCmd::Snooze { kind, id, duration }).await
+    }
+    async fn alert_cmd(&self, cmd: AlertCmd) -> Result<(), GrowError> {
+        let stopped = || GrowError::TaskStopped(String::from("Alerter"));
+        match &self.alerter {
+            Some(alerter) => alerter.cmd_sender().send(cmd).await.map_err(|_| stopped()),
+            None => Err(stopped()),
+        }
+    }
+
+    pub async fn blink(&mut self) -> (Result<(), Box<dyn Error>>) {
+        self.board
+            .lock()
+            .await
+            .blink_all(Duration::from_millis(500), Duration::from_secs(1));
+
+        Ok(())
+    }
+
+
+}
+
+/// Set the indicators from the zone statuses, never waiting on the House lock
+async fn update_board(board: &BoardMutex, view: &ZoneView) {
+    let all_ds = view.collect_display_status();
+    let _ = board.lock().await.set(all_ds).await;
+}
+
+/// Lease a pump for a manual run, preempting watering
+async fn manual_pump(
+    house: &HouseMutex,
+    id: u8,
+    holder: LeaseHolder,
+    duration: Duration,
+) -> Result<(Lease, pump::PumpHandle), GrowError> {
+    let lock = house.lock().await;
+    let pump = lock.pump_handle(id)?;
+    Ok((lock.leases().acquire(ZoneKind::Pump, id, holder, duration)?, pump))
+}
+
+/// Move arm with remote control and store the position for a water zone
+async fn position_from_rc(
+water_id: u8,
+house: HouseMutex,
+remote: RemoteMutex,
+to_log: SysLogTx,
+) -> Option<(i32, i32, i32)> {
+    let settings = house.lock().await.get_water_settings(water_id);
+    if settings.is_none() {
+        to_log
+            .send(SysLog::new(format!(
+                "Set position failed; Water id:{} not found",
+                &water_id
+            )))
+            .await;
+        return None;
+    }
+    let arm_id = settings.unwrap().position.arm_id;
+    let pos = jog_from_rc(arm_id, house.clone(), remote, to_log).await?;
+    house.lock().await.set_water_position(water_id, pos);
+    Some(pos)
+}
+
+/// Move arm with remote control and store the position by name in the arm's library
+async fn teach_from_rc(
+    arm_id: u8,
+    name: &str,
+    house: HouseMutex,
+    remote: RemoteMutex,
+    to_log: SysLogTx,
+) -> Result<(i32, i32, i32), String> {
+    jog_from_rc(arm_id, house.clone(), remote, to_log.clone())
+        .await
+        .ok_or_else(|| String::from("Position not confirmed"))?;
+    let taught = house.lock().await.teach_position(arm_id, name).map_err(|e| e.to_string());
+    let _ = to_log
+        .send(SysLog::new(format!("Teach position {}: {:?}", name, &taught)).source(&format!("arm/{}", arm_id)))
+        .await;
+    taught
+}
+
+/// Move arm with remote control until position is confirmed or cancelled
+async fn jog_from_rc(
+arm_id: u8,
+house: HouseMutex,
+remote: RemoteMutex,
+to_log: SysLogTx,
+) -> Option<(i32, i32, i32)> {
+    let mut to_arm: Option<broadcast::Sender<ArmCmd>> = None;
+    let leases = house.lock().await.leases();
+    for z in house.lock().await.zones() {
+        match z {
+            Zone::Arm { id, runner, .. } if id == &arm_id => {
+                // } if id == &zid => {  // Calling Arm with non-existing id (like 2) leads to interesting panics, look to make that more resilient later
+                to_arm = Some(runner.cmd_sender());
+            }
+            _ => {}
+        }
+    }
+    if to_arm.is_none() {
+        to_log
+            .send(SysLog::new(format!(
+                "Set position failed; Arm id:{} not found",
+                arm_id
+            )))
+            .await;
+        return None;
+    }
+    let to_arm = to_arm.unwrap();
+    // Watering is refused the arm until the session ends, sensors and console carry on
+    let lease = match leases.acquire(ZoneKind::Arm, arm_id, LeaseHolder::Remote, RC_LEASE) {
+        Ok(lease) => lease,
+        Err(e) => {
+            let _ = to_log
+                .send(SysLog::with_level(LogLevel::Warning, format!("Position finder: {}", e)).source(&format!("arm/{}", arm_id)))
                 .await;
             return None;
         }
-        let arm_id = settings.unwrap().position.arm_id;
+    };
 
-        let mut to_arm: Option<broadcast::Sender<ArmCmd>> = None;
-        for z in self.house.lock().await.zones() {
-            match z {
-                Zone::Arm { id, runner, .. } if id == &arm_id => {
-                    // } if id == &zid => {  // Calling Arm with non-existing id (like 2) leads to interesting panics, look to make that more resilient later
-                    to_arm = Some(runner.cmd_sender());
-                }
-                _ => {}
+    /// Init remote control
+    let (rc_tx, mut rc_rx) = mpsc::channel::<RcInput>(64);
+    let cancel = CancellationToken::new();
+    let guard = cancel.clone().drop_guard();
+    match remote.lock().await.init(rc_tx, cancel.clone()).await {
+        Ok(_) => {},
+        Err(e) => {
+            eprintln!("Remote init error: {}", e);
+            // to_log
+            // .send(SysLog::new(format!(
+            //     "Remote init error: {}", e 
+            // )))
+            // .await;
+        }
+    }
+    let to_log_finder = to_log.clone();
+
+    // Move: to_log, to_arm, rc_rx, lease
+    let position_finder = tokio::task::spawn(async move {
+        to_log_finder
+            .send(SysLog::new(format!("Spawned position finder")))
+            .await;
+        let exit = {
+            loop {
+                tokio::select! {
+                    _ = lease.lost() => {
+                        break RcModeExit::LeaseLost;
+                    }
+                    Some(data) = rc_rx.recv() => {
+                        match data {
+                            RcInput::LeftUp | RcInput::RightUp => {
+                                to_arm.send(ArmCmd::StopX);
+                            }
+                            RcInput::DownUp | RcInput::UpUp => {
+                                to_arm.send(ArmCmd::StopY);
+                            }
+                            RcInput::Left => {
+                                to_arm.send(ArmCmd::StartX { speed: -20 });
+                            }
+                            RcInput::Right => {
+                                to_arm.send(ArmCmd::StartX { speed: 20 });
+                            }
+                            RcInput::Up => {
+                                to_arm.send(ArmCmd::StartY { speed: 80 });
+                            }
+                            RcInput::Down => {
+                                to_arm.send(ArmCmd::StartY { speed: -80 });
+                            }
+                            RcInput::Confirm => {
+                                break RcModeExit::Confirm;
+                            }
+                            RcInput::Back => {
+                                break RcModeExit::Cancel;
+                            }
+                            RcInput::Mode => {
+                            }
+                            RcInput::Exit => {
+                                break RcModeExit::Cancel;
+                            }
+                            RcInput::ConfirmUp => {
+                            }
+                            RcInput::DownUp | RcInput::UpUp => {
+                            }
+                            RcInput::BackUp => {
+                            }
+                            RcInput::ModeUp => {
+                            }
+                        }
+                    }
+                    else => { break RcModeExit::ElseExit; }
+                };
             }
+        };
+        // Axes started by the remote keep running until stopped, stop before the lease goes
+        let _ = to_arm.send(ArmCmd::Stop);
+        exit
+    });
+    let exit_kind = match position_finder.await {
+        Ok(exitmode) => Some(exitmode),
+        Err(e) => {
+            eprintln!("Position finder error: {}", e);
+            None
         }
-        if to_arm.is_none() {
+    };
+    println!("RC mode exit kind: {:?}", &exit_kind);
+    to_log
+        .send(SysLog::new(
+            format!("Exit position finder")))
+        .await;
+
+    // Get current position from house after exit:
+    let pos = match house.lock().await.arm_position(arm_id) {
+        Ok(pos) => pos,
+        Err(e) => {
             to_log
-                .send(SysLog::new(format!(
-                    "Set position failed; Arm id:{} not found",
-                    arm_id
-                )))
+                .send(SysLog::with_level(LogLevel::Warning, format!("Position finder: {}", e)).source(&format!("arm/{}", arm_id)))
                 .await;
             return None;
         }
-        let to_arm = to_arm.unwrap();
+    };
 
-        /// Init remote control
-        let (rc_tx, mut rc_rx) = mpsc::channel::<RcInput>(64);
-        let cancel = CancellationToken::new();
-        let guard = cancel.clone().drop_guard();
-        match self.remote.init(rc_tx, cancel.clone()).await {
-            Ok(_) => {},
-            Err(e) => {
-                eprintln!("Remote init error: {}", e);
-                // to_log
-                // .send(SysLog::new(format!(
-                //     "Remote init error: {}", e 
-                // )))
-                // .await;
-            }
+    /// Exit mode from RC-loop determines what to do next
+    match exit_kind {
+        Some(RcModeExit::Confirm) => {
+            to_log
+                .send(SysLog::new(
+                    format!("Selected position: {:?}", &pos)))
+                .await;
+            Some(pos)
         }
-        let house = self.house.clone();
-
-        // Move: to_log, to_arm, rc_rx, house
-        let position_finder = tokio::task::spawn(async move {
+        Some(RcModeExit::Cancel) => {
             to_log
-                .send(SysLog::new(format!("Spawned position finder")))
+                .send(SysLog::new(
+                    format!("Position finder cancelled: {:?}", &pos)))
                 .await;
-            {
-                // Lock house during this to queue watering (and other future) commands
-                let mut lock = house.lock().await;
-                loop {
-                    tokio::select! {
-                        Some(data) = rc_rx.recv() => {
-                            match data {
-                                RcInput::LeftUp | RcInput::RightUp => {
-                                    to_arm.send(ArmCmd::StopX);
-                                }
-                                RcInput::DownUp | RcInput::UpUp => {
-                                    to_arm.send(ArmCmd::StopY);
-                                }
-                                RcInput::Left => {
-                                    to_arm.send(ArmCmd::StartX { speed: -20 });
-                                }
-                                RcInput::Right => {
-                                    to_arm.send(ArmCmd::StartX { speed: 20 });
-                                }
-                                RcInput::Up => {
-                                    to_arm.send(ArmCmd::StartY { speed: 80 });
-                                }
-                                RcInput::Down => {
-                                    to_arm.send(ArmCmd::StartY { speed: -80 });
-                                }
-                                RcInput::Confirm => {
-                                    break RcModeExit::Confirm;
-                                }
-                                RcInput::Back => {
-                                    break RcModeExit::Cancel;
-                                }
-                                RcInput::Mode => {
-                                }
-                                RcInput::Exit => {
-                                    break RcModeExit::Cancel;
-                                }
-                                RcInput::ConfirmUp => {
-                                }
-                                RcInput::DownUp | RcInput::UpUp => {
-                                }
-                                RcInput::BackUp => {
-                                }
-                                RcInput::ModeUp => {
-                                }
-                            }
-                        }
-                        else => { break RcModeExit::ElseExit; }
-                    };
-                }
-            }
-        });
-        let exit_kind = match position_finder.await {
-            Ok(exitmode) => Some(exitmode),
-            Err(e) => {
-                eprintln!("Position finder error: {}", e);
-                None
-            }
+            None
+        }
+        Some(RcModeExit::SwitchFromPositionMode) => {
+            to_log
+                .send(SysLog::new(
+                    format!("Position finder mode switch")))
+                .await;
+            None
+        }
+        Some(RcModeExit::SwitchFromOpsMode) => {
+            to_log
+                .send(SysLog::new(
+                    format!("Position finder unexpected exit (wrong mode)")))
+                .await;
+            None
+        }
+        Some(RcModeExit::LeaseLost) => {
+            to_log
+                .send(SysLog::with_level(LogLevel::Warning, format!("Position finder lease expired: {:?}", &pos)).source(&format!("arm/{}", arm_id)))
+                .await;
+            None
+        }
+        Some(RcModeExit::ElseExit) => {
+            to_log
+                .send(SysLog::new(
+                    format!("Position finder unexpected exit (select else)")))
+                .await;
+            None
+        }
+        None => None,
+    }
+}
+
+/// Start remote control input for a menu session in the background, the remote may take a while to connect
+fn menu_remote(remote: RemoteMutex, rc_tx: mpsc::Sender<RcInput>) -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let remote_cancel = cancel.clone();
+    tokio::spawn(async move {
+        let mut lock = remote.lock().await;
+        // Give up waiting for the remote when the session ends
+        let result = tokio::select! {
+            result = lock.init(rc_tx, remote_cancel.clone()) => result.map_err(|e| e.to_string()),
+            _ = remote_cancel.cancelled() => Ok(()),
         };
-        println!("RC mode exit kind: {:?}", &exit_kind);
-        self.ops_tx
-            .syslog
-            .send(SysLog::new(
-                format!("Exit position finder")))
-            .await;
+        if let Err(e) = result {
+            eprintln!("Remote init error: {}", e);
+        }
+    });
+    cancel
+}
 
-        // Get current position from house after exit:
-        let pos: (i32, i32, i32);
-        {
-            let mut house = self.house.lock().await;
-            pos = house
-                .arm_position(arm_id)
-                .expect("Error getting arm position from house");
-        }
-
-        /// Exit mode from RC-loop determines what to do next
-        match exit_kind {
-            Some(RcModeExit::Confirm) => {
-                self.ops_tx
-                    .syslog
-                    .send(SysLog::new(
-                        format!("Selected position: {:?}", &pos)))
-                    .await;
-                self.house.lock().await.set_water_position(water_id, pos);
-                Some(pos)
-            }
-            Some(RcModeExit::Cancel) => {
-                self.ops_tx
-                    .syslog
-                    .send(SysLog::new(
-                        format!("Position finder cancelled: {:?}", &pos)))
-                    .await;
+/// Operate zones from the text display menu until the menu is exited
+async fn menu_session(
+    house: HouseMutex,
+    display: Arc<dyn TextDisplay>,
+    remote: RemoteMutex,
+    from_buttons: &mut broadcast::Receiver<ButtonInput>,
+    heartbeat: &Heartbeat,
+    to_log: SysLogTx,
+) {
+    let _ = to_log
+        .send(SysLog::new(String::from("Menu opened")).source("menu"))
+        .await;
+    let mut menu = Menu::new();
+    let (rc_tx, mut rc_rx) = mpsc::channel::<RcInput>(64);
+    let mut cancel = menu_remote(remote.clone(), rc_tx.clone());
+    let mut refresh = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        let input = tokio::select! {
+            Some(data) = rc_rx.recv() => MenuInput::from_rc(&data),
+            Ok(data) = from_buttons.recv() => MenuInput::from_button(&data),
+            _ = refresh.tick() => {
+                heartbeat.beat();
                 None
             }
-            Some(RcModeExit::SwitchFromPositionMode) => {
-                self.ops_tx
-                    .syslog
-                    .send(SysLog::new(
-                        format!("Position finder mode switch")))
-                    .await;
-                None
+        };
+        let action = match input {
+            Some(input) => menu.handle(input, &mut *house.lock().await),
+            None => MenuAction::None,
+        };
+        let result: Result<(), String> = match action {
+            MenuAction::None => Ok(()),
+            MenuAction::Exit => break,
+            MenuAction::PumpRun { id, secs } => match manual_pump(&house, id, LeaseHolder::Panel, Duration::from_secs(secs as u64)).await {
+                Ok((lease, pump)) => {
+                    let ran = pump.run_for_secs(secs).await.map_err(|e| e.to_string());
+                    if ran.is_ok() {
+                        lease.keep();
+                    }
+                    ran
+                }
+                Err(e) => Err(e.to_string()),
+            },
+            MenuAction::PumpStop { id } => {
+                let pump = house.lock().await.pump_handle(id);
+                match pump {
+                    Ok(pump) => pump.stop().await.map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
             }
-            Some(RcModeExit::SwitchFromOpsMode) => {
-                self.ops_tx
-                    .syslog
-                    .send(SysLog::new(
-                        format!("Position finder unexpected exit (wrong mode)")))
-                    .await;
-                None
+            MenuAction::Lamp { id, state } => {
+                house.lock().await.set_lamp_state(id, state).map_err(|e| e.to_string())
             }
-            Some(RcModeExit::ElseExit) => {
-                self.ops_tx
-                    .syslog
-                    .send(SysLog::new(
-                        format!("Position finder unexpected exit (select else)")))
-                    .await;
-                None
+            MenuAction::SetThreshold { id, threshold, value } => {
+                house.lock().await.set_threshold(id, threshold, value).map_err(|e| e.to_string())
             }
-            None => None,
-        }
-    }
-
-    pub fn zonelog_toggle(&self) -> Option<bool> {
-        let mut r: Option<bool> = None;
-        match &self.zonelog_enable {
-            Some(sender) => {
-                if *sender.borrow() {
-                    sender.send(false);
-                    r = Some(false);
-                } else {
-                    sender.send(true);
-                    r = Some(true);
+            MenuAction::GotoPosition { arm_id, index } => {
+                let planned = {
+                    let mut lock = house.lock().await;
+                    let leased = lock.leases().acquire(ZoneKind::Arm, arm_id, LeaseHolder::Panel, MANUAL_LEASE).map_err(|e| e.to_string());
+                    match lock.arm_positions(arm_id).and_then(|p| p.into_keys().nth(index)) {
+                        Some(name) => leased.and_then(|l| lock.arm_plan_named(arm_id, &name).map(|m| (l, m)).map_err(|e| e.to_string())),
+                        None => Err(String::from("Position not found")),
+                    }
+                };
+                match planned {
+                    Ok((_lease, arm_move)) => heartbeat.beat_while(arm_move.run(arm::STEP_TIMEOUT)).await.map(|_| ()).map_err(|e| e.to_string()),
+                    Err(e) => Err(e),
                 }
             }
-            None => {}
-        }
-
-        r
-    }
-    pub fn statuslog_toggle(&self) -> Option<bool> {
-        let mut r: Option<bool> = None;
-        match &self.status_enable {
-            Some(sender) => {
-                if *sender.borrow() {
-                    sender.send(false);
-                    r = Some(false);
-                } else {
-                    sender.send(true);
-                    r = Some(true);
+            MenuAction::TeachPosition { arm_id } => {
+                // Name new positions pos-1, pos-2.., rename in config if needed
+                let taken = house.lock().await.arm_positions(arm_id).unwrap_or_default();
+                let name = (1..)
+                    .map(|n| format!("pos-{}", n))
+                    .find(|n| !taken.contains_key(n))
+                    .unwrap_or_default();
+                cancel.cancel();
+                let _ = display.render(Some(MenuView {
+                    title: format!("Teach {}", name),
+                    lines: vec![String::from("Move with remote"), String::from("Confirm or back")],
+                    selected: None,
+                }));
+                // Held for up to the remote control lease, the buttons loop keeps beating
+                let taught = heartbeat.beat_while(teach_from_rc(arm_id, &name, house.clone(), remote.clone(), to_log.clone())).await;
+                cancel = menu_remote(remote.clone(), rc_tx.clone());
+                taught.map(|_| ())
+            }
+            MenuAction::SetPosition { water_id } => {
+                // Position mode takes over the remote control
+                cancel.cancel();
+                let _ = display.render(Some(MenuView {
+                    title: format!("Water {}", water_id),
+                    lines: vec![String::from("Move with remote"), String::from("Confirm or back")],
+                    selected: None,
+                }));
+                let pos = heartbeat.beat_while(position_from_rc(water_id, house.clone(), remote.clone(), to_log.clone())).await;
+                cancel = menu_remote(remote.clone(), rc_tx.clone());
+                match pos {
+                    Some(_) => Ok(()),
+                    None => Err(String::from("Position not set")),
                 }
             }
-            None => {}
+        };
+        match result {
+            Ok(()) if action != MenuAction::None => {
+                let _ = to_log
+                    .send(SysLog::new(format!("Menu: {:?}", action)).source("menu"))
+                    .await;
+            }
+            Ok(()) => {}
+            Err(e) => {
+                let _ = to_log
+                    .send(SysLog::with_level(LogLevel::Warning, format!("Menu: {:?} failed: {}", action, e)).source("menu"))
+                    .await;
+            }
+        }
+        let view = menu.view(&mut *house.lock().await);
+        if let Err(e) = display.render(Some(view)) {
+            eprintln!("Menu render error: {}", e);
         }
-
-        r
-    }
-    pub async fn blink(&mut self) -> (Result<(), Box<dyn Error>>) {
-        self.board
-            .blink_all(Duration::from_millis(500), Duration::from_secs(1));
-
-        Ok(())
     }
-
-
+    cancel.cancel();
+    let _ = display.render(None);
+    let _ = to_log
+        .send(SysLog::new(String::from("Menu closed")).source("menu"))
+        .await;
 }
 
 /// Perform watering
@@ -491,122 +1164,207 @@ async fn watering(
     status: Arc<RwLock<crate::zone::water::Status>>,
     to_syslog: super::SysLogTx,
     house: HouseMutex,
-// ) -> Result<(), Box<dyn Error>> {
-) -> Result<( bool,String ), Box<dyn Error>> {
+    view: ZoneView,
+    leases: Leases,
+) -> Result<( bool,String ), GrowError> {
     // println!("fn watering start");
     let moisture = status.read().moisture_level;
     if moisture.is_none() {
-        return Err(Box::new(WateringError::new(&format!("Moisture level not found for {}.", water_id))))
+        return Err(GrowError::hardware_msg(format!("Moisture level not found for water zone {}", water_id)))
     }
     let moisture = moisture.unwrap();
     if moisture > settings.moisture_limit_water {
         return (Ok( (false, format!("Water {}; moist {} above limit {}.", water_id, moisture, settings.moisture_limit_water)) ))
     }
+    let postponed = house.lock().await.rule_effects().watering_postponed(water_id);
+    if let Some(rule) = postponed {
+        return Ok( (false, format!("Water {}; moist {} below limit {}, postponed by rule {}.", water_id, moisture, settings.moisture_limit_water, rule)) )
+    }
     to_syslog.send(SysLog::new(format!("Water {}; moist {} below limit {}. Init watering.", water_id, moisture, settings.moisture_limit_water))).await;
 
     // Check tank status
-    let tank_status = house
-        .lock()
-        .await
-        .get_displaystatus(ZoneKind::Tank, settings.tank_id);
-    if tank_status.is_none() {
-        return Err(Box::new(ZoneError::new(&format!(
-            "Water zone {} failed: Tank {} not found",
-            &water_id, &settings.tank_id
-        ))))
-    }
-    let tank_status = tank_status.unwrap();
+    let tank_status = view
+        .display_status(ZoneKind::Tank, settings.tank_id)
+        .ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Tank, id: settings.tank_id })?;
     if tank_status.indicator == Indicator::Red {
-        return Err(Box::new(WateringError::new(&format!(
-            "Water zone {} failed: Tank {} empty",
-            &water_id, &settings.tank_id))))
+        return Err(GrowError::Interlock {
+            kind: ZoneKind::Tank,
+            id: settings.tank_id,
+            reason: tank_status.msg.unwrap_or(String::from("Tank not ok")),
+        })
     }
 
-    // TODO: Check pump status
+    // Out of range positions and unknown names fail here, before reaching the arm
+    let arm_id = settings.position.arm_id;
+    let (route, pump, arm) = {
+        let mut lock = house.lock().await;
+        let route = lock.water_route(water_id)?;
+        let pump = lock.pump_handle(settings.pump_id)?;
+        let arm = lock
+            .arm_handle(arm_id)
+            .ok_or(GrowError::ZoneNotFound { kind: ZoneKind::Arm, id: arm_id })?;
+        (route, pump, arm)
+    };
 
-    // Get Arm status and control_rx
-    let movement = settings.position;
-    let mut arm_status: Option<
-        Arc<RwLock<crate::zone::arm::Status>>,
-    > = None;
-    let mut arm_control_rx: Option<
-        broadcast::Receiver<ArmState>,
-    > = None;
-    for z in house.lock().await.zones() {
-        match z {
-            Zone::Arm {
-                id: arm_id , runner, status, ..
-            } if arm_id == &movement.arm_id => {
-                arm_status = Some(status.clone());
-                arm_control_rx = Some(
-                    runner
-                        .control_feedback_sender()
-                        .subscribe(),
-                );
-            }
-            _ => {}
-        }
-    }
-    if arm_status.is_none() | arm_control_rx.is_none() {
-        return Err(Box::new(ZoneError::new(&format!(
-            "Water zone {} failed: Arm {} not found",
-            &water_id, &movement.arm_id
-        ))))
+    // Refuse before moving the arm if the pump is locked out, e.g. by an empty tank
+    let longest = route.iter().map(|step| step.pump_time).max();
+    pump.check(longest)?;
+
+    if arm.mode() == ArmMode::Fault {
+        return Err(GrowError::Interlock { kind: ZoneKind::Arm, id: arm_id, reason: String::from("Arm in fault") })
     }
-    let arm_status = arm_status.unwrap();
-    let mut arm_control_rx = arm_control_rx.unwrap();
 
-    // TODO: Check arm status        
+    // Manual use of the arm or pump, e.g. from the console, refuses watering.
+    // The leases outlast the route so a timed out cycle can still stop the pump and park.
+    let budget = route_budget(&route);
+    let holder = LeaseHolder::Watering(water_id);
+    let park = PARK_TIME * arm::PLAN_STEPS;
+    let arm_lease = leases.acquire(ZoneKind::Arm, arm_id, holder, budget + park)?;
+    let pump_lease = leases.acquire(ZoneKind::Pump, settings.pump_id, holder, budget + park)?;
 
-    /// Move arm, try 3 times to get within acceptable delta
-    let mut tries = 0u8;
-    while tries < 3 {
-        println!("Watering: moving arm - try {}", &tries);
-        let _ = house.lock().await.arm_goto(
-            movement.arm_id,
-            movement.x,
-            movement.y,
-            movement.z,
-        ).await; // TODO check result
-        
-        // Wait until arm has moved
-        while let Ok(arm_data) = arm_control_rx.recv().await
-        {
-            match arm_data {
-                ArmState::Busy => {}
-                ArmState::Idle => { break; }
-            }
+    let result = match timeout(budget, run_route(water_id, (&pump, &pump_lease), &route, (&arm, &arm_lease), &house, &to_syslog)).await {
+        Ok(result) => result,
+        Err(_) => Err(GrowError::Timeout(format!("Water zone {} route over {:?}", water_id, budget))),
+    };
+    if let Err(e) = result {
+        // Abort: pump off, arm out of the way, left to a holder that preempted us
+        if pump_lease.check().is_ok() {
+            let _ = pump.stop().await;
         }
-        // Confirm arm position within acceptable delta
-        let confirmed = house
-            .lock()
-            .await
-            .confirm_arm_position(water_id, 5)
-            .unwrap();
+        // Stops a move left behind by the timeout, or a hold, so the park is not refused
+        if arm_lease.check().is_ok() {
+            arm.cancel().await;
+        }
+        let parked = match arm_lease.check() {
+            Ok(()) => {
+                let parked = house.lock().await.arm_plan_park(arm_id);
+                match parked {
+                    Ok(arm_move) => arm_move.run(PARK_TIME).await,
+                    Err(e) => Err(e),
+                }
+            }
+            Err(lost) => Err(lost),
+        };
         to_syslog
-            .send(SysLog::new(format!(
-                "Confirm position: {:?}",
-                confirmed
-            )))
+            .send(SysLog::with_level(LogLevel::Warning, format!(
+                "Water zone {} aborted, arm parked: {:?}",
+                water_id, parked.map_err(|e| e.to_string())
+            )).source(&format!("water/{}", water_id)))
             .await;
+        return Err(e)
+    }
+    Ok( (true, format!(
+        "Water zone {} ok, {} waypoint(s)",
+        water_id, route.len())) )
+}
+
+/// Time for a route: its pump times, every try of a planned move at each waypoint,
+/// and the line steps when pumping on the way
+fn route_budget(route: &[RouteStep]) -> Duration {
+    let mut from: Option<(i32, i32, i32)> = None;
+    let mut budget = Duration::ZERO;
+    for step in route {
+        let to = (step.position.x, step.position.y, step.position.z);
+        let line = match (from, step.pump_on_way) {
+            (Some(from), true) => arm::line_steps(from, to) as u32,
+            _ => 0,
+        };
+        budget += step.pump_time + arm::STEP_TIMEOUT * (MOVE_TRIES * arm::PLAN_STEPS + line);
+        from = Some(to);
+    }
+    budget
+}
 
-        if confirmed.0 {
-            break;
-        }
-        tries += 1;
-    }
-    if tries < 3 {
-        let _ = // TODO check result
-            house.lock().await.pump_run(settings.pump_id).await; 
-        sleep(settings.pump_time).await;
-        let _ = // TODO check result
-            house.lock().await.pump_stop(settings.pump_id).await; 
-        return Ok( (true, format!(
-            "Water zone {} ok",
-            water_id)) )
-    } else {
-        return Err(Box::new(WateringError::new(&format!(
-            "Water zone {} failed, couldn't confirm position", water_id
-        ))))
+/// Visit waypoints in order, confirming position and pumping at each.
+/// The House lock is only taken to plan and confirm moves, not while moving or pumping.
+async fn run_route(
+    water_id: u8,
+    (pump, pump_lease): (&pump::PumpHandle, &Lease),
+    route: &[RouteStep],
+    (arm, arm_lease): (&ArmHandle, &Lease),
+    house: &HouseMutex,
+    to_syslog: &SysLogTx,
+) -> Result<(), GrowError> {
+    for (i, step) in route.iter().enumerate() {
+        arm_lease.check()?;
+        pump_lease.check()?;
+        let movement = step.position;
+        let target = (movement.x, movement.y, movement.z);
+        if step.pump_on_way & (i > 0) {
+            // Water along the line from the previous waypoint
+            let arm_move = house
+                .lock()
+                .await
+                .arm_plan_line(movement.arm_id, movement.x, movement.y, movement.z)?;
+            pump.run().await?;
+            let moved = arm_move.run(arm::STEP_TIMEOUT).await;
+            pump_lease.check()?;
+            pump.stop().await?;
+            moved?;
+        }
+
+        /// Move arm, try 3 times to get within acceptable delta
+        let mut tries = 0;
+        let mut position = arm.position();
+        while tries < MOVE_TRIES {
+            println!("Watering: moving arm - try {}", &tries);
+            let arm_move = house
+                .lock()
+                .await
+                .arm_plan(movement.arm_id, movement.x, movement.y, movement.z)?;
+            if let Err(e) = arm_move.run(arm::STEP_TIMEOUT).await {
+                to_syslog
+                    .send(SysLog::with_level(LogLevel::Warning, format!(
+                        "Water zone {} arm move: {}",
+                        water_id, e
+                    )).source(&format!("water/{}", water_id)))
+                    .await;
+                // A stalled or timed out arm needs homing, retrying won't help
+                if arm.mode() == ArmMode::Fault {
+                    return Err(e);
+                }
+            }
+            // Confirm arm position within acceptable delta
+            let confirmed = house
+                .lock()
+                .await
+                .confirm_arm_at(movement.arm_id, target, 5);
+            to_syslog
+                .send(SysLog::new(format!(
+                    "Confirm position: {:?}",
+                    confirmed
+                )))
+                .await;
+
+            if confirmed.is_ok_and(|c| c.0) {
+                break;
+            }
+            position = arm.position();
+            tries += 1;
+        }
+        if tries >= MOVE_TRIES {
+            return Err(GrowError::PositionNotConfirmed { arm_id: movement.arm_id, target, position });
+        }
+        if step.pump_time.is_zero() {
+            continue;
+        }
+        // Keep the arm in place while watering
+        arm.hold().await;
+        let pumped = pump.run().await;
+        if pumped.is_ok() {
+            tokio::select! {
+                _ = sleep(step.pump_time) => {}
+                _ = pump_lease.lost() => {}
+            }
+        }
+        // A holder that preempted us decides when the pump stops
+        let leased = pump_lease.check();
+        let stopped = match leased {
+            Ok(()) => pump.stop().await,
+            Err(_) => Ok(()),
+        };
+        arm.release().await;
+        pumped.and(leased).and(stopped)?;
     }
+    Ok(())
 }
@@ -0,0 +1,348 @@
+use crate::zone::light::LampState;
+use crate::zone::{Threshold, ZoneDisplay, ZoneKind};
+use crate::House;
+use super::io::ButtonInput;
+use super::remote::RcInput;
+
+/// Pump run time when started from the menu
+pub const MENU_PUMP_SECS: u16 = 5;
+
+/// Navigation input, from remote control or buttons
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Back,
+    Mode,
+}
+impl MenuInput {
+    /// Key presses navigate, key releases are ignored
+    pub fn from_rc(input: &RcInput) -> Option<Self> {
+        match input {
+            RcInput::Up => Some(MenuInput::Up),
+            RcInput::Down => Some(MenuInput::Down),
+            RcInput::Left => Some(MenuInput::Left),
+            RcInput::Right => Some(MenuInput::Right),
+            RcInput::Confirm => Some(MenuInput::Confirm),
+            RcInput::Back => Some(MenuInput::Back),
+            RcInput::Mode => Some(MenuInput::Mode),
+            _ => None,
+        }
+    }
+    /// With two buttons, one steps to the next line and two selects it
+    pub fn from_button(input: &ButtonInput) -> Option<Self> {
+        match input {
+            ButtonInput::OneDown => Some(MenuInput::Down),
+            ButtonInput::TwoDown => Some(MenuInput::Confirm),
+            _ => None,
+        }
+    }
+}
+
+/// A menu page as shown on the text display
+#[derive(Clone, Debug, PartialEq)]
+pub struct MenuView {
+    pub title: String,
+    pub lines: Vec<String>,
+    pub selected: Option<usize>,
+}
+impl MenuView {
+    /// Lines that fit in `rows`, scrolled to keep the selected line visible and marked
+    pub fn window(&self, rows: usize) -> Vec<String> {
+        let first = match self.selected {
+            Some(selected) if selected >= rows => selected + 1 - rows,
+            _ => 0,
+        };
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(rows)
+            .map(|(i, line)| match self.selected == Some(i) {
+                true => format!(">{}", line),
+                false => format!(" {}", line),
+            })
+            .collect()
+    }
+}
+
+/// What the menu asks the house to do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuAction {
+    None,
+    PumpRun { id: u8, secs: u16 },
+    PumpStop { id: u8 },
+    Lamp { id: u8, state: LampState },
+    SetPosition { water_id: u8 },
+    /// Move to the named position at `index` in the arm's library
+    GotoPosition { arm_id: u8, index: usize },
+    /// Jog with remote control and store as a new named position
+    TeachPosition { arm_id: u8 },
+    SetThreshold { id: u8, threshold: Threshold, value: f64 },
+    Exit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Item {
+    PumpRun,
+    PumpStop,
+    LampOn,
+    LampOff,
+    SetPosition,
+    Goto(usize),
+    Teach,
+    Edit(Threshold),
+    Back,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EditItem {
+    Increase,
+    Decrease,
+    Save,
+    Back,
+}
+const EDIT_ITEMS: [EditItem; 4] = [EditItem::Increase, EditItem::Decrease, EditItem::Save, EditItem::Back];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Screen {
+    Zones { cursor: usize },
+    Zone { kind: ZoneKind, id: u8, cursor: usize },
+    Edit { kind: ZoneKind, id: u8, threshold: Threshold, value: f64, cursor: usize },
+}
+
+/// Menu state: zone list, zone page with readings and actions, and threshold editor
+#[derive(Debug)]
+pub struct Menu {
+    screen: Screen,
+}
+impl Default for Menu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Menu {
+    pub fn new() -> Self {
+        Self { screen: Screen::Zones { cursor: 0 } }
+    }
+
+    fn zones(house: &mut House) -> Vec<ZoneDisplay> {
+        let mut zones = house.collect_display_status();
+        zones.sort_by_key(|zd| zd.kind());
+        zones
+    }
+
+    fn items(kind: ZoneKind, id: u8, house: &mut House) -> Vec<Item> {
+        let mut items = match kind {
+            ZoneKind::Pump => vec![Item::PumpRun, Item::PumpStop],
+            ZoneKind::Light => vec![Item::LampOn, Item::LampOff],
+            ZoneKind::Water => vec![Item::SetPosition],
+            ZoneKind::Arm => {
+                let count = house.arm_positions(id).map(|p| p.len()).unwrap_or(0);
+                let mut items: Vec<Item> = (0..count).map(Item::Goto).collect();
+                items.push(Item::Teach);
+                items
+            }
+            _ => Vec::new(),
+        };
+        items.extend(Threshold::for_kind(kind).iter().map(|t| Item::Edit(*t)));
+        items.push(Item::Back);
+        items
+    }
+
+    fn step(cursor: usize, len: usize, input: MenuInput) -> usize {
+        match input {
+            MenuInput::Up => (cursor + len - 1) % len,
+            MenuInput::Down => (cursor + 1) % len,
+            _ => cursor,
+        }
+    }
+
+    pub fn handle(&mut self, input: MenuInput, house: &mut House) -> MenuAction {
+        if input == MenuInput::Mode {
+            return MenuAction::Exit;
+        }
+        match self.screen {
+            Screen::Zones { cursor } => {
+                let zones = Self::zones(house);
+                // Last line is exit
+                let len = zones.len() + 1;
+                let cursor = cursor.min(len - 1);
+                match input {
+                    MenuInput::Up | MenuInput::Down => {
+                        self.screen = Screen::Zones { cursor: Self::step(cursor, len, input) };
+                    }
+                    MenuInput::Confirm | MenuInput::Right => match zones.get(cursor) {
+                        Some(zd) => {
+                            let (kind, id) = zd.kind();
+                            self.screen = Screen::Zone { kind, id, cursor: 0 };
+                        }
+                        None => return MenuAction::Exit,
+                    },
+                    MenuInput::Back | MenuInput::Left => return MenuAction::Exit,
+                    MenuInput::Mode => {}
+                }
+                MenuAction::None
+            }
+            Screen::Zone { kind, id, cursor } => {
+                let items = Self::items(kind, id, house);
+                // Items change with the zone, e.g. taught positions or a removed zone
+                let cursor = cursor.min(items.len() - 1);
+                let back = Screen::Zones {
+                    cursor: Self::zones(house)
+                        .iter()
+                        .position(|zd| zd.kind() == (kind, id))
+                        .unwrap_or(0),
+                };
+                match input {
+                    MenuInput::Up | MenuInput::Down => {
+                        self.screen = Screen::Zone { kind, id, cursor: Self::step(cursor, items.len(), input) };
+                        MenuAction::None
+                    }
+                    MenuInput::Back | MenuInput::Left => {
+                        self.screen = back;
+                        MenuAction::None
+                    }
+                    MenuInput::Confirm | MenuInput::Right => match items.get(cursor).copied() {
+                        Some(Item::PumpRun) => MenuAction::PumpRun { id, secs: MENU_PUMP_SECS },
+                        Some(Item::PumpStop) => MenuAction::PumpStop { id },
+                        Some(Item::LampOn) => MenuAction::Lamp { id, state: LampState::On },
+                        Some(Item::LampOff) => MenuAction::Lamp { id, state: LampState::Off },
+                        Some(Item::SetPosition) => MenuAction::SetPosition { water_id: id },
+                        Some(Item::Goto(index)) => MenuAction::GotoPosition { arm_id: id, index },
+                        Some(Item::Teach) => MenuAction::TeachPosition { arm_id: id },
+                        Some(Item::Edit(threshold)) => {
+                            if let Some(value) = house.get_threshold(id, threshold) {
+                                self.screen = Screen::Edit { kind, id, threshold, value, cursor: 0 };
+                            }
+                            MenuAction::None
+                        }
+                        Some(Item::Back) | None => {
+                            self.screen = back;
+                            MenuAction::None
+                        }
+                    },
+                    MenuInput::Mode => MenuAction::None,
+                }
+            }
+            Screen::Edit { kind, id, threshold, value, cursor } => {
+                let back = Screen::Zone {
+                    kind,
+                    id,
+                    cursor: Self::items(kind, id, house)
+                        .iter()
+                        .position(|i| i == &Item::Edit(threshold))
+                        .unwrap_or(0),
+                };
+                let (min, max) = house.threshold_bounds(id, threshold).unwrap_or(threshold.range());
+                let increase = Screen::Edit { kind, id, threshold, value: (value + threshold.step()).clamp(min, max.max(min)), cursor };
+                let decrease = Screen::Edit { kind, id, threshold, value: (value - threshold.step()).clamp(min, max.max(min)), cursor };
+                match input {
+                    MenuInput::Up | MenuInput::Down => {
+                        self.screen = Screen::Edit { kind, id, threshold, value, cursor: Self::step(cursor, EDIT_ITEMS.len(), input) };
+                    }
+                    MenuInput::Right => self.screen = increase,
+                    MenuInput::Left => self.screen = decrease,
+                    MenuInput::Back => self.screen = back,
+                    MenuInput::Confirm => match EDIT_ITEMS[cursor] {
+                        EditItem::Increase => self.screen = increase,
+                        EditItem::Decrease => self.screen = decrease,
+                        EditItem::Save => {
+                            self.screen = back;
+                            return MenuAction::SetThreshold { id, threshold, value };
+                        }
+                        EditItem::Back => self.screen = back,
+                    },
+                    MenuInput::Mode => {}
+                }
+                MenuAction::None
+            }
+        }
+    }
+
+    /// Current page, with live readings from the house
+    pub fn view(&self, house: &mut House) -> MenuView {
+        match self.screen {
+            Screen::Zones { cursor } => {
+                let mut lines: Vec<String> = Self::zones(house)
+                    .iter()
+                    .map(|zd| {
+                        let (kind, id) = zd.kind();
+                        format!("{:?} {} {:?}", kind, id, zd.info().indicator)
+                    })
+                    .collect();
+                lines.push(String::from("< Exit"));
+                MenuView {
+                    title: String::from("Zones"),
+                    selected: Some(cursor.min(lines.len() - 1)),
+                    lines,
+                }
+            }
+            Screen::Zone { kind, id, cursor } => {
+                let info = Self::zones(house)
+                    .into_iter()
+                    .find(|zd| zd.kind() == (kind, id))
+                    .map(|zd| zd.info().clone());
+                let mut lines: Vec<String> = Vec::new();
+                match &info {
+                    Some(ds) => {
+                        lines.push(format!("{:?}", ds.indicator));
+                        if let Some(msg) = &ds.msg {
+                            lines.extend(msg.split(", ").map(|s| s.trim().to_string()));
+                        }
+                    }
+                    None => lines.push(String::from("Zone not found")),
+                }
+                let first_item = lines.len();
+                let names: Vec<String> = house
+                    .arm_positions(id)
+                    .filter(|_| kind == ZoneKind::Arm)
+                    .map(|p| p.into_keys().collect())
+                    .unwrap_or_default();
+                for item in Self::items(kind, id, house) {
+                    lines.push(match item {
+                        Item::PumpRun => format!("Run {} s", MENU_PUMP_SECS),
+                        Item::PumpStop => String::from("Stop"),
+                        Item::LampOn => String::from("Lamp on"),
+                        Item::LampOff => String::from("Lamp off"),
+                        Item::SetPosition => String::from("Set position"),
+                        Item::Goto(index) => match names.get(index) {
+                            Some(name) => format!("Go {}", name),
+                            None => String::from("Go -"),
+                        },
+                        Item::Teach => String::from("Teach new"),
+                        Item::Edit(t) => match house.get_threshold(id, t) {
+                            Some(value) => format!("{} {:.1}", t.label(), value),
+                            None => format!("{} -", t.label()),
+                        },
+                        Item::Back => String::from("< Back"),
+                    });
+                }
+                MenuView {
+                    title: format!("{:?} {}", kind, id),
+                    selected: Some((first_item + cursor).min(lines.len() - 1)),
+                    lines,
+                }
+            }
+            Screen::Edit { kind, id, threshold, value, cursor } => {
+                let mut lines = vec![format!("Value {:.1}", value)];
+                for item in EDIT_ITEMS {
+                    lines.push(match item {
+                        EditItem::Increase => format!("+ {}", threshold.step()),
+                        EditItem::Decrease => format!("- {}", threshold.step()),
+                        EditItem::Save => String::from("Save"),
+                        EditItem::Back => String::from("< Back"),
+                    });
+                }
+                MenuView {
+                    title: format!("{:?} {} {}", kind, id, threshold.label()),
+                    lines,
+                    selected: Some(1 + cursor),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use core::error::Error;
+use parking_lot::RwLock;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use super::{SysLog, SysLogTx};
+use crate::zone::{ZoneDisplay, ZoneLog, ZoneStatusTx};
+
+/// Prometheus endpoint settings, read from grow-metrics.js
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSettings {
+    pub enabled: bool,
+    /// Listen address, e.g. "0.0.0.0:9184"
+    pub bind: String,
+}
+impl MetricsSettings {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let readdata = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&readdata)?)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MetricType {
+    Gauge,
+    Counter,
+}
+
+#[derive(Clone, Debug)]
+struct Family {
+    help: &'static str,
+    kind: MetricType,
+    samples: BTreeMap<String, f64>,
+}
+
+/// Latest zone readings and event counters in OpenMetrics text form
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    families: Arc<RwLock<BTreeMap<&'static str, Family>>>,
+}
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn labels(labels: &[(&str, String)]) -> String {
+        let inner: Vec<String> = labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect();
+        format!("{{{}}}", inner.join(","))
+    }
+
+    pub fn set_gauge(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, String)],
+        value: f64,
+    ) {
+        let mut lock = self.families.write();
+        let family = lock.entry(name).or_insert(Family {
+            help,
+            kind: MetricType::Gauge,
+            samples: BTreeMap::new(),
+        });
+        family.samples.insert(Self::labels(labels), value);
+    }
+
+    /// `name` is the family name, samples are rendered with the `_total` suffix
+    pub fn inc_counter(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, String)],
+        by: f64,
+    ) {
+        let mut lock = self.families.write();
+        let family = lock.entry(name).or_insert(Family {
+            help,
+            kind: MetricType::Counter,
+            samples: BTreeMap::new(),
+        });
+        *family.samples.entry(Self::labels(labels)).or_insert(0.0) += by;
+    }
+
+    pub fn observe_log(&self, data: &ZoneLog) {
+        match data {
+            ZoneLog::Air { id, temp, fan_rpm, .. } => {
+                if let Some(temp) = temp {
+                    self.set_gauge("grow_air_temp_celsius", "Air zone temperature", &[("id", id.to_string())], *temp);
+                }
+                if let Some(rpm) = fan_rpm {
+                    self.set_gauge("grow_air_fan_rpm", "Air zone fan speed", &[("id", id.to_string())], *rpm as f64);
+                }
+            }
+            ZoneLog::Light { id, light_level, .. } => {
+                if let Some(level) = light_level {
+                    self.set_gauge("grow_light_level", "Light zone light level", &[("id", id.to_string())], *level as f64);
+                }
+            }
+            ZoneLog::Water { id, moisture, .. } => {
+                if let Some(moisture) = moisture {
+                    self.set_gauge("grow_water_moisture_level", "Water zone moisture level", &[("id", id.to_string())], *moisture as f64);
+                }
+            }
+            ZoneLog::Arm { id, x, y, z, .. } => {
+                for (axis, pos) in [("x", x), ("y", y), ("z", z)] {
+                    self.set_gauge("grow_arm_position", "Arm position per axis", &[("id", id.to_string()), ("axis", axis.to_string())], *pos as f64);
+                }
+            }
+            ZoneLog::Tank { id, level, percent, days_left, refilling, .. } => {
+                let value = level.and_then(|l| l.value()).unwrap_or(f64::NAN);
+                self.set_gauge("grow_tank_level", "Tank level: Empty 0, Low 1, Ok 2, Overfill 3", &[("id", id.to_string())], value);
+                if let Some(percent) = percent {
+                    self.set_gauge("grow_tank_percent", "Tank level in percent of full", &[("id", id.to_string())], *percent as f64);
+                }
+                if let Some(days) = days_left {
+                    self.set_gauge("grow_tank_days_left", "Tank forecast days until empty", &[("id", id.to_string())], *days as f64);
+                }
+                self.set_gauge("grow_tank_refilling", "Tank refill running: 1, else 0", &[("id", id.to_string())], *refilling as u8 as f64);
+            }
+            ZoneLog::Pump { .. } | ZoneLog::Aux { .. } => {}
+        }
+    }
+
+    pub fn observe_status(&self, data: &ZoneDisplay) {
+        let (kind, id) = data.kind();
+        self.set_gauge(
+            "grow_zone_indicator",
+            "Zone indicator: Blue 0, Green 1, Yellow 2, Red 3",
+            &[("kind", format!("{:?}", kind)), ("id", id.to_string())],
+            data.info().indicator as u8 as f64,
+        );
+    }
+
+    pub fn watering(&self, id: u8, result: &str) {
+        self.inc_counter(
+            "grow_waterings",
+            "Watering attempts by result",
+            &[("id", id.to_string()), ("result", result.to_string())],
+            1.0,
+        );
+    }
+
+    pub fn channel_lagged(&self, channel: &str, skipped: u64) {
+        self.inc_counter(
+            "grow_channel_lagged",
+            "Messages skipped by lagging broadcast receivers",
+            &[("channel", channel.to_string())],
+            skipped as f64,
+        );
+    }
+
+    pub fn channel_dropped(&self, channel: &str) {
+        self.inc_counter(
+            "grow_channel_dropped",
+            "Messages dropped on full or closed channels",
+            &[("channel", channel.to_string())],
+            1.0,
+        );
+    }
+
+    pub fn render(&self) -> String {
+        let mut r = String::new();
+        for (name, family) in self.families.read().iter() {
+            let (kind, suffix) = match family.kind {
+                MetricType::Gauge => ("gauge", ""),
+                MetricType::Counter => ("counter", "_total"),
+            };
+            r.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, family.help, name, kind));
+            for (labels, value) in &family.samples {
+                r.push_str(&format!("{}{}{} {}\n", name, suffix, labels, value));
+            }
+        }
+        r.push_str("# EOF\n");
+
+        r
+    }
+}
+
+/// Serve GET /metrics
+#[derive(Debug)]
+pub struct MetricsServer {
+    settings: MetricsSettings,
+    task: Option<JoinHandle<()>>,
+}
+impl MetricsServer {
+    pub fn new(settings: MetricsSettings) -> Self {
+        Self { settings, task: None }
+    }
+    pub fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+
+    pub async fn run(
+        &mut self,
+        metrics: Metrics,
+        zonestatus: ZoneStatusTx,
+        to_syslog: SysLogTx,
+    ) -> Result<(), Box<dyn Error>> {
+        let listener = TcpListener::bind(&self.settings.bind).await?;
+        let bind = self.settings.bind.clone();
+        self.task = Some(tokio::spawn(async move {
+            let _ = to_syslog
+                .send(SysLog::new(format!("Spawned metrics endpoint on {}", bind)))
+                .await;
+            while let Ok((mut stream, _addr)) = listener.accept().await {
+                let metrics = metrics.clone();
+                let queued = zonestatus.len();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let n = match stream.read(&mut buf).await {
+                        Ok(n) => n,
+                        Err(_) => return,
+                    };
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    let path = request.split_whitespace().nth(1).unwrap_or("");
+                    let response = if request.starts_with("GET ") & ((path == "/metrics") | path.starts_with("/metrics?")) {
+                        metrics.set_gauge(
+                            "grow_channel_queued",
+                            "Messages queued in broadcast channel",
+                            &[("channel", String::from("zonestatus"))],
+                            queued as f64,
+                        );
+                        let body = metrics.render();
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    } else {
+                        String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                    };
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        }));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_openmetrics() {
+        let metrics = Metrics::new();
+        metrics.set_gauge("grow_tank_percent", "Tank level in percent of full", &[("id", String::from("1"))], 42.5);
+        metrics.watering(2, "ok");
+        metrics.watering(2, "ok");
+        metrics.channel_lagged("zonelog", 3);
+        assert_eq!(
+            metrics.render(),
+            "# HELP grow_channel_lagged Messages skipped by lagging broadcast receivers\n\
+             # TYPE grow_channel_lagged counter\n\
+             grow_channel_lagged_total{channel=\"zonelog\"} 3\n\
+             # HELP grow_tank_percent Tank level in percent of full\n\
+             # TYPE grow_tank_percent gauge\n\
+             grow_tank_percent{id=\"1\"} 42.5\n\
+             # HELP grow_waterings Watering attempts by result\n\
+             # TYPE grow_waterings counter\n\
+             grow_waterings_total{id=\"2\",result=\"ok\"} 2\n\
+             # EOF\n"
+        );
+    }
+
+    #[test]
+    fn label_values_escaped() {
+        let metrics = Metrics::new();
+        metrics.channel_dropped("a\"b\\c");
+        assert!(metrics.render().contains("grow_channel_dropped_total{channel=\"a\\\"b\\\\c\"} 1\n"));
+    }
+}
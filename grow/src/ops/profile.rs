@@ -0,0 +1,357 @@
+//! Grow-stage profiles, named overrides of light, water and air settings,
+//! activated by hand or following a calendar of stages
+use alloc::collections::BTreeMap;
+use core::fmt;
+use core::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use time::{Date, OffsetDateTime, Time};
+
+use super::display::{DisplayStatus, Indicator};
+use crate::zone::{air, light, water, ZoneSave};
+
+/// House status id showing the stage, next to the controller health on id 0
+pub const STAGE_DISPLAY_ID: u8 = 1;
+
+time::serde::format_description!(calendar_date, Date, "[year]-[month]-[day]");
+
+/// Profiles and calendar, read from grow-profiles.js
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileSettings {
+    pub profiles: Vec<Profile>,
+    pub calendar: Option<Calendar>,
+}
+impl ProfileSettings {
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+}
+
+/// Settings overrides by zone id, settings not given keep their value from grow-conf.js
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub light: BTreeMap<u8, LightOverrides>,
+    pub water: BTreeMap<u8, WaterOverrides>,
+    pub air: BTreeMap<u8, AirOverrides>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightOverrides {
+    pub lamp_on: Option<Time>,
+    pub lamp_off: Option<Time>,
+    pub lightlevel_low_yellow_warning: Option<f32>,
+    pub lightlevel_low_red_alert: Option<f32>,
+}
+impl LightOverrides {
+    pub fn apply(&self, settings: &mut light::Settings) {
+        set(&mut settings.lamp_on, self.lamp_on);
+        set(&mut settings.lamp_off, self.lamp_off);
+        set(&mut settings.lightlevel_low_yellow_warning, self.lightlevel_low_yellow_warning);
+        set(&mut settings.lightlevel_low_red_alert, self.lightlevel_low_red_alert);
+    }
+    /// Put back the overridden settings from `base`
+    pub fn reset(&self, settings: &mut light::Settings, base: &light::Settings) {
+        reset(&mut settings.lamp_on, base.lamp_on, self.lamp_on);
+        reset(&mut settings.lamp_off, base.lamp_off, self.lamp_off);
+        reset(&mut settings.lightlevel_low_yellow_warning, base.lightlevel_low_yellow_warning, self.lightlevel_low_yellow_warning);
+        reset(&mut settings.lightlevel_low_red_alert, base.lightlevel_low_red_alert, self.lightlevel_low_red_alert);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WaterOverrides {
+    pub moisture_low_red_alert: Option<f32>,
+    pub moisture_low_yellow_warning: Option<f32>,
+    pub moisture_limit_water: Option<f32>,
+    pub moisture_high_yellow_warning: Option<f32>,
+    pub moisture_high_red_alert: Option<f32>,
+    pub pump_time: Option<Duration>,
+}
+impl WaterOverrides {
+    pub fn apply(&self, settings: &mut water::Settings) {
+        set(&mut settings.moisture_low_red_alert, self.moisture_low_red_alert);
+        set(&mut settings.moisture_low_yellow_warning, self.moisture_low_yellow_warning);
+        set(&mut settings.moisture_limit_water, self.moisture_limit_water);
+        set(&mut settings.moisture_high_yellow_warning, self.moisture_high_yellow_warning);
+        set(&mut settings.moisture_high_red_alert, self.moisture_high_red_alert);
+        set(&mut settings.pump_time, self.pump_time);
+    }
+    pub fn reset(&self, settings: &mut water::Settings, base: &water::Settings) {
+        reset(&mut settings.moisture_low_red_alert, base.moisture_low_red_alert, self.moisture_low_red_alert);
+        reset(&mut settings.moisture_low_yellow_warning, base.moisture_low_yellow_warning, self.moisture_low_yellow_warning);
+        reset(&mut settings.moisture_limit_water, base.moisture_limit_water, self.moisture_limit_water);
+        reset(&mut settings.moisture_high_yellow_warning, base.moisture_high_yellow_warning, self.moisture_high_yellow_warning);
+        reset(&mut settings.moisture_high_red_alert, base.moisture_high_red_alert, self.moisture_high_red_alert);
+        reset(&mut settings.pump_time, base.pump_time, self.pump_time);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AirOverrides {
+    pub temp_high_yellow_warning: Option<f64>,
+    pub temp_high_red_alert: Option<f64>,
+    pub temp_fan_low: Option<f32>,
+    pub temp_fan_high: Option<f32>,
+}
+impl AirOverrides {
+    pub fn apply(&self, settings: &mut air::Settings) {
+        set(&mut settings.temp_high_yellow_warning, self.temp_high_yellow_warning);
+        set(&mut settings.temp_high_red_alert, self.temp_high_red_alert);
+        set(&mut settings.temp_fan_low, self.temp_fan_low);
+        set(&mut settings.temp_fan_high, self.temp_fan_high);
+    }
+    pub fn reset(&self, settings: &mut air::Settings, base: &air::Settings) {
+        reset(&mut settings.temp_high_yellow_warning, base.temp_high_yellow_warning, self.temp_high_yellow_warning);
+        reset(&mut settings.temp_high_red_alert, base.temp_high_red_alert, self.temp_high_red_alert);
+        reset(&mut settings.temp_fan_low, base.temp_fan_low, self.temp_fan_low);
+        reset(&mut settings.temp_fan_high, base.temp_fan_high, self.temp_fan_high);
+    }
+}
+
+fn set<T>(setting: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *setting = value;
+    }
+}
+fn reset<T>(setting: &mut T, base: T, overridden: Option<T>) {
+    if overridden.is_some() {
+        *setting = base;
+    }
+}
+
+/// Stages in order from `start`, the last stage is kept after it ends
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Calendar {
+    /// E.g. "2024-03-01"
+    #[serde(with = "calendar_date")]
+    pub start: Date,
+    pub stages: Vec<CalendarStage>,
+}
+impl Calendar {
+    /// Stage on `date` and the day of the stage counted from 1, `None` before the start
+    pub fn stage_on(&self, date: Date) -> Option<(&CalendarStage, u16)> {
+        let mut day = (date - self.start).whole_days();
+        if day < 0 {
+            return None;
+        }
+        for stage in &self.stages {
+            if day < stage.days as i64 {
+                return Some((stage, day as u16 + 1));
+            }
+            day -= stage.days as i64;
+        }
+        self.stages.last().map(|stage| (stage, stage.days))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CalendarStage {
+    pub profile: String,
+    pub days: u16,
+}
+
+/// Active profile as shown on the display and returned by `Command::Stage`
+#[derive(Clone, Debug, PartialEq)]
+pub struct StageInfo {
+    pub profile: String,
+    /// Day of the stage and its length, `None` when activated by hand
+    pub day: Option<(u16, u16)>,
+    pub since: OffsetDateTime,
+}
+impl StageInfo {
+    pub fn display_status(&self) -> DisplayStatus {
+        DisplayStatus::new(Indicator::Green, Some(self.to_string()))
+    }
+}
+impl fmt::Display for StageInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.day {
+            Some((day, days)) => write!(f, "Stage {} day {}/{}", self.profile, day, days),
+            None => write!(f, "Stage {} (manual)", self.profile),
+        }
+    }
+}
+
+/// Applied profile and the zone settings it was applied to. Only the overridden
+/// settings are taken from here, others may change while the profile is active.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProfileBase {
+    pub profile: Profile,
+    pub light: BTreeMap<u8, light::Settings>,
+    pub water: BTreeMap<u8, water::Settings>,
+    pub air: BTreeMap<u8, air::Settings>,
+}
+impl ProfileBase {
+    pub fn reset_light(&self, id: u8, settings: &mut light::Settings) {
+        if let (Some(overrides), Some(base)) = (self.profile.light.get(&id), self.light.get(&id)) {
+            overrides.reset(settings, base);
+        }
+    }
+    pub fn reset_water(&self, id: u8, settings: &mut water::Settings) {
+        if let (Some(overrides), Some(base)) = (self.profile.water.get(&id), self.water.get(&id)) {
+            overrides.reset(settings, base);
+        }
+    }
+    pub fn reset_air(&self, id: u8, settings: &mut air::Settings) {
+        if let (Some(overrides), Some(base)) = (self.profile.air.get(&id), self.air.get(&id)) {
+            overrides.reset(settings, base);
+        }
+    }
+    /// Zones about to be saved get the settings from grow-conf.js instead of the profile's
+    pub fn restore(&self, saved: &mut [ZoneSave]) {
+        for zone in saved.iter_mut() {
+            match zone {
+                ZoneSave::Light { id, settings, .. } => self.reset_light(*id, settings),
+                ZoneSave::Water { id, settings, .. } => self.reset_water(*id, settings),
+                ZoneSave::Air { id, settings, .. } => self.reset_air(*id, settings),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Profile state kept by the House
+#[derive(Clone, Debug, Default)]
+pub struct Stages {
+    pub settings: ProfileSettings,
+    /// Profile activated by hand, overrides the calendar until cleared
+    pub manual: Option<String>,
+    pub current: Option<StageInfo>,
+    pub base: Option<ProfileBase>,
+}
+impl Stages {
+    /// Profile and stage that should be active now
+    pub fn wanted(&self, now: OffsetDateTime) -> Option<(Profile, StageInfo)> {
+        let (name, day) = match (&self.manual, &self.settings.calendar) {
+            (Some(name), _) => (name.clone(), None),
+            (None, Some(calendar)) => {
+                let (stage, day) = calendar.stage_on(now.date())?;
+                (stage.profile.clone(), Some((day, stage.days)))
+            }
+            (None, None) => return None,
+        };
+        let profile = self.settings.profile(&name)?.clone();
+        let since = match &self.current {
+            Some(current) if current.profile == name => current.since,
+            _ => now,
+        };
+        Some((profile, StageInfo { profile: name, day, since }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::{date, time};
+
+    fn calendar() -> Calendar {
+        Calendar {
+            start: date!(2024 - 03 - 01),
+            stages: vec![
+                CalendarStage { profile: String::from("seedling"), days: 10 },
+                CalendarStage { profile: String::from("veg"), days: 20 },
+            ],
+        }
+    }
+
+    fn light() -> light::Settings {
+        light::Settings {
+            lightlevel_low_yellow_warning: 10.0,
+            lightlevel_low_red_alert: 5.0,
+            lamp_on: time!(6:00),
+            lamp_off: time!(22:00),
+        }
+    }
+
+    #[test]
+    fn stage_on() {
+        let calendar = calendar();
+        let stage = |date| calendar.stage_on(date).map(|(stage, day)| (stage.profile.as_str(), day));
+        assert_eq!(stage(date!(2024 - 02 - 29)), None);
+        assert_eq!(stage(date!(2024 - 03 - 01)), Some(("seedling", 1)));
+        assert_eq!(stage(date!(2024 - 03 - 10)), Some(("seedling", 10)));
+        assert_eq!(stage(date!(2024 - 03 - 11)), Some(("veg", 1)));
+        assert_eq!(stage(date!(2024 - 03 - 30)), Some(("veg", 20)));
+        // The last stage is kept on its last day
+        assert_eq!(stage(date!(2024 - 06 - 01)), Some(("veg", 20)));
+        assert_eq!(Calendar { stages: Vec::new(), ..calendar.clone() }.stage_on(date!(2024 - 03 - 05)), None);
+    }
+
+    #[test]
+    fn overrides_apply_and_reset() {
+        let overrides = LightOverrides { lamp_on: Some(time!(4:00)), lamp_off: Some(time!(23:00)), ..Default::default() };
+        let base = light();
+        let mut settings = base;
+        overrides.apply(&mut settings);
+        assert_eq!((settings.lamp_on, settings.lamp_off), (time!(4:00), time!(23:00)));
+        assert_eq!(settings.lightlevel_low_red_alert, base.lightlevel_low_red_alert);
+        // Settings not overridden keep changes made while the profile is active
+        settings.lightlevel_low_red_alert = 7.0;
+        overrides.reset(&mut settings, &base);
+        assert_eq!((settings.lamp_on, settings.lamp_off), (base.lamp_on, base.lamp_off));
+        assert_eq!(settings.lightlevel_low_red_alert, 7.0);
+    }
+
+    #[test]
+    fn base_reset_and_restore() {
+        let mut profile = Profile { name: String::from("flower"), ..Default::default() };
+        profile.light.insert(1, LightOverrides { lamp_off: Some(time!(18:00)), ..Default::default() });
+        let mut base = ProfileBase { profile, ..Default::default() };
+        base.light.insert(1, light());
+
+        let mut applied = light();
+        base.profile.light[&1].apply(&mut applied);
+        assert_eq!(applied.lamp_off, time!(18:00));
+        let mut settings = applied;
+        base.reset_light(1, &mut settings);
+        assert_eq!(settings, light());
+        // Zones without overrides are left alone
+        let mut other = applied;
+        base.reset_light(2, &mut other);
+        assert_eq!(other, applied);
+
+        let mut saved = vec![
+            ZoneSave::Light { id: 1, settings: applied, hardware: Vec::new() },
+            ZoneSave::Light { id: 2, settings: applied, hardware: Vec::new() },
+        ];
+        base.restore(&mut saved);
+        assert_eq!(
+            saved,
+            vec![
+                ZoneSave::Light { id: 1, settings: light(), hardware: Vec::new() },
+                ZoneSave::Light { id: 2, settings: applied, hardware: Vec::new() },
+            ]
+        );
+    }
+
+    #[test]
+    fn wanted_stage() {
+        let mut stages = Stages {
+            settings: ProfileSettings {
+                profiles: vec![
+                    Profile { name: String::from("seedling"), ..Default::default() },
+                    Profile { name: String::from("veg"), ..Default::default() },
+                ],
+                calendar: Some(calendar()),
+            },
+            ..Default::default()
+        };
+        let now = date!(2024 - 03 - 12).with_time(time!(12:00)).assume_utc();
+        let (profile, info) = stages.wanted(now).unwrap();
+        assert_eq!(profile.name, "veg");
+        assert_eq!(info.day, Some((2, 20)));
+        stages.manual = Some(String::from("seedling"));
+        let (profile, info) = stages.wanted(now).unwrap();
+        assert_eq!(profile.name, "seedling");
+        assert_eq!(info.to_string(), "Stage seedling (manual)");
+        stages.manual = None;
+        stages.settings.calendar = None;
+        assert!(stages.wanted(now).is_none());
+    }
+}
@@ -0,0 +1,448 @@
+//! Automation rules across zones: conditions on the latest readings and time of day,
+//! with actions held while the condition is met
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::time::Duration;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, Time};
+use tokio::task::JoinHandle;
+
+use super::{LogLevel, SysLog, SysLogTx};
+use crate::zone::air::FanSetting;
+use crate::zone::tank::TankLevel;
+use crate::zone::ZoneView;
+use crate::GrowError;
+use crate::HouseMutex;
+use crate::TIME_OFFSET;
+
+/// Rules read from grow-rules.js
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleSettings {
+    pub enabled: bool,
+    /// How often conditions are evaluated
+    pub check_interval: Duration,
+    pub rules: Vec<Rule>,
+}
+impl Default for RuleSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            check_interval: Duration::from_secs(30),
+            rules: Vec::new(),
+        }
+    }
+}
+impl RuleSettings {
+    /// Rules are tracked by name, a duplicate would share the state of the first.
+    /// Pump time factors must be finite and not negative.
+    pub fn check(&self) -> Result<(), GrowError> {
+        let mut names = BTreeSet::new();
+        for rule in &self.rules {
+            if !names.insert(rule.name.as_str()) {
+                return Err(GrowError::Config(format!("Rule name {} used more than once", rule.name)));
+            }
+            for action in &rule.then {
+                if let Action::ScalePumpTime { factor, .. } = action {
+                    if !factor.is_finite() | (*factor < 0.0) {
+                        return Err(GrowError::Config(format!("Rule {} pump time factor {} not allowed", rule.name, factor)));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Actions in `then` apply while `when` holds, and are undone when it no longer does
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub when: Condition,
+    pub then: Vec<Action>,
+}
+
+/// Conditions on zone readings are false while the zone has no reading
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Condition {
+    TempAbove { id: u8, value: f64 },
+    TempBelow { id: u8, value: f64 },
+    MoistureAbove { id: u8, value: f32 },
+    MoistureBelow { id: u8, value: f32 },
+    LightBelow { id: u8, value: f32 },
+    /// Tank reports `level`, Low also matches Empty
+    TankLevel { id: u8, level: TankLevel },
+    /// From `from` until `to`, past midnight if `to` is earlier
+    TimeBetween { from: Time, to: Time },
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+}
+impl Condition {
+    pub fn matches(&self, view: &ZoneView, now: Time) -> bool {
+        match self {
+            Condition::TempAbove { id, value } => view
+                .air_status(*id)
+                .and_then(|status| status.read().temp)
+                .is_some_and(|temp| temp > *value),
+            Condition::TempBelow { id, value } => view
+                .air_status(*id)
+                .and_then(|status| status.read().temp)
+                .is_some_and(|temp| temp < *value),
+            Condition::MoistureAbove { id, value } => view
+                .water_status(*id)
+                .and_then(|status| status.read().moisture_level)
+                .is_some_and(|moisture| moisture > *value),
+            Condition::MoistureBelow { id, value } => view
+                .water_status(*id)
+                .and_then(|status| status.read().moisture_level)
+                .is_some_and(|moisture| moisture < *value),
+            Condition::LightBelow { id, value } => view
+                .light_status(*id)
+                .and_then(|status| status.read().light_level)
+                .is_some_and(|light| light < *value),
+            Condition::TankLevel { id, level } => view
+                .tank_status(*id)
+                .and_then(|status| status.read().level)
+                .is_some_and(|current| {
+                    (current == *level) | ((*level == TankLevel::Low) & (current == TankLevel::Empty))
+                }),
+            Condition::TimeBetween { from, to } => {
+                if from <= to {
+                    (now >= *from) & (now < *to)
+                } else {
+                    (now >= *from) | (now < *to)
+                }
+            }
+            Condition::All(conditions) => conditions.iter().all(|c| c.matches(view, now)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.matches(view, now)),
+            Condition::Not(condition) => !condition.matches(view, now),
+        }
+    }
+}
+
+/// `id: None` applies to every water zone
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    /// Skip watering, it runs again at the next request after the rule clears
+    PostponeWatering { id: Option<u8> },
+    /// Multiply pump times, factors of several rules are multiplied
+    ScalePumpTime { id: Option<u8>, factor: f64 },
+    /// Hold the fan mode regardless of temperature, the highest of several rules wins
+    ForceFan { id: u8, setting: FanSetting },
+}
+
+#[derive(Clone, Debug)]
+struct ActiveRule {
+    then: Vec<Action>,
+    since: OffsetDateTime,
+}
+
+/// Actions of the active rules, read by the House and watering
+#[derive(Clone, Debug, Default)]
+pub struct RuleEffects {
+    active: Arc<RwLock<BTreeMap<String, ActiveRule>>>,
+}
+impl RuleEffects {
+    /// Name of a rule postponing watering of the zone
+    pub fn watering_postponed(&self, water_id: u8) -> Option<String> {
+        self.active
+            .read()
+            .iter()
+            .find(|(_, rule)| {
+                rule.then.iter().any(|action| match action {
+                    Action::PostponeWatering { id } => id.is_none_or(|id| id == water_id),
+                    _ => false,
+                })
+            })
+            .map(|(name, _)| name.clone())
+    }
+    pub fn pump_time_factor(&self, water_id: u8) -> f64 {
+        self.active
+            .read()
+            .values()
+            .flat_map(|rule| rule.then.iter())
+            .filter_map(|action| match action {
+                Action::ScalePumpTime { id, factor } if id.is_none_or(|id| id == water_id) => Some(*factor),
+                _ => None,
+            })
+            .product()
+    }
+    fn forced_fans(&self) -> BTreeMap<u8, FanSetting> {
+        let mut fans: BTreeMap<u8, FanSetting> = BTreeMap::new();
+        for action in self.active.read().values().flat_map(|rule| rule.then.iter()) {
+            if let Action::ForceFan { id, setting } = action {
+                let forced = fans.entry(*id).or_insert(*setting);
+                if *setting > *forced {
+                    *forced = *setting;
+                }
+            }
+        }
+        fans
+    }
+    /// Set whether the rule is active, returns true if it changed
+    fn set(&self, rule: &Rule, active: bool, now: OffsetDateTime) -> bool {
+        let mut lock = self.active.write();
+        match (active, lock.contains_key(&rule.name)) {
+            (true, false) => {
+                lock.insert(rule.name.clone(), ActiveRule { then: rule.then.clone(), since: now });
+                true
+            }
+            (false, true) => {
+                lock.remove(&rule.name);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Rule as listed to operators
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleInfo {
+    pub name: String,
+    pub active: bool,
+    pub since: Option<OffsetDateTime>,
+}
+
+#[derive(Debug)]
+pub struct RulesEngine {
+    settings: RuleSettings,
+    effects: RuleEffects,
+    task: Option<JoinHandle<()>>,
+}
+impl RulesEngine {
+    /// `effects` is shared with the House, see `House::rule_effects`
+    pub fn new(settings: RuleSettings, effects: RuleEffects) -> Self {
+        Self {
+            settings,
+            effects,
+            task: None,
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+
+    pub fn list(&self) -> Vec<RuleInfo> {
+        let active = self.effects.active.read();
+        self.settings
+            .rules
+            .iter()
+            .map(|rule| RuleInfo {
+                name: rule.name.clone(),
+                active: active.contains_key(&rule.name),
+                since: active.get(&rule.name).map(|a| a.since),
+            })
+            .collect()
+    }
+
+    pub fn run(&mut self, house: HouseMutex, view: ZoneView, to_syslog: SysLogTx) {
+        let rules = self.settings.rules.clone();
+        let effects = self.effects.clone();
+        let mut interval = tokio::time::interval(self.settings.check_interval);
+
+        self.task = Some(tokio::spawn(async move {
+            let _ = to_syslog
+                .send(SysLog::new(String::from("Spawned rules engine")))
+                .await;
+            // Fan modes last sent to the air zones
+            let mut fans_sent: BTreeMap<u8, FanSetting> = BTreeMap::new();
+            loop {
+                interval.tick().await;
+                let now = OffsetDateTime::now_utc().to_offset(TIME_OFFSET);
+                for rule in &rules {
+                    let active = rule.when.matches(&view, now.time());
+                    if !effects.set(rule, active, now) {
+                        continue;
+                    }
+                    let msg = match active {
+                        true => format!("Rule {} active: {:?}", rule.name, rule.then),
+                        false => format!("Rule {} cleared", rule.name),
+                    };
+                    let _ = to_syslog
+                        .send(SysLog::new(msg).source("rules").field("rule", &rule.name))
+                        .await;
+                }
+
+                let fans = effects.forced_fans();
+                let ids: BTreeSet<u8> = fans.keys().chain(fans_sent.keys()).copied().collect();
+                for id in ids {
+                    let setting = fans.get(&id).copied();
+                    if setting == fans_sent.get(&id).copied() {
+                        continue;
+                    }
+                    // Not retried, a missing zone or fan is reported once
+                    let result = house.lock().await.force_fan(id, setting);
+                    if let Err(e) = result {
+                        let _ = to_syslog
+                            .send(SysLog::with_level(LogLevel::Warning, format!("Rule fan force failed: {}", e)).source("rules"))
+                            .await;
+                    }
+                    match setting {
+                        Some(setting) => fans_sent.insert(id, setting),
+                        None => fans_sent.remove(&id),
+                    };
+                }
+            }
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zone::{air, tank};
+
+    fn view_with(temp: Option<f64>, level: Option<TankLevel>) -> ZoneView {
+        let view = ZoneView::default();
+        let air = air::new(
+            1,
+            air::Settings {
+                temp_high_yellow_warning: 30.0,
+                temp_high_red_alert: 35.0,
+                temp_fan_low: 20.0,
+                temp_fan_high: 25.0,
+                fan_rpm_low_red_alert: 100.0,
+                safe_duty_cycle: 0.0,
+            },
+        );
+        if let crate::zone::Zone::Air { status, .. } = &air {
+            status.write().temp = temp;
+        }
+        view.insert(&air);
+        let tank = tank::new(1, tank::Settings::default());
+        if let crate::zone::Zone::Tank { status, .. } = &tank {
+            status.write().level = level;
+        }
+        view.insert(&tank);
+        view
+    }
+
+    fn rule(name: &str, then: Vec<Action>) -> Rule {
+        Rule {
+            name: String::from(name),
+            when: Condition::All(Vec::new()),
+            then,
+        }
+    }
+
+    #[tokio::test]
+    async fn readings() {
+        let view = view_with(Some(28.0), Some(TankLevel::Empty));
+        let noon = Time::from_hms(12, 0, 0).unwrap();
+        assert!(Condition::TempAbove { id: 1, value: 27.5 }.matches(&view, noon));
+        assert!(!Condition::TempBelow { id: 1, value: 27.5 }.matches(&view, noon));
+        assert!(Condition::TankLevel { id: 1, level: TankLevel::Empty }.matches(&view, noon));
+        assert!(Condition::TankLevel { id: 1, level: TankLevel::Low }.matches(&view, noon));
+        assert!(!Condition::TankLevel { id: 1, level: TankLevel::Ok }.matches(&view, noon));
+        // Missing zones and readings never match, not even negated comparisons
+        assert!(!Condition::TempAbove { id: 2, value: 0.0 }.matches(&view, noon));
+        assert!(!Condition::MoistureBelow { id: 1, value: 100.0 }.matches(&view, noon));
+        let no_data = view_with(None, Some(TankLevel::Low));
+        assert!(!Condition::TempBelow { id: 1, value: 100.0 }.matches(&no_data, noon));
+        assert!(!Condition::TankLevel { id: 1, level: TankLevel::Empty }.matches(&no_data, noon));
+    }
+
+    #[tokio::test]
+    async fn combined() {
+        let view = view_with(Some(28.0), Some(TankLevel::Ok));
+        let noon = Time::from_hms(12, 0, 0).unwrap();
+        let hot = Condition::TempAbove { id: 1, value: 27.0 };
+        let low = Condition::TankLevel { id: 1, level: TankLevel::Low };
+        assert!(!Condition::All(vec![hot.clone(), low.clone()]).matches(&view, noon));
+        assert!(Condition::Any(vec![hot.clone(), low.clone()]).matches(&view, noon));
+        assert!(Condition::Not(Box::new(low)).matches(&view, noon));
+        assert!(Condition::All(Vec::new()).matches(&view, noon));
+        assert!(!Condition::Any(Vec::new()).matches(&view, noon));
+    }
+
+    #[test]
+    fn time_between() {
+        let view = ZoneView::default();
+        let at = |h, m| Time::from_hms(h, m, 0).unwrap();
+        let day = Condition::TimeBetween { from: at(8, 0), to: at(20, 0) };
+        assert!(day.matches(&view, at(8, 0)));
+        assert!(day.matches(&view, at(19, 59)));
+        assert!(!day.matches(&view, at(20, 0)));
+        assert!(!day.matches(&view, at(2, 0)));
+        let night = Condition::TimeBetween { from: at(22, 0), to: at(6, 0) };
+        assert!(night.matches(&view, at(23, 30)));
+        assert!(night.matches(&view, at(0, 0)));
+        assert!(night.matches(&view, at(5, 59)));
+        assert!(!night.matches(&view, at(6, 0)));
+        assert!(!night.matches(&view, at(12, 0)));
+    }
+
+    #[test]
+    fn pump_time_factor() {
+        let effects = RuleEffects::default();
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(effects.pump_time_factor(1), 1.0);
+        effects.set(&rule("hot", vec![Action::ScalePumpTime { id: None, factor: 1.5 }]), true, now);
+        effects.set(&rule("dry", vec![Action::ScalePumpTime { id: Some(1), factor: 2.0 }]), true, now);
+        assert_eq!(effects.pump_time_factor(1), 3.0);
+        assert_eq!(effects.pump_time_factor(2), 1.5);
+        effects.set(&rule("hot", Vec::new()), false, now);
+        assert_eq!(effects.pump_time_factor(1), 2.0);
+        assert_eq!(effects.pump_time_factor(2), 1.0);
+    }
+
+    #[test]
+    fn forced_fans() {
+        let effects = RuleEffects::default();
+        let now = OffsetDateTime::now_utc();
+        assert!(effects.forced_fans().is_empty());
+        effects.set(&rule("warm", vec![Action::ForceFan { id: 1, setting: FanSetting::Medium }]), true, now);
+        effects.set(&rule("heat", vec![Action::ForceFan { id: 1, setting: FanSetting::High }]), true, now);
+        effects.set(&rule("quiet", vec![Action::ForceFan { id: 2, setting: FanSetting::Off }]), true, now);
+        let fans = effects.forced_fans();
+        assert_eq!(fans.get(&1), Some(&FanSetting::High));
+        assert_eq!(fans.get(&2), Some(&FanSetting::Off));
+        effects.set(&rule("heat", Vec::new()), false, now);
+        assert_eq!(effects.forced_fans().get(&1), Some(&FanSetting::Medium));
+    }
+
+    #[test]
+    fn set_reports_changes() {
+        let effects = RuleEffects::default();
+        let now = OffsetDateTime::now_utc();
+        let postpone = rule("rain", vec![Action::PostponeWatering { id: Some(2) }]);
+        assert!(effects.set(&postpone, true, now));
+        assert!(!effects.set(&postpone, true, now));
+        assert_eq!(effects.watering_postponed(2), Some(String::from("rain")));
+        assert_eq!(effects.watering_postponed(1), None);
+        assert!(effects.set(&postpone, false, now));
+        assert!(!effects.set(&postpone, false, now));
+        assert_eq!(effects.watering_postponed(2), None);
+    }
+
+    #[test]
+    fn duplicate_names_rejected() {
+        let mut settings = RuleSettings {
+            rules: vec![rule("a", Vec::new()), rule("b", Vec::new())],
+            ..Default::default()
+        };
+        assert!(settings.check().is_ok());
+        settings.rules.push(rule("a", Vec::new()));
+        assert!(matches!(settings.check(), Err(GrowError::Config(_))));
+    }
+
+    #[test]
+    fn bad_factors_rejected() {
+        let with_factor = |factor| RuleSettings {
+            rules: vec![rule("scale", vec![Action::ScalePumpTime { id: None, factor }])],
+            ..Default::default()
+        };
+        assert!(with_factor(0.0).check().is_ok());
+        assert!(with_factor(2.5).check().is_ok());
+        for factor in [-1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(matches!(with_factor(factor).check(), Err(GrowError::Config(_))), "factor {}", factor);
+        }
+    }
+}
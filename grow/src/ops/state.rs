@@ -0,0 +1,58 @@
+//! Runtime state kept across restarts, restored before the runners start
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::alert::SavedAlert;
+use crate::zone::light::LampState;
+use crate::GrowError;
+
+pub const STATE_FILE: &str = "grow-state.js";
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeState {
+    pub saved: Option<OffsetDateTime>,
+    pub water: BTreeMap<u8, WaterState>,
+    pub lamps: BTreeMap<u8, LampState>,
+    pub overrides: Overrides,
+    pub alerts: Vec<SavedAlert>,
+    /// Grow profile activated by hand, the calendar applies without one
+    pub profile: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WaterState {
+    pub last_watering: Option<OffsetDateTime>,
+    /// Waterings on the day of `last_watering`
+    pub waterings_today: u32,
+}
+
+/// Manual settings made from the console or menu, applied again after a restart
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Overrides {
+    pub lamps: BTreeMap<u8, LampState>,
+    pub fan_duty_cycles: BTreeMap<u8, f64>,
+}
+
+impl RuntimeState {
+    pub fn load(path: &str) -> Result<Self, GrowError> {
+        let readdata = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&readdata)?)
+    }
+
+    /// Write to a temporary file and rename, a crash leaves either the old or the new state
+    pub fn save(&self, path: &str) -> Result<(), GrowError> {
+        let writestring = serde_json::to_string_pretty(self)?;
+        let tmp = format!("{}.tmp", path);
+        let mut f = File::create(&tmp)?;
+        f.write_all(writestring.as_bytes())?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
@@ -1,58 +1,279 @@
-use tokio::net::TcpStream;
-use tokio::io::AsyncWriteExt;
-use core::error::Error;
-use std::time::Duration;
-use crate::ops::display::Indicator;
-use crate::zone::ZoneDisplay;
-use crate::zone::ZoneKind::*;
-use std::thread;
-use serde::{Serialize, Deserialize};
-use std::sync::Arc;
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct XymonSettings {
-   pub port: u16,
-   pub host: String,
-   pub client: String,
-}
-macro_rules! xymon_match {
-    ($x:ident, $y:ident, [$( $variant:tt ),+] ) => {
-       match $x {
-        $(
-        ZoneDisplay::$variant {id, info} => {
-            let ind = match info.indicator {
-                Indicator::Blue => "clear",
-                Indicator::Green => "green",
-                Indicator::Yellow => "yellow",
-                Indicator::Red => "red",
-            };
-            let info_msg = match &info.msg {
-                Some(info_msg) => &info_msg,
-                None => "No message",
-            };
-            $y.push_str(&format!("{:?}_{} {} &{} {}\nTimestamp: {}", $variant, &id, ind, ind, info_msg, info.changed));
-           }
-        )+
-       }
-   }
-}
-/// Send status update to Xymon server, format:
-/// status[+LIFETIME][/group:GROUP] HOSTNAME.TESTNAME COLOR <additional text>
-pub async fn send_status(data: &ZoneDisplay, x: Arc<XymonSettings>) -> Result<(), Box<dyn Error>> {
-    let xymon = format!("{}:{}", x.host, x.port);
-    let mut stream = match TcpStream::connect(&xymon).await {
-        Ok(stream) =>  stream,
-        Err(e) => return Err(Box::new(e))
-    };
-    let mut xymon_status = format!("status+1h {}.", x.client);
-    xymon_match!(data, xymon_status, [Water, Air, Light, Aux, Tank, Pump, Arm]);
-    match stream.write_all(xymon_status.as_bytes()).await {
-        // Ok(_) => {println!("Sent to Xymon: {:?}", &xymon_status);},
-        Ok(_) => (),
-        Err(e) => return Err(Box::new(e))
-    }
-    let _ = stream.shutdown();
-    // thread::sleep(Duration::from_millis(10));
-    
-    Ok(())
-}
+use tokio::net::TcpStream;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use core::error::Error;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+use crate::ops::display::Indicator;
+use crate::ops::{LogLevel, SysLog, SysLogTx};
+use crate::zone::{ZoneDisplay, ZoneKind, ZoneLog};
+use crate::zone::ZoneKind::*;
+use crate::GrowError;
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct XymonSettings {
+   #[serde(default = "default_enabled")]
+   pub enabled: bool,
+   pub port: u16,
+   pub host: String,
+   pub client: String,
+   /// Xymon status lifetime, e.g. "30m", "1h"
+   #[serde(default = "default_lifetime")]
+   pub lifetime: String,
+   /// How often all zones are reported, regardless of changes
+   #[serde(default = "default_report_interval")]
+   pub report_interval: Duration,
+   /// How often queued changes are flushed
+   #[serde(default = "default_flush_interval")]
+   pub flush_interval: Duration,
+   /// Upper limit for retry backoff
+   #[serde(default = "default_max_backoff")]
+   pub max_backoff: Duration,
+   /// Test name for numeric sensor data
+   #[serde(default = "default_data_test")]
+   pub data_test: String,
+}
+fn default_enabled() -> bool { true }
+fn default_lifetime() -> String { String::from("1h") }
+fn default_report_interval() -> Duration { Duration::from_secs(300) }
+fn default_flush_interval() -> Duration { Duration::from_secs(5) }
+fn default_max_backoff() -> Duration { Duration::from_secs(600) }
+fn default_data_test() -> String { String::from("grow") }
+
+impl XymonSettings {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let readdata = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&readdata)?)
+    }
+}
+
+macro_rules! xymon_match {
+    ($x:ident, $y:ident, [$( $variant:tt ),+] ) => {
+       match $x {
+        $(
+        ZoneDisplay::$variant {id, info} => {
+            let ind = match info.indicator {
+                Indicator::Blue => "clear",
+                Indicator::Green => "green",
+                Indicator::Yellow => "yellow",
+                Indicator::Red => "red",
+            };
+            let info_msg = match &info.msg {
+                Some(info_msg) => &info_msg,
+                None => "No message",
+            };
+            $y.push_str(&format!("{:?}_{} {} &{} {}\nTimestamp: {}", $variant, &id, ind, ind, info_msg, info.changed));
+           }
+        )+
+       }
+   }
+}
+
+/// Format a status message:
+/// status[+LIFETIME][/group:GROUP] HOSTNAME.TESTNAME COLOR <additional text>
+pub fn format_status(data: &ZoneDisplay, x: &XymonSettings) -> String {
+    let mut xymon_status = format!("status+{} {}.", x.lifetime, x.client);
+    xymon_match!(data, xymon_status, [Water, Air, Light, Aux, Tank, Pump, Arm, House]);
+
+    xymon_status
+}
+
+/// Format a data message in name-colon-value form for RRD graphs:
+/// data HOSTNAME.TESTNAME\nname : value
+pub fn format_data(values: &BTreeMap<String, f64>, x: &XymonSettings) -> String {
+    let mut xymon_data = format!("data {}.{}\n", x.client, x.data_test);
+    for (name, value) in values {
+        xymon_data.push_str(&format!("{} : {}\n", name, value));
+    }
+
+    xymon_data
+}
+
+/// Numeric readings carried by a zone log entry
+pub fn log_values(data: &ZoneLog) -> Vec<(String, f64)> {
+    let mut r: Vec<(String, f64)> = Vec::new();
+    match data {
+        ZoneLog::Air { id, temp, fan_rpm, .. } => {
+            if let Some(temp) = temp { r.push((format!("air{}_temp", id), *temp)); }
+            if let Some(rpm) = fan_rpm { r.push((format!("air{}_fan_rpm", id), *rpm as f64)); }
+        }
+        ZoneLog::Light { id, light_level, .. } => {
+            if let Some(level) = light_level { r.push((format!("light{}_level", id), *level as f64)); }
+        }
+        ZoneLog::Water { id, moisture, .. } => {
+            if let Some(moisture) = moisture { r.push((format!("water{}_moisture", id), *moisture as f64)); }
+        }
+        ZoneLog::Arm { id, x, y, z, .. } => {
+            r.push((format!("arm{}_x", id), *x as f64));
+            r.push((format!("arm{}_y", id), *y as f64));
+            r.push((format!("arm{}_z", id), *z as f64));
+        }
+        ZoneLog::Tank { id, level, percent, days_left, .. } => {
+            if let Some(level) = level.and_then(|l| l.value()) { r.push((format!("tank{}_level", id), level)); }
+            if let Some(percent) = percent { r.push((format!("tank{}_percent", id), *percent as f64)); }
+            if let Some(days) = days_left { r.push((format!("tank{}_days_left", id), *days as f64)); }
+        }
+        ZoneLog::Pump { .. } | ZoneLog::Aux { .. } => {}
+    }
+
+    r
+}
+
+/// Send status update to Xymon server as a single message
+pub async fn send_status(data: &ZoneDisplay, x: Arc<XymonSettings>) -> Result<(), Box<dyn Error>> {
+    send_message(&format_status(data, &x), &x).await
+}
+
+/// Time limit for connecting and for writing a message, a stalled server counts as a failed send
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Xymon reads one message per connection, terminated by the client closing its end
+async fn send_message(msg: &str, x: &XymonSettings) -> Result<(), Box<dyn Error>> {
+    let xymon = format!("{}:{}", x.host, x.port);
+    let mut stream = timeout(SEND_TIMEOUT, TcpStream::connect(&xymon))
+        .await
+        .map_err(|_| GrowError::Timeout(format!("Xymon connect to {}", xymon)))??;
+    timeout(SEND_TIMEOUT, async {
+        stream.write_all(msg.as_bytes()).await?;
+        stream.shutdown().await
+    })
+    .await
+    .map_err(|_| GrowError::Timeout(format!("Xymon send to {}", xymon)))??;
+
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
+pub enum XymonMsg {
+    Status(ZoneDisplay),
+    Log(ZoneLog),
+}
+
+/// Queues zone statuses and sensor data, and sends them as combo messages
+#[derive(Debug)]
+pub struct XymonClient {
+    settings: Arc<XymonSettings>,
+    tx: mpsc::Sender<XymonMsg>,
+    rx: Option<mpsc::Receiver<XymonMsg>>,
+    task: Option<JoinHandle<()>>,
+}
+impl XymonClient {
+    pub fn new(settings: XymonSettings) -> Self {
+        let (tx, rx) = mpsc::channel(256);
+        Self {
+            settings: Arc::new(settings),
+            tx,
+            rx: Some(rx),
+            task: None,
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+    pub fn sender(&self) -> mpsc::Sender<XymonMsg> {
+        self.tx.clone()
+    }
+
+    pub fn run(&mut self, to_syslog: SysLogTx) {
+        let mut rx = match self.rx.take() {
+            Some(rx) => rx,
+            None => return,
+        };
+        let x = self.settings.clone();
+        let mut report = tokio::time::interval(x.report_interval);
+        let mut flush = tokio::time::interval(x.flush_interval);
+        flush.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        self.task = Some(tokio::spawn(async move {
+            let _ = to_syslog
+                .send(SysLog::new(format!("Spawned xymon client {}:{}", x.host, x.port)))
+                .await;
+            let mut latest: BTreeMap<(ZoneKind, u8), ZoneDisplay> = BTreeMap::new();
+            let mut changed: VecDeque<(ZoneKind, u8)> = VecDeque::new();
+            let mut values: BTreeMap<String, f64> = BTreeMap::new();
+            let mut values_changed = false;
+            let mut full_report = false;
+            let mut backoff = x.flush_interval;
+            let mut retry_at = tokio::time::Instant::now();
+            let mut failing = false;
+            loop {
+                tokio::select! {
+                    Some(data) = rx.recv() => {
+                        match data {
+                            XymonMsg::Status(zd) => {
+                                let key = zd.kind();
+                                latest.insert(key, zd);
+                                if !changed.contains(&key) {
+                                    changed.push_back(key);
+                                }
+                            }
+                            XymonMsg::Log(zl) => {
+                                for (name, value) in log_values(&zl) {
+                                    values.insert(name, value);
+                                    values_changed = true;
+                                }
+                            }
+                        }
+                    }
+                    _ = report.tick() => {
+                        full_report = true;
+                    }
+                    _ = flush.tick() => {
+                        if (changed.is_empty() & !full_report) | (tokio::time::Instant::now() < retry_at) {
+                            continue;
+                        }
+                        let keys: Vec<(ZoneKind, u8)> = match full_report {
+                            true => latest.keys().cloned().collect(),
+                            false => changed.iter().cloned().collect(),
+                        };
+                        let mut combo = String::from("combo\n");
+                        for key in &keys {
+                            if let Some(zd) = latest.get(key) {
+                                combo.push_str(&format_status(zd, &x));
+                                combo.push_str("\n\n");
+                            }
+                        }
+                        let send_values = full_report & values_changed;
+                        if send_values {
+                            combo.push_str(&format_data(&values, &x));
+                        }
+                        match send_message(&combo, &x).await.map_err(|e| e.to_string()) {
+                            Ok(()) => {
+                                changed.clear();
+                                full_report = false;
+                                if send_values {
+                                    values_changed = false;
+                                }
+                                if failing {
+                                    failing = false;
+                                    let _ = to_syslog
+                                        .send(SysLog::new(String::from("Xymon connection restored")).source("xymon"))
+                                        .await;
+                                }
+                                backoff = x.flush_interval;
+                            }
+                            Err(e) => {
+                                if !failing {
+                                    failing = true;
+                                    let _ = to_syslog
+                                        .send(SysLog::with_level(LogLevel::Warning, format!("Xymon send error: {}", e)).source("xymon"))
+                                        .await;
+                                }
+                                retry_at = tokio::time::Instant::now() + backoff;
+                                backoff = core::cmp::min(backoff * 2, x.max_backoff);
+                            }
+                        }
+                    }
+                    else => { break }
+                };
+            }
+        }));
+    }
+}
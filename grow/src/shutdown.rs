@@ -0,0 +1,161 @@
+//! Orderly shutdown, actuators to their safe state before the runners are stopped
+use core::fmt;
+use core::time::Duration;
+use std::time::Instant;
+
+use time::OffsetDateTime;
+
+use crate::ops::{LogLevel, SysLog};
+use crate::zone::arm::STEP_TIMEOUT;
+use crate::zone::{Zone, ZoneKind};
+use crate::House;
+
+/// Parts that did not reach their safe state or stop in time, by log source e.g. "pump/1"
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShutdownReport {
+    pub failed: Vec<(String, String)>,
+}
+impl ShutdownReport {
+    pub fn fail(&mut self, source: &str, reason: String) {
+        self.failed.push((source.to_string(), reason));
+    }
+    pub fn is_clean(&self) -> bool {
+        self.failed.is_empty()
+    }
+    pub fn merge(&mut self, other: ShutdownReport) {
+        self.failed.extend(other.failed);
+    }
+}
+impl fmt::Display for ShutdownReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "Shutdown clean");
+        }
+        write!(f, "Shutdown with {} problems:", self.failed.len())?;
+        for (source, reason) in &self.failed {
+            write!(f, "\n  {}: {}", source, reason)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn source(kind: ZoneKind, id: u8) -> String {
+    format!("{}/{}", format!("{:?}", kind).to_lowercase(), id)
+}
+
+impl House {
+    /// Stop pumps and refill, park arms, stop the runners, then leave fans and lamps
+    /// at their configured state. Waits at most `deadline` for arms and runners.
+    pub async fn shutdown(&mut self, deadline: Duration) -> ShutdownReport {
+        let started = Instant::now();
+        let remaining = || deadline.saturating_sub(started.elapsed());
+        let mut report = ShutdownReport::default();
+
+        // No water flowing while the arm moves
+        let mut pumps = Vec::new();
+        for zone in self.zones.iter() {
+            match zone {
+                Zone::Pump { id, interface, .. } => {
+                    if let Some(pump) = interface.pump.as_deref() {
+                        if let Err(e) = pump.stop().await {
+                            report.fail(&source(ZoneKind::Pump, *id), format!("Stop: {}", e));
+                        }
+                    }
+                    pumps.push(*id);
+                }
+                Zone::Tank { id, interface, .. } => {
+                    if let Some(valve) = interface.refill.as_deref() {
+                        if let Err(e) = valve.set_open(false) {
+                            report.fail(&source(ZoneKind::Tank, *id), format!("Close refill: {}", e));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        for id in pumps {
+            self.pump_stopped(id);
+        }
+
+        let arms: Vec<u8> = self
+            .zones
+            .iter()
+            .filter_map(|zone| match zone {
+                Zone::Arm { id, .. } => Some(*id),
+                _ => None,
+            })
+            .collect();
+        for id in arms {
+            // A move left by a cancelled caller would refuse the park
+            let handle = self.arm_handle(id);
+            if let Some(handle) = &handle {
+                handle.cancel().await;
+            }
+            let parked = match self.arm_plan_park(id) {
+                Ok(arm_move) => {
+                    match tokio::time::timeout(remaining(), arm_move.run(STEP_TIMEOUT)).await {
+                        Ok(result) => result.map(|_| ()).map_err(|e| e.to_string()),
+                        Err(_) => {
+                            if let Some(handle) = &handle {
+                                handle.cancel().await;
+                            }
+                            Err(String::from("Deadline passed"))
+                        }
+                    }
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = parked {
+                report.fail(&source(ZoneKind::Arm, id), format!("Park: {}", e));
+            }
+        }
+
+        for zone in self.zones.iter() {
+            zone.abort();
+        }
+        for zone in self.zones.iter_mut() {
+            if !zone.join(remaining()).await {
+                let (kind, id) = zone.key();
+                report.fail(&source(kind, id), String::from("Runner did not stop"));
+            }
+        }
+
+        // Runners are stopped and will not override these
+        let now = OffsetDateTime::now_utc().to_offset(crate::TIME_OFFSET).time();
+        for zone in self.zones.iter() {
+            match zone {
+                Zone::Air { id, settings, interface, .. } => {
+                    if let Some(fan) = interface.fan.as_ref() {
+                        if let Err(e) = fan.set_duty_cycle(settings.safe_duty_cycle) {
+                            report.fail(&source(ZoneKind::Air, *id), format!("Fan: {}", e));
+                        }
+                    }
+                }
+                Zone::Light { id, settings, interface, status, .. } => {
+                    if let Some(lamp) = interface.lamp.as_ref() {
+                        let state = settings.scheduled_state(now);
+                        match lamp.set_state(state) {
+                            Ok(()) => status.write().lamp_state = Some(state),
+                            Err(e) => report.fail(&source(ZoneKind::Light, *id), format!("Lamp: {}", e)),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (part, reason) in &report.failed {
+            let _ = self
+                .ops_tx
+                .syslog
+                .send(SysLog::with_level(LogLevel::Warning, format!("Shutdown: {}", reason)).source(part))
+                .await;
+        }
+        let _ = self
+            .ops_tx
+            .syslog
+            .send(SysLog::new(format!("House zones stopped in {:?}", started.elapsed())))
+            .await;
+        report
+    }
+}
@@ -0,0 +1,300 @@
+//! Restart zone runners and manager tasks that panic or end, with a growing delay
+use alloc::collections::BTreeMap;
+use core::fmt::Debug;
+use core::time::Duration;
+use std::sync::Arc;
+use std::time::Instant;
+
+use parking_lot::RwLock;
+use time::OffsetDateTime;
+use tokio::task::{JoinError, JoinHandle};
+
+use crate::ops::display::{DisplayStatus, Indicator};
+use crate::ops::{LogLevel, SysLog, SysLogTx};
+use crate::shutdown::{source, ShutdownReport};
+use crate::zone::ZoneKind;
+use crate::{run_zone, GrowError, House, HouseMutex};
+
+/// How often task handles are checked
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Delay before a restart, doubled for each crash in a row
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    /// A task running this long before crashing starts over at `initial`
+    pub reset_after: Duration,
+}
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(300),
+            reset_after: Duration::from_secs(600),
+        }
+    }
+}
+impl Backoff {
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.initial
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskState {
+    Running,
+    Restarting { at: Instant },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskHealth {
+    /// Log source style name, e.g. "light/1" or "manager/log"
+    pub name: String,
+    pub state: TaskState,
+    pub restarts: u32,
+    pub last_failure: Option<(OffsetDateTime, String)>,
+    attempt: u32,
+    running_since: Instant,
+}
+impl TaskHealth {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            state: TaskState::Running,
+            restarts: 0,
+            last_failure: None,
+            attempt: 0,
+            running_since: Instant::now(),
+        }
+    }
+    /// Record a crash and schedule the restart, returns the delay
+    fn failed(&mut self, reason: String, backoff: &Backoff) -> Duration {
+        if self.running_since.elapsed() > backoff.reset_after {
+            self.attempt = 0;
+        }
+        let delay = backoff.delay(self.attempt);
+        self.attempt += 1;
+        self.state = TaskState::Restarting { at: Instant::now() + delay };
+        self.last_failure = Some((OffsetDateTime::now_utc().to_offset(crate::TIME_OFFSET), reason));
+        delay
+    }
+    fn restarted(&mut self) {
+        self.state = TaskState::Running;
+        self.restarts += 1;
+        self.running_since = Instant::now();
+    }
+    fn restart_due(&self) -> bool {
+        matches!(self.state, TaskState::Restarting { at } if at <= Instant::now())
+    }
+}
+
+/// Task the supervisor can spawn again
+pub struct SupervisedTask {
+    name: String,
+    spawn: Box<dyn FnMut() -> JoinHandle<()> + Send>,
+    task: JoinHandle<()>,
+}
+impl SupervisedTask {
+    /// Spawns the task once now
+    pub fn new(name: &str, mut spawn: impl FnMut() -> JoinHandle<()> + Send + 'static) -> Self {
+        let task = spawn();
+        Self {
+            name: name.to_string(),
+            spawn: Box::new(spawn),
+            task,
+        }
+    }
+}
+impl Debug for SupervisedTask {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "SupervisedTask {{{}}}", self.name)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    backoff: Backoff,
+    health: Arc<RwLock<BTreeMap<String, TaskHealth>>>,
+    /// Manager tasks, shared with the supervising loop that restarts them
+    tasks: Arc<tokio::sync::Mutex<Vec<SupervisedTask>>>,
+    task: Option<JoinHandle<()>>,
+}
+impl Supervisor {
+    pub fn new(backoff: Backoff) -> Self {
+        Self {
+            backoff,
+            ..Default::default()
+        }
+    }
+
+    /// Health of the supervised tasks by name
+    pub fn health(&self) -> Vec<TaskHealth> {
+        self.health.read().values().cloned().collect()
+    }
+
+    /// Watch `tasks` and the runners of all zones in the house, also zones added later
+    pub fn run(&mut self, tasks: Vec<SupervisedTask>, house: HouseMutex, to_syslog: SysLogTx) {
+        let backoff = self.backoff;
+        let health = self.health.clone();
+        for t in &tasks {
+            health.write().insert(t.name.clone(), TaskHealth::new(&t.name));
+        }
+        self.tasks = Arc::new(tokio::sync::Mutex::new(tasks));
+        let tasks_mutex = self.tasks.clone();
+        self.task = Some(tokio::spawn(async move {
+            let _ = to_syslog.send(SysLog::new(String::from("Spawned supervisor"))).await;
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                let mut tasks = tasks_mutex.lock().await;
+                for t in tasks.iter_mut() {
+                    let restart_due = health.read().get(&t.name).is_some_and(|h| h.restart_due());
+                    let running = health.read().get(&t.name).is_some_and(|h| h.state == TaskState::Running);
+                    if running && t.task.is_finished() {
+                        let reason = exit_reason((&mut t.task).await);
+                        let delay = health.write().get_mut(&t.name).map(|h| h.failed(reason.clone(), &backoff));
+                        log_failure(&to_syslog, &t.name, &reason, delay).await;
+                    } else if restart_due {
+                        t.task = (t.spawn)();
+                        if let Some(h) = health.write().get_mut(&t.name) {
+                            h.restarted();
+                        }
+                        log_restart(&to_syslog, &t.name).await;
+                    }
+                }
+                let names: Vec<String> = tasks.iter().map(|t| t.name.clone()).collect();
+                drop(tasks);
+
+                let mut house = house.lock().await;
+                let keys: Vec<(ZoneKind, u8)> = house.zones.iter().map(|zone| zone.key()).collect();
+                for (kind, id) in keys.iter().copied() {
+                    let name = source(kind, id);
+                    let known = health.read().get(&name).cloned();
+                    match known {
+                        Some(h) if h.restart_due() => match house.restart_runner(kind, id) {
+                            Ok(()) => {
+                                if let Some(h) = health.write().get_mut(&name) {
+                                    h.restarted();
+                                }
+                                log_restart(&to_syslog, &name).await;
+                            }
+                            Err(e) => {
+                                let delay = health.write().get_mut(&name).map(|h| h.failed(e.to_string(), &backoff));
+                                log_failure(&to_syslog, &name, &e.to_string(), delay).await;
+                            }
+                        },
+                        Some(h) if h.state != TaskState::Running => {}
+                        _ => {
+                            if let Some(reason) = house.runner_exit(kind, id).await {
+                                let delay = health
+                                    .write()
+                                    .entry(name.clone())
+                                    .or_insert_with(|| TaskHealth::new(&name))
+                                    .failed(reason.clone(), &backoff);
+                                house.runner_crashed(kind, id, &reason);
+                                log_failure(&to_syslog, &name, &reason, Some(delay)).await;
+                            } else if known.is_none() {
+                                health.write().insert(name.clone(), TaskHealth::new(&name));
+                            }
+                        }
+                    }
+                }
+                drop(house);
+
+                // Forget zones taken out of the house
+                health.write().retain(|name, _| {
+                    names.contains(name) || keys.iter().any(|(kind, id)| &source(*kind, *id) == name)
+                });
+            }
+        }));
+    }
+
+    /// Stop restarting, tasks already running are left running
+    pub fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+    /// Abort the named manager tasks and wait at most `deadline` for them to end,
+    /// after `stop` so they are not restarted. Tasks still running are reported.
+    pub async fn abort_tasks(&mut self, names: &[&str], deadline: Duration) -> ShutdownReport {
+        let started = Instant::now();
+        let mut report = ShutdownReport::default();
+        let mut tasks = self.tasks.lock().await;
+        let (stopping, kept): (Vec<SupervisedTask>, Vec<SupervisedTask>) =
+            tasks.drain(..).partition(|t| names.contains(&t.name.as_str()));
+        *tasks = kept;
+        drop(tasks);
+        for t in &stopping {
+            t.task.abort();
+        }
+        for t in stopping {
+            let remaining = deadline.saturating_sub(started.elapsed());
+            if tokio::time::timeout(remaining, t.task).await.is_err() {
+                report.fail(&t.name, String::from("Not stopped in time"));
+            }
+            self.health.write().remove(&t.name);
+        }
+        report
+    }
+}
+
+fn exit_reason(result: Result<(), JoinError>) -> String {
+    match result {
+        Ok(()) => String::from("Ended"),
+        Err(e) if e.is_panic() => {
+            let panic = e.into_panic();
+            let msg = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            format!("Panicked {}", msg)
+        }
+        Err(_) => String::from("Cancelled"),
+    }
+}
+
+async fn log_failure(to_syslog: &SysLogTx, name: &str, reason: &str, delay: Option<Duration>) {
+    let _ = to_syslog
+        .send(SysLog::with_level(LogLevel::Error, format!("Task stopped: {}, restart in {:?}", reason, delay.unwrap_or_default())).source(name))
+        .await;
+}
+
+async fn log_restart(to_syslog: &SysLogTx, name: &str) {
+    let _ = to_syslog
+        .send(SysLog::with_level(LogLevel::Warning, String::from("Task restarted")).source(name))
+        .await;
+}
+
+impl House {
+    /// Why the runner of a zone ended, `None` while it runs
+    pub async fn runner_exit(&mut self, kind: ZoneKind, id: u8) -> Option<String> {
+        let zone = self.zones.get_mut(kind, id)?;
+        if !zone.task_mut().is_finished() {
+            return None;
+        }
+        // A handle must not be polled again after it completed
+        let task = core::mem::replace(zone.task_mut(), tokio::spawn(async {}));
+        Some(exit_reason(task.await))
+    }
+
+    /// Show a crashed runner red on the board until it reports again
+    pub fn runner_crashed(&mut self, kind: ZoneKind, id: u8, reason: &str) {
+        if let Some(zone) = self.zones.get(kind, id) {
+            zone.set_display_status(DisplayStatus::new(Indicator::Red, Some(format!("Runner crashed: {}", reason))));
+            let _ = self.zone_tx.zonestatus.send(zone.zone_display());
+        }
+    }
+
+    /// Spawn the runner of a zone again, the interface stays bound to the channels of the runner
+    pub fn restart_runner(&mut self, kind: ZoneKind, id: u8) -> Result<(), GrowError> {
+        let (tanks, pumps) = self.level_links();
+        let zone = self.zones.get_mut(kind, id).ok_or(GrowError::ZoneNotFound { kind, id })?;
+        run_zone(zone, &tanks, &pumps, &self.zone_tx, &self.ops_tx);
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use std::sync::Arc;
 use tokio::sync::{broadcast, mpsc};
 
 // use tokio::sync::Mutex;
+use crate::driver::Binding;
 use crate::ops::display::DisplayStatus;
 use light::LampState;
 use parking_lot::RwLock;
@@ -16,6 +17,12 @@ pub mod auxiliary;
 pub mod light;
 pub mod water;
 pub use water::{arm, pump, tank};
+pub mod registry;
+pub use registry::ZoneRegistry;
+pub mod lease;
+pub use lease::{Lease, LeaseHolder, LeaseInfo, Leases};
+pub mod view;
+pub use view::{ZoneStatus, ZoneView};
 
 pub type ZoneUpdateRx = tokio::sync::mpsc::Receiver<ZoneUpdate>;
 pub type ZoneUpdateTx = tokio::sync::mpsc::Sender<ZoneUpdate>;
@@ -91,6 +98,45 @@ pub enum Zone {
     },
 }
 impl Zone {
+    /// Registry key
+    pub fn key(&self) -> (ZoneKind, u8) {
+        match self {
+            Zone::Air { id, .. } => (ZoneKind::Air, *id),
+            Zone::Aux { id, .. } => (ZoneKind::Aux, *id),
+            Zone::Light { id, .. } => (ZoneKind::Light, *id),
+            Zone::Water { id, .. } => (ZoneKind::Water, *id),
+            Zone::Arm { id, .. } => (ZoneKind::Arm, *id),
+            Zone::Pump { id, .. } => (ZoneKind::Pump, *id),
+            Zone::Tank { id, .. } => (ZoneKind::Tank, *id),
+        }
+    }
+    /// Stop the runner task, the interface is released when the zone is dropped
+    pub fn abort(&self) {
+        match self {
+            Zone::Air { runner, .. } => runner.abort(),
+            Zone::Aux { runner, .. } => runner.abort(),
+            Zone::Light { runner, .. } => runner.abort(),
+            Zone::Water { runner, .. } => runner.abort(),
+            Zone::Arm { runner, .. } => runner.abort(),
+            Zone::Pump { runner, .. } => runner.abort(),
+            Zone::Tank { runner, .. } => runner.abort(),
+        }
+    }
+    pub fn task_mut(&mut self) -> &mut tokio::task::JoinHandle<()> {
+        match self {
+            Zone::Air { runner, .. } => runner.task_mut(),
+            Zone::Aux { runner, .. } => runner.task_mut(),
+            Zone::Light { runner, .. } => runner.task_mut(),
+            Zone::Water { runner, .. } => runner.task_mut(),
+            Zone::Arm { runner, .. } => runner.task_mut(),
+            Zone::Pump { runner, .. } => runner.task_mut(),
+            Zone::Tank { runner, .. } => runner.task_mut(),
+        }
+    }
+    /// Wait for the runner task to end after `abort`, false if still running at `deadline`
+    pub async fn join(&mut self, deadline: core::time::Duration) -> bool {
+        tokio::time::timeout(deadline, self.task_mut()).await.is_ok()
+    }
     pub fn display_status(&self) -> DisplayStatus {
         match self {
             Zone::Air { status, .. } => status.read().disp.clone(),
@@ -102,6 +148,17 @@ impl Zone {
             Zone::Aux { status, .. } => status.read().disp.clone(),
         }
     }
+    pub fn set_display_status(&self, ds: DisplayStatus) {
+        match self {
+            Zone::Air { status, .. } => status.write().disp = ds,
+            Zone::Light { status, .. } => status.write().disp = ds,
+            Zone::Water { status, .. } => status.write().disp = ds,
+            Zone::Arm { status, .. } => status.write().disp = ds,
+            Zone::Tank { status, .. } => status.write().disp = ds,
+            Zone::Pump { status, .. } => status.write().disp = ds,
+            Zone::Aux { status, .. } => status.write().disp = ds,
+        }
+    }
     pub fn zone_display(&self) -> ZoneDisplay {
         match self {
             Zone::Air { id, status, .. } => ZoneDisplay::Air {
@@ -152,7 +209,7 @@ pub enum ZoneUpdate {
     },
     Arm {
         id: u8,
-        state: arm::ArmState,
+        state: arm::ArmMode,
         x: i32,
         y: i32,
         z: i32,
@@ -180,10 +237,19 @@ pub enum ZoneLog {
     },
     Tank {
         id: u8,
+        level: Option<tank::TankLevel>,
+        /// From continuous level sensors
+        percent: Option<f32>,
+        /// Forecast from recent pump use
+        days_left: Option<f32>,
+        refilling: bool,
         changed_status: Option<DisplayStatus>,
     },
     Pump {
         id: u8,
+        state: pump::PumpState,
+        /// Motor speed feedback
+        speed: Option<i8>,
         changed_status: Option<DisplayStatus>,
     },
     Arm {
@@ -208,9 +274,49 @@ pub enum ZoneDisplay {
     Pump { id: u8, info: DisplayStatus },
     Arm { id: u8, info: DisplayStatus },
     Aux { id: u8, info: DisplayStatus },
+    /// Controller health on id 0, grow stage on id 1
+    House { id: u8, info: DisplayStatus },
+}
+impl ZoneDisplay {
+    pub fn kind(&self) -> (ZoneKind, u8) {
+        match self {
+            ZoneDisplay::Air { id, .. } => (ZoneKind::Air, *id),
+            ZoneDisplay::Light { id, .. } => (ZoneKind::Light, *id),
+            ZoneDisplay::Water { id, .. } => (ZoneKind::Water, *id),
+            ZoneDisplay::Tank { id, .. } => (ZoneKind::Tank, *id),
+            ZoneDisplay::Pump { id, .. } => (ZoneKind::Pump, *id),
+            ZoneDisplay::Arm { id, .. } => (ZoneKind::Arm, *id),
+            ZoneDisplay::Aux { id, .. } => (ZoneKind::Aux, *id),
+            ZoneDisplay::House { id, .. } => (ZoneKind::House, *id),
+        }
+    }
+    pub fn info(&self) -> &DisplayStatus {
+        match self {
+            ZoneDisplay::Air { info, .. } => info,
+            ZoneDisplay::Light { info, .. } => info,
+            ZoneDisplay::Water { info, .. } => info,
+            ZoneDisplay::Tank { info, .. } => info,
+            ZoneDisplay::Pump { info, .. } => info,
+            ZoneDisplay::Arm { info, .. } => info,
+            ZoneDisplay::Aux { info, .. } => info,
+            ZoneDisplay::House { info, .. } => info,
+        }
+    }
+    pub fn set_info(&mut self, status: DisplayStatus) {
+        match self {
+            ZoneDisplay::Air { info, .. } => *info = status,
+            ZoneDisplay::Light { info, .. } => *info = status,
+            ZoneDisplay::Water { info, .. } => *info = status,
+            ZoneDisplay::Tank { info, .. } => *info = status,
+            ZoneDisplay::Pump { info, .. } => *info = status,
+            ZoneDisplay::Arm { info, .. } => *info = status,
+            ZoneDisplay::Aux { info, .. } => *info = status,
+            ZoneDisplay::House { info, .. } => *info = status,
+        }
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
 pub enum ZoneKind {
     Air,
     Aux,
@@ -219,6 +325,191 @@ pub enum ZoneKind {
     Arm,
     Pump,
     Tank,
+    /// The controller itself, not a zone in the house
+    House,
+}
+
+/// Zone settings that can be adjusted while the zone is running
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Threshold {
+    AirTempWarning,
+    AirTempAlert,
+    AirFanLow,
+    AirFanHigh,
+    AirFanRpmAlert,
+    LightLevelWarning,
+    LightLevelAlert,
+    MoistureLowAlert,
+    MoistureLowWarning,
+    MoistureLimitWater,
+    MoistureHighWarning,
+    MoistureHighAlert,
+}
+impl Threshold {
+    pub fn for_kind(kind: ZoneKind) -> &'static [Threshold] {
+        match kind {
+            ZoneKind::Air => &[
+                Threshold::AirTempWarning,
+                Threshold::AirTempAlert,
+                Threshold::AirFanLow,
+                Threshold::AirFanHigh,
+                Threshold::AirFanRpmAlert,
+            ],
+            ZoneKind::Light => &[
+                Threshold::LightLevelWarning,
+                Threshold::LightLevelAlert,
+            ],
+            ZoneKind::Water => &[
+                Threshold::MoistureLimitWater,
+                Threshold::MoistureLowWarning,
+                Threshold::MoistureLowAlert,
+                Threshold::MoistureHighWarning,
+                Threshold::MoistureHighAlert,
+            ],
+            _ => &[],
+        }
+    }
+    pub fn kind(&self) -> ZoneKind {
+        match self {
+            Threshold::AirTempWarning
+            | Threshold::AirTempAlert
+            | Threshold::AirFanLow
+            | Threshold::AirFanHigh
+            | Threshold::AirFanRpmAlert => ZoneKind::Air,
+            Threshold::LightLevelWarning | Threshold::LightLevelAlert => ZoneKind::Light,
+            _ => ZoneKind::Water,
+        }
+    }
+    /// Short label for small displays
+    pub fn label(&self) -> &'static str {
+        match self {
+            Threshold::AirTempWarning => "Temp warn",
+            Threshold::AirTempAlert => "Temp alert",
+            Threshold::AirFanLow => "Fan low",
+            Threshold::AirFanHigh => "Fan high",
+            Threshold::AirFanRpmAlert => "Fan rpm",
+            Threshold::LightLevelWarning => "Light warn",
+            Threshold::LightLevelAlert => "Light alert",
+            Threshold::MoistureLowAlert => "Low alert",
+            Threshold::MoistureLowWarning => "Low warn",
+            Threshold::MoistureLimitWater => "Water at",
+            Threshold::MoistureHighWarning => "High warn",
+            Threshold::MoistureHighAlert => "High alert",
+        }
+    }
+    /// Increment used when adjusting the value step by step
+    pub fn step(&self) -> f64 {
+        match self {
+            Threshold::AirTempWarning
+            | Threshold::AirTempAlert
+            | Threshold::AirFanLow
+            | Threshold::AirFanHigh => 0.5,
+            Threshold::AirFanRpmAlert => 50.0,
+            Threshold::LightLevelWarning | Threshold::LightLevelAlert => 10.0,
+            _ => 1.0,
+        }
+    }
+    /// Allowed values regardless of the other settings
+    pub fn range(&self) -> (f64, f64) {
+        match self {
+            Threshold::AirTempWarning
+            | Threshold::AirTempAlert
+            | Threshold::AirFanLow
+            | Threshold::AirFanHigh => (-20.0, 60.0),
+            Threshold::AirFanRpmAlert => (0.0, 10000.0),
+            Threshold::LightLevelWarning | Threshold::LightLevelAlert => (0.0, 100000.0),
+            _ => (0.0, 100.0),
+        }
+    }
+    /// Thresholds of one zone that have to stay in ascending order
+    fn order(&self) -> &'static [Threshold] {
+        match self {
+            Threshold::AirTempWarning | Threshold::AirTempAlert => &[Threshold::AirTempWarning, Threshold::AirTempAlert],
+            Threshold::AirFanLow | Threshold::AirFanHigh => &[Threshold::AirFanLow, Threshold::AirFanHigh],
+            Threshold::AirFanRpmAlert => &[Threshold::AirFanRpmAlert],
+            Threshold::LightLevelWarning | Threshold::LightLevelAlert => &[Threshold::LightLevelAlert, Threshold::LightLevelWarning],
+            _ => &[
+                Threshold::MoistureLowAlert,
+                Threshold::MoistureLowWarning,
+                Threshold::MoistureLimitWater,
+                Threshold::MoistureHighWarning,
+                Threshold::MoistureHighAlert,
+            ],
+        }
+    }
+    /// Allowed values given the current values of the other thresholds,
+    /// e.g. a warning can not pass its alert and the fan low temperature can not pass the high one
+    pub fn bounds_with(&self, current: impl Fn(Threshold) -> Option<f64>) -> (f64, f64) {
+        let (mut min, mut max) = self.range();
+        let order = self.order();
+        if let Some(index) = order.iter().position(|t| t == self) {
+            if let Some(below) = order[..index].iter().rev().find_map(|t| current(*t)) {
+                min = min.max(below);
+            }
+            if let Some(above) = order[index + 1..].iter().find_map(|t| current(*t)) {
+                max = max.min(above);
+            }
+        }
+        (min, max)
+    }
+    pub fn bounds(&self, zone: &Zone) -> (f64, f64) {
+        self.bounds_with(|t| t.get(zone))
+    }
+    pub fn get(&self, zone: &Zone) -> Option<f64> {
+        match (self, zone) {
+            (Threshold::AirTempWarning, Zone::Air { settings, .. }) => Some(settings.temp_high_yellow_warning),
+            (Threshold::AirTempAlert, Zone::Air { settings, .. }) => Some(settings.temp_high_red_alert),
+            (Threshold::AirFanLow, Zone::Air { settings, .. }) => Some(settings.temp_fan_low as f64),
+            (Threshold::AirFanHigh, Zone::Air { settings, .. }) => Some(settings.temp_fan_high as f64),
+            (Threshold::AirFanRpmAlert, Zone::Air { settings, .. }) => Some(settings.fan_rpm_low_red_alert as f64),
+            (Threshold::LightLevelWarning, Zone::Light { settings, .. }) => Some(settings.lightlevel_low_yellow_warning as f64),
+            (Threshold::LightLevelAlert, Zone::Light { settings, .. }) => Some(settings.lightlevel_low_red_alert as f64),
+            (Threshold::MoistureLowAlert, Zone::Water { settings, .. }) => Some(settings.moisture_low_red_alert as f64),
+            (Threshold::MoistureLowWarning, Zone::Water { settings, .. }) => Some(settings.moisture_low_yellow_warning as f64),
+            (Threshold::MoistureLimitWater, Zone::Water { settings, .. }) => Some(settings.moisture_limit_water as f64),
+            (Threshold::MoistureHighWarning, Zone::Water { settings, .. }) => Some(settings.moisture_high_yellow_warning as f64),
+            (Threshold::MoistureHighAlert, Zone::Water { settings, .. }) => Some(settings.moisture_high_red_alert as f64),
+            _ => None,
+        }
+    }
+    /// Update zone settings and pass them on to the running zone, returns false if the zone has no such setting
+    pub fn set(&self, zone: &mut Zone, value: f64) -> bool {
+        match zone {
+            Zone::Air { settings, runner, .. } => {
+                match self {
+                    Threshold::AirTempWarning => settings.temp_high_yellow_warning = value,
+                    Threshold::AirTempAlert => settings.temp_high_red_alert = value,
+                    Threshold::AirFanLow => settings.temp_fan_low = value as f32,
+                    Threshold::AirFanHigh => settings.temp_fan_high = value as f32,
+                    Threshold::AirFanRpmAlert => settings.fan_rpm_low_red_alert = value as f32,
+                    _ => return false,
+                }
+                let _ = runner.settings_sender().send(settings.clone());
+            }
+            Zone::Light { settings, runner, .. } => {
+                match self {
+                    Threshold::LightLevelWarning => settings.lightlevel_low_yellow_warning = value as f32,
+                    Threshold::LightLevelAlert => settings.lightlevel_low_red_alert = value as f32,
+                    _ => return false,
+                }
+                let _ = runner.settings_sender().send(settings.clone());
+            }
+            Zone::Water { settings, runner, .. } => {
+                match self {
+                    Threshold::MoistureLowAlert => settings.moisture_low_red_alert = value as f32,
+                    Threshold::MoistureLowWarning => settings.moisture_low_yellow_warning = value as f32,
+                    Threshold::MoistureLimitWater => settings.moisture_limit_water = value as f32,
+                    Threshold::MoistureHighWarning => settings.moisture_high_yellow_warning = value as f32,
+                    Threshold::MoistureHighAlert => settings.moisture_high_red_alert = value as f32,
+                    _ => return false,
+                }
+                let _ = runner.settings_sender().send(settings.clone());
+            }
+            _ => return false,
+        }
+
+        true
+    }
 }
 
 pub fn zone_channels() -> (ZoneChannelsTx, ZoneChannelsRx) {
@@ -240,54 +531,110 @@ pub fn zone_channels() -> (ZoneChannelsTx, ZoneChannelsRx) {
 }
 
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ZoneSave {
     Air {
         id: u8,
         settings: air::Settings,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        hardware: Vec<Binding>,
     },
     Aux {
         id: u8,
         settings: auxiliary::Settings,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        hardware: Vec<Binding>,
     },
     Light {
         id: u8,
         settings: light::Settings,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        hardware: Vec<Binding>,
     },
     Water {
         id: u8,
         settings: water::Settings,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        hardware: Vec<Binding>,
     },
     Arm {
         id: u8,
         settings: arm::Settings,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        hardware: Vec<Binding>,
     },
     Pump {
         id: u8,
         settings: pump::Settings,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        hardware: Vec<Binding>,
     },
     Tank {
         id: u8,
         settings: tank::Settings,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        hardware: Vec<Binding>,
     },
 }
+impl ZoneSave {
+    /// Registry key and the hardware bindings of the saved zone
+    pub fn bindings(&self) -> ((ZoneKind, u8), &Vec<Binding>) {
+        match self {
+            ZoneSave::Air { id, hardware, .. } => ((ZoneKind::Air, *id), hardware),
+            ZoneSave::Aux { id, hardware, .. } => ((ZoneKind::Aux, *id), hardware),
+            ZoneSave::Light { id, hardware, .. } => ((ZoneKind::Light, *id), hardware),
+            ZoneSave::Water { id, hardware, .. } => ((ZoneKind::Water, *id), hardware),
+            ZoneSave::Arm { id, hardware, .. } => ((ZoneKind::Arm, *id), hardware),
+            ZoneSave::Pump { id, hardware, .. } => ((ZoneKind::Pump, *id), hardware),
+            ZoneSave::Tank { id, hardware, .. } => ((ZoneKind::Tank, *id), hardware),
+        }
+    }
+}
 
-// #[derive(Clone, Debug)]
-// pub enum ZoneCmd {
-//     // Air { id: u8, sender: broadcast::Sender<AirCmd>, },
-//     // Aux { id: u8, sender: broadcast::Sender<AuxCmd>, },
-//     Light {
-//         id: u8,
-//         sender: broadcast::Sender<(u8, bool)>,
-//     },
-//     // Water { id: u8, sender: broadcast::Sender<WaterCmd>, },
-//     Arm {
-//         id: u8,
-//         sender: broadcast::Sender<ArmCmd>,
-//     },
-//     Pump {
-//         id: u8,
-//         sender: broadcast::Sender<(u8, PumpCmd)>,
-//     },
-//     // Tank { id: u8, sender: broadcast::Sender<(u8, TankCmd)>, },
-// }
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moisture(t: Threshold) -> Option<f64> {
+        match t {
+            Threshold::MoistureLowAlert => Some(20.0),
+            Threshold::MoistureLowWarning => Some(30.0),
+            Threshold::MoistureLimitWater => Some(50.0),
+            Threshold::MoistureHighWarning => Some(90.0),
+            Threshold::MoistureHighAlert => Some(100.0),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn bounds_keep_order() {
+        assert_eq!(Threshold::MoistureLimitWater.bounds_with(moisture), (30.0, 90.0));
+        assert_eq!(Threshold::MoistureLowAlert.bounds_with(moisture), (0.0, 30.0));
+        assert_eq!(Threshold::MoistureHighAlert.bounds_with(moisture), (90.0, 100.0));
+        let air = |t| match t {
+            Threshold::AirFanLow => Some(25.0),
+            Threshold::AirFanHigh => Some(30.0),
+            Threshold::AirTempAlert => Some(40.0),
+            _ => None,
+        };
+        assert_eq!(Threshold::AirFanLow.bounds_with(air), (-20.0, 30.0));
+        assert_eq!(Threshold::AirFanHigh.bounds_with(air), (25.0, 60.0));
+        assert_eq!(Threshold::AirTempWarning.bounds_with(air), (-20.0, 40.0));
+        let light = |t| match t {
+            Threshold::LightLevelAlert => Some(80.0),
+            _ => None,
+        };
+        assert_eq!(Threshold::LightLevelWarning.bounds_with(light), (80.0, 100000.0));
+    }
+
+    #[test]
+    fn bounds_skip_missing() {
+        let partial = |t| match t {
+            Threshold::MoistureLowAlert => Some(20.0),
+            Threshold::MoistureHighAlert => Some(80.0),
+            _ => None,
+        };
+        assert_eq!(Threshold::MoistureLimitWater.bounds_with(partial), (20.0, 80.0));
+        assert_eq!(Threshold::AirFanRpmAlert.bounds_with(|_| None), (0.0, 10000.0));
+    }
+}
@@ -1,6 +1,6 @@
 
 use async_trait::async_trait;
-use core::error::Error;
+use crate::GrowError;
 use parking_lot::RwLock;
 use std::sync::Arc;
 use tokio::sync::broadcast;
@@ -15,8 +15,9 @@ pub type FanMutex = Arc<Mutex<Box<dyn Fan>>>;
 use super::Zone;
 use super::*;
 use crate::ops::display::{DisplayStatus, Indicator};
+use crate::ops::heartbeat::HEARTBEAT_INTERVAL;
 use crate::ops::OpsChannelsTx;
-use crate::ops::SysLog;
+use crate::ops::{LogLevel, SysLog, SysLogTx};
 // use crate::TIME_OFFSET;
 
 pub fn new(id: u8, settings: Settings) -> super::Zone {
@@ -24,10 +25,12 @@ pub fn new(id: u8, settings: Settings) -> super::Zone {
         temp: None,
         fan_rpm: None,
         fan_mode: None,
+        fan_forced: None,
         disp: DisplayStatus {
             indicator: Default::default(),
             msg: None,
             changed: OffsetDateTime::UNIX_EPOCH,
+            lease: None,
         },
     };
     let status_mutex = Arc::new(RwLock::new(status));
@@ -50,6 +53,9 @@ pub struct Settings {
     pub temp_fan_low: f32,
     pub temp_fan_high: f32,
     pub fan_rpm_low_red_alert: f32,
+    /// Fan duty cycle 0.0-1.0 left at shutdown
+    #[serde(default)]
+    pub safe_duty_cycle: f64,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -57,6 +63,8 @@ pub struct Status {
     pub temp: Option<f64>,
     pub fan_rpm: Option<f32>,
     pub fan_mode: Option<FanSetting>,
+    /// Set by a rule instead of the temperature, e.g. High in a heat wave
+    pub fan_forced: Option<FanSetting>,
     pub disp: DisplayStatus,
 }
 
@@ -74,14 +82,14 @@ pub trait Fan: Send {
         &mut self,
         tx_rpm: tokio::sync::broadcast::Sender<(u8, Option<f32>)>,
         rx_control: tokio::sync::broadcast::Receiver<FanSetting>,
-    ) -> Result<(), Box<dyn Error>>;
-    fn read(&mut self) -> Result<Option<f32>, Box<dyn Error + '_>>;
-    fn to_high(&self) -> Result<(), Box<dyn Error + '_>>;
-    fn to_low(&self) -> Result<(), Box<dyn Error + '_>>;
+    ) -> Result<(), GrowError>;
+    fn read(&mut self) -> Result<Option<f32>, GrowError>;
+    fn to_high(&self) -> Result<(), GrowError>;
+    fn to_low(&self) -> Result<(), GrowError>;
     fn set_duty_cycle(
         &self,
         duty_cycle: f64,
-    ) -> Result<(), Box<dyn Error + '_>>;
+    ) -> Result<(), GrowError>;
 }
 impl Debug for dyn Fan {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -94,8 +102,8 @@ pub trait Thermometer: Send {
     async fn init(
         &mut self,
         tx_temp: tokio::sync::broadcast::Sender<(u8, Option<f64>)>,
-    ) -> Result<(), Box<dyn Error>>;
-    fn read(&self) -> Result<f64, Box<dyn Error + '_>>;
+    ) -> Result<(), GrowError>;
+    fn read(&self) -> Result<f64, GrowError>;
 }
 impl Debug for dyn Thermometer {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -103,7 +111,7 @@ impl Debug for dyn Thermometer {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone, PartialOrd)]
+#[derive(Debug, PartialEq, Copy, Clone, PartialOrd, Serialize, Deserialize)]
 pub enum FanSetting {
     Off,
     Low,
@@ -117,8 +125,10 @@ pub enum FanSetting {
 pub struct Runner {
     id: u8,
     tx_fan_control: broadcast::Sender<FanSetting>,
+    tx_fan_force: broadcast::Sender<Option<FanSetting>>,
     tx_fan_rpm: broadcast::Sender<(u8, Option<f32>)>,
     temp: broadcast::Sender<(u8, Option<f64>)>,
+    tx_settings: broadcast::Sender<Settings>,
     task: tokio::task::JoinHandle<()>,
     status: Arc<RwLock<Status>>,
 }
@@ -128,8 +138,10 @@ impl Runner {
             id,
             status,
             tx_fan_control: broadcast::channel(1).0,
+            tx_fan_force: broadcast::channel(1).0,
             tx_fan_rpm: broadcast::channel(8).0,
             temp: broadcast::channel(1).0,
+            tx_settings: broadcast::channel(1).0,
             task: tokio::spawn(async move {}),
         }
     }
@@ -147,6 +159,23 @@ impl Runner {
     ) -> broadcast::Sender<(u8, Option<f64>)> {
         self.temp.clone()
     }
+    pub fn settings_sender(&self) -> broadcast::Sender<Settings> {
+        self.tx_settings.clone()
+    }
+    pub fn fan_cmd_sender(&self) -> broadcast::Sender<FanSetting> {
+        self.tx_fan_control.clone()
+    }
+    /// Fan mode held regardless of temperature, `None` gives control back to the runner
+    pub fn fan_force_sender(&self) -> broadcast::Sender<Option<FanSetting>> {
+        self.tx_fan_force.clone()
+    }
+
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+    pub fn task_mut(&mut self) -> &mut tokio::task::JoinHandle<()> {
+        &mut self.task
+    }
 
     pub fn run(
         &mut self,
@@ -160,9 +189,14 @@ impl Runner {
         let to_status_subscribers = zone_channels.zonestatus;
         let to_logger = zone_channels.zonelog;
         let to_syslog = ops_channels.syslog;
+        let heartbeat = ops_channels.heartbeats.register(&format!("air/{}", id));
+        let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
         let status = self.status.clone();
         let mut rx_rpm = self.tx_fan_rpm.subscribe();
         let mut rx_temp = self.temp.subscribe();
+        let mut rx_settings = self.tx_settings.subscribe();
+        let mut rx_force = self.tx_fan_force.subscribe();
+        let mut settings = settings;
         let tx_fan = self.tx_fan_control.clone();
         let mut requested_fan_mode: FanSetting = FanSetting::Off;
 
@@ -189,6 +223,16 @@ impl Runner {
             let mut buf_fan_ind = Indicator::Blue;
             loop {
                 tokio::select! {
+                    Ok(new_settings) = rx_settings.recv() => {
+                        settings = new_settings;
+                    }
+                    Ok(forced) = rx_force.recv() => {
+                        status.write().fan_forced = forced;
+                        if have_fan {
+                            let mode = forced.unwrap_or(requested_fan_mode);
+                            set_fan_mode(id, mode, &tx_fan, &status, &to_syslog).await;
+                        }
+                    }
                     Ok(data) = rx_rpm.recv() => {
                         // println!("\tFan rpm: {:?}", data);
                         let o_ds: Option<DisplayStatus>; // = None;
@@ -258,18 +302,8 @@ impl Runner {
 
                         // Set fan speed
                         if have_fan {
-                            let current_mode = status.read().fan_mode;
-                            if !(current_mode.is_some_and(|x|x == requested_fan_mode))  {
-                                match tx_fan.send(requested_fan_mode) {
-                                    Ok(_) => {
-                                        let _ = to_syslog.send(SysLog::new(format!("Air {} fan set to {:?}", &id, &requested_fan_mode))).await;
-                                        status.write().fan_mode = Some(requested_fan_mode);
-                                    },
-                                    Err(e) => {
-                                        let _ = to_syslog.send(SysLog::new(format!("Air {} fan error: {:?}", &id, e))).await;
-                                    }
-                                }
-                            }
+                            let mode = status.read().fan_forced.unwrap_or(requested_fan_mode);
+                            set_fan_mode(id, mode, &tx_fan, &status, &to_syslog).await;
                         }
                         let fan_rpm = status.read().fan_rpm;
                         let _ = to_logger.send(ZoneLog::Air {id: data.0, temp: data.1, fan_rpm, changed_status: o_ds.clone() }).await;
@@ -279,9 +313,33 @@ impl Runner {
                         }
 
                     }
+                    _ = beat.tick() => heartbeat.beat(),
                     else => { break }
                 };
             }
         });
     }
 }
+
+/// Send the fan mode to the fan unless it is already set
+async fn set_fan_mode(
+    id: u8,
+    mode: FanSetting,
+    tx_fan: &broadcast::Sender<FanSetting>,
+    status: &Arc<RwLock<Status>>,
+    to_syslog: &SysLogTx,
+) {
+    let current_mode = status.read().fan_mode;
+    if current_mode.is_some_and(|x| x == mode) {
+        return;
+    }
+    match tx_fan.send(mode) {
+        Ok(_) => {
+            let _ = to_syslog.send(SysLog::new(format!("Air {} fan set to {:?}", &id, &mode)).source(&format!("air/{}", id))).await;
+            status.write().fan_mode = Some(mode);
+        }
+        Err(e) => {
+            let _ = to_syslog.send(SysLog::with_level(LogLevel::Error, format!("Air {} fan error: {:?}", &id, e)).source(&format!("air/{}", id))).await;
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use core::error::Error;
+use crate::GrowError;
 use parking_lot::RwLock;
 
 use std::sync::Arc;
@@ -13,6 +13,7 @@ use serde::{Serialize, Deserialize};
 use super::Zone;
 use super::*;
 use crate::ops::display::{DisplayStatus, Indicator};
+use crate::ops::heartbeat::HEARTBEAT_INTERVAL;
 use crate::ops::OpsChannelsTx;
 use crate::ops::SysLog;
 // use crate::TIME_OFFSET;
@@ -23,6 +24,7 @@ pub fn new(id: u8, settings: Settings) -> super::Zone {
             indicator: Default::default(),
             msg: None,
             changed: OffsetDateTime::UNIX_EPOCH,
+            lease: None,
         },
     };
     let status_mutex = Arc::new(RwLock::new(status));
@@ -54,8 +56,8 @@ pub trait AuxDevice: Send + Sync {
     async fn init(
         &mut self,
         tx: tokio::sync::broadcast::Sender<(u8, DisplayStatus)>,
-    ) -> Result<(), Box<dyn Error>>;
-    fn read(&self) -> Result<String, Box<dyn Error + '_>>;
+    ) -> Result<(), GrowError>;
+    fn read(&self) -> Result<String, GrowError>;
 }
 impl Debug for dyn AuxDevice {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -86,6 +88,13 @@ impl Runner {
         self.tx_auxiliary.clone()
     }
 
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+    pub fn task_mut(&mut self) -> &mut tokio::task::JoinHandle<()> {
+        &mut self.task
+    }
+
     pub fn run(
         &mut self,
         _settings: Settings,
@@ -97,6 +106,8 @@ impl Runner {
         let to_status_subscribers = zone_channels.zonestatus;
         let to_logger = zone_channels.zonelog;
         let to_syslog = ops_channels.syslog;
+        let heartbeat = ops_channels.heartbeats.register(&format!("aux/{}", id));
+        let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
         let mut rx = self.tx_auxiliary.subscribe();
         let status = self.status.clone();
         self.task = tokio::spawn(async move {
@@ -122,6 +133,7 @@ impl Runner {
                             // _ => ()
                         }
                     }
+                    _ = beat.tick() => heartbeat.beat(),
                     else => { break }
                 };
             }
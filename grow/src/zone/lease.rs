@@ -0,0 +1,282 @@
+//! Exclusive, time-limited leases on shared actuators, e.g. an arm or pump used by several water zones
+use alloc::collections::BTreeMap;
+use core::fmt;
+use core::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use parking_lot::RwLock;
+use tokio_util::sync::CancellationToken;
+
+use super::{ZoneKind, ZoneStatusTx, ZoneView};
+use crate::GrowError;
+
+/// Time limit of a lease for a manual command, e.g. a pump run from the console
+pub const MANUAL_LEASE: Duration = Duration::from_secs(30);
+
+/// Who asks for a lease, a higher priority preempts a lower one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeaseHolder {
+    /// Automatic watering of a water zone
+    Watering(u8),
+    /// Text display menu and panel buttons
+    Panel,
+    Console,
+    /// Remote control position mode
+    Remote,
+}
+impl LeaseHolder {
+    pub fn priority(&self) -> u8 {
+        match self {
+            LeaseHolder::Watering(_) => 0,
+            LeaseHolder::Panel | LeaseHolder::Console => 1,
+            LeaseHolder::Remote => 2,
+        }
+    }
+}
+impl fmt::Display for LeaseHolder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LeaseHolder::Watering(id) => write!(f, "water/{}", id),
+            LeaseHolder::Panel => write!(f, "panel"),
+            LeaseHolder::Console => write!(f, "console"),
+            LeaseHolder::Remote => write!(f, "rc"),
+        }
+    }
+}
+
+/// Lease as listed by `Leases::held`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaseInfo {
+    pub kind: ZoneKind,
+    pub id: u8,
+    pub holder: LeaseHolder,
+    pub until: Instant,
+}
+
+#[derive(Debug)]
+struct Held {
+    holder: LeaseHolder,
+    serial: u64,
+    until: Instant,
+    revoked: CancellationToken,
+}
+
+/// Leases by zone, shown in the zone's display status while held
+#[derive(Clone, Debug)]
+pub struct Leases {
+    held: Arc<RwLock<BTreeMap<(ZoneKind, u8), Held>>>,
+    serial: Arc<AtomicU64>,
+    view: ZoneView,
+    to_status_subscribers: ZoneStatusTx,
+}
+impl Leases {
+    pub fn new(view: ZoneView, to_status_subscribers: ZoneStatusTx) -> Self {
+        Self {
+            held: Arc::new(RwLock::new(BTreeMap::new())),
+            serial: Arc::new(AtomicU64::new(0)),
+            view,
+            to_status_subscribers,
+        }
+    }
+
+    /// Lease the zone for `duration`. A lease held with the same or higher priority refuses,
+    /// a lower one is preempted. Expired leases count as free.
+    pub fn acquire(
+        &self,
+        kind: ZoneKind,
+        id: u8,
+        holder: LeaseHolder,
+        duration: Duration,
+    ) -> Result<Lease, GrowError> {
+        let now = Instant::now();
+        let serial = self.serial.fetch_add(1, Ordering::Relaxed);
+        let until = now + duration;
+        let revoked = CancellationToken::new();
+        {
+            let mut held = self.held.write();
+            if let Some(current) = held.get(&(kind, id)) {
+                if (current.until > now) & (current.holder.priority() >= holder.priority()) {
+                    return Err(GrowError::Leased { kind, id, holder: current.holder.to_string() });
+                }
+                current.revoked.cancel();
+            }
+            held.insert((kind, id), Held { holder, serial, until, revoked: revoked.clone() });
+        }
+        self.publish(kind, id, Some(holder));
+        Ok(Lease {
+            kind,
+            id,
+            holder,
+            serial,
+            until,
+            revoked,
+            leases: self.clone(),
+        })
+    }
+    pub fn holder(&self, kind: ZoneKind, id: u8) -> Option<LeaseHolder> {
+        self.held
+            .read()
+            .get(&(kind, id))
+            .filter(|held| held.until > Instant::now())
+            .map(|held| held.holder)
+    }
+    /// Leases not yet released, expired ones included until their holder lets go
+    pub fn held(&self) -> Vec<LeaseInfo> {
+        self.held
+            .read()
+            .iter()
+            .map(|((kind, id), held)| LeaseInfo { kind: *kind, id: *id, holder: held.holder, until: held.until })
+            .collect()
+    }
+
+    fn release(&self, kind: ZoneKind, id: u8, serial: u64) {
+        let released = {
+            let mut held = self.held.write();
+            match held.get(&(kind, id)) {
+                Some(current) if current.serial == serial => held.remove(&(kind, id)).is_some(),
+                _ => false,
+            }
+        };
+        if released {
+            self.publish(kind, id, None);
+        }
+    }
+    fn publish(&self, kind: ZoneKind, id: u8, holder: Option<LeaseHolder>) {
+        if let Some(display) = self.view.set_lease(kind, id, holder.map(|h| h.to_string())) {
+            let _ = self.to_status_subscribers.send(display);
+        }
+    }
+}
+
+/// Held until dropped, preempted by a higher priority or at the time limit
+#[derive(Debug)]
+pub struct Lease {
+    kind: ZoneKind,
+    id: u8,
+    holder: LeaseHolder,
+    serial: u64,
+    until: Instant,
+    revoked: CancellationToken,
+    leases: Leases,
+}
+impl Lease {
+    pub fn holder(&self) -> LeaseHolder {
+        self.holder
+    }
+    pub fn until(&self) -> Instant {
+        self.until
+    }
+    /// `LeaseLost` once preempted or past the time limit, checked before each use of the actuator
+    pub fn check(&self) -> Result<(), GrowError> {
+        let reason = if self.revoked.is_cancelled() {
+            match self.leases.holder(self.kind, self.id) {
+                Some(by) => format!("preempted by {}", by),
+                None => String::from("preempted"),
+            }
+        } else if Instant::now() >= self.until {
+            String::from("expired")
+        } else {
+            return Ok(());
+        };
+        Err(GrowError::LeaseLost { kind: self.kind, id: self.id, reason })
+    }
+    /// Wait until preempted or past the time limit
+    pub async fn lost(&self) {
+        tokio::select! {
+            _ = self.revoked.cancelled() => {}
+            _ = tokio::time::sleep_until(self.until.into()) => {}
+        }
+    }
+    /// Keep the lease after the caller is done, e.g. for a timed pump run, until lost
+    pub fn keep(self) {
+        tokio::spawn(async move {
+            self.lost().await;
+        });
+    }
+}
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.leases.release(self.kind, self.id, self.serial);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast;
+
+    fn leases() -> Leases {
+        Leases::new(ZoneView::default(), broadcast::channel(8).0)
+    }
+
+    #[test]
+    fn priority_order() {
+        assert!(LeaseHolder::Watering(1).priority() < LeaseHolder::Panel.priority());
+        assert_eq!(LeaseHolder::Panel.priority(), LeaseHolder::Console.priority());
+        assert!(LeaseHolder::Console.priority() < LeaseHolder::Remote.priority());
+    }
+
+    #[test]
+    fn higher_priority_preempts() {
+        let leases = leases();
+        let watering = leases.acquire(ZoneKind::Arm, 1, LeaseHolder::Watering(2), MANUAL_LEASE).unwrap();
+        assert!(watering.check().is_ok());
+        let console = leases.acquire(ZoneKind::Arm, 1, LeaseHolder::Console, MANUAL_LEASE).unwrap();
+        assert!(console.check().is_ok());
+        match watering.check() {
+            Err(GrowError::LeaseLost { reason, .. }) => assert_eq!(reason, "preempted by console"),
+            other => panic!("Expected LeaseLost, got {:?}", other),
+        }
+        assert_eq!(leases.holder(ZoneKind::Arm, 1), Some(LeaseHolder::Console));
+        // Lower priority is refused while the higher one holds
+        assert!(matches!(
+            leases.acquire(ZoneKind::Arm, 1, LeaseHolder::Watering(2), MANUAL_LEASE),
+            Err(GrowError::Leased { .. })
+        ));
+    }
+
+    #[test]
+    fn same_priority_refused() {
+        let leases = leases();
+        let _panel = leases.acquire(ZoneKind::Pump, 1, LeaseHolder::Panel, MANUAL_LEASE).unwrap();
+        match leases.acquire(ZoneKind::Pump, 1, LeaseHolder::Console, MANUAL_LEASE) {
+            Err(GrowError::Leased { holder, .. }) => assert_eq!(holder, "panel"),
+            other => panic!("Expected Leased, got {:?}", other),
+        }
+        // Other zones are leased separately
+        assert!(leases.acquire(ZoneKind::Pump, 2, LeaseHolder::Console, MANUAL_LEASE).is_ok());
+        assert!(leases.acquire(ZoneKind::Arm, 1, LeaseHolder::Console, MANUAL_LEASE).is_ok());
+    }
+
+    #[tokio::test]
+    async fn expired_lease_is_free() {
+        let leases = leases();
+        let remote = leases.acquire(ZoneKind::Arm, 1, LeaseHolder::Remote, Duration::from_millis(20)).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), remote.lost()).await.unwrap();
+        match remote.check() {
+            Err(GrowError::LeaseLost { reason, .. }) => assert_eq!(reason, "expired"),
+            other => panic!("Expected LeaseLost, got {:?}", other),
+        }
+        assert_eq!(leases.holder(ZoneKind::Arm, 1), None);
+        // Listed until released
+        assert_eq!(leases.held().len(), 1);
+        let watering = leases.acquire(ZoneKind::Arm, 1, LeaseHolder::Watering(1), MANUAL_LEASE).unwrap();
+        assert!(watering.check().is_ok());
+    }
+
+    #[test]
+    fn release_only_own_lease() {
+        let leases = leases();
+        let first = leases.acquire(ZoneKind::Arm, 1, LeaseHolder::Panel, MANUAL_LEASE).unwrap();
+        let second = leases.acquire(ZoneKind::Arm, 1, LeaseHolder::Remote, MANUAL_LEASE).unwrap();
+        // The preempted lease dropped late must not free the zone for the new holder
+        drop(first);
+        assert_eq!(leases.holder(ZoneKind::Arm, 1), Some(LeaseHolder::Remote));
+        assert_eq!(leases.held().len(), 1);
+        drop(second);
+        assert_eq!(leases.holder(ZoneKind::Arm, 1), None);
+        assert!(leases.held().is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 
 
-use core::error::Error;
+use crate::GrowError;
 use parking_lot::RwLock;
 use std::sync::Arc;
 use tokio::sync::broadcast;
@@ -16,6 +16,7 @@ use serde::{Serialize, Deserialize};
 use super::Zone;
 use super::*;
 use crate::ops::display::{DisplayStatus, Indicator};
+use crate::ops::heartbeat::HEARTBEAT_INTERVAL;
 use crate::ops::OpsChannelsTx;
 use crate::ops::SysLog;
 // use crate::TIME_OFFSET;
@@ -28,6 +29,7 @@ pub fn new(id: u8, settings: Settings) -> super::Zone {
             indicator: Default::default(),
             msg: None,
             changed: OffsetDateTime::UNIX_EPOCH,
+            lease: None,
         },
         kind: None,
     };
@@ -51,6 +53,16 @@ pub struct Settings {
     pub lamp_on: Time,
     pub lamp_off: Time,
 }
+impl Settings {
+    /// Lamp state by the on/off times, as the runner switches it
+    pub fn scheduled_state(&self, now: Time) -> LampState {
+        if now > self.lamp_off || now <= self.lamp_on {
+            LampState::Off
+        } else {
+            LampState::On
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Status {
@@ -66,7 +78,7 @@ pub struct Interface {
     pub lightmeter: Option<Box<dyn Lightmeter>>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum LampState {
     On,
     Off,
@@ -77,9 +89,9 @@ pub trait Lamp: Send {
     fn init(
         &mut self,
         rx_lamp: tokio::sync::broadcast::Receiver<(u8, bool)>,
-    ) -> Result<(), Box<dyn Error>>;
-    fn set_state(&self, state: LampState) -> Result<(), Box<dyn Error + '_>>;
-    fn state(&self) -> Result<LampState, Box<dyn Error>>;
+    ) -> Result<(), GrowError>;
+    fn set_state(&self, state: LampState) -> Result<(), GrowError>;
+    fn state(&self) -> Result<LampState, GrowError>;
 }
 impl Debug for dyn Lamp {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -92,8 +104,8 @@ pub trait Lightmeter: Send {
     fn init(
         &mut self,
         tx_light: tokio::sync::broadcast::Sender<(u8, Option<f32>)>,
-    ) -> Result<(), Box<dyn Error>>;
-    fn read(&self) -> Result<f32, Box<dyn Error + '_>>;
+    ) -> Result<(), GrowError>;
+    fn read(&self) -> Result<f32, GrowError>;
 }
 impl Debug for dyn Lightmeter {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -107,6 +119,7 @@ pub struct Runner {
     status: Arc<RwLock<Status>>,
     tx_lightmeter: broadcast::Sender<(u8, Option<f32>)>,
     tx_lamp: broadcast::Sender<(u8, bool)>,
+    tx_settings: broadcast::Sender<Settings>,
     task: tokio::task::JoinHandle<()>,
 }
 impl Runner {
@@ -114,6 +127,7 @@ impl Runner {
         Self {
             tx_lightmeter: broadcast::channel(1).0,
             tx_lamp: broadcast::channel(1).0,
+            tx_settings: broadcast::channel(1).0,
             task: tokio::spawn(async move {}),
             id,
             status,
@@ -131,6 +145,16 @@ impl Runner {
     pub fn lamp_cmd_sender(&self) -> broadcast::Sender<(u8, bool)> {
         self.tx_lamp.clone()
     }
+    pub fn settings_sender(&self) -> broadcast::Sender<Settings> {
+        self.tx_settings.clone()
+    }
+
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+    pub fn task_mut(&mut self) -> &mut tokio::task::JoinHandle<()> {
+        &mut self.task
+    }
 
     pub fn run(
         &mut self,
@@ -143,7 +167,11 @@ impl Runner {
         let to_status_subscribers = zone_channels.zonestatus;
         let to_logger = zone_channels.zonelog;
         let to_syslog = ops_channels.syslog;
+        let heartbeat = ops_channels.heartbeats.register(&format!("light/{}", id));
+        let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
         let mut rx = self.tx_lightmeter.subscribe();
+        let mut rx_settings = self.tx_settings.subscribe();
+        let mut settings = settings;
         let status = self.status.clone();
         let to_lamp = self.lamp_cmd_sender();
         let mut each_minute = tokio::time::interval(Duration::from_secs(60));
@@ -162,6 +190,9 @@ impl Runner {
             ));
             loop {
                 tokio::select! {
+                    Ok(new_settings) = rx_settings.recv() => {
+                        settings = new_settings;
+                    }
                     Ok(data) = rx.recv() => {
                         // println!("Light: {:?}", data);
                         let o_ds: Option<DisplayStatus>; // = None;
@@ -204,7 +235,7 @@ impl Runner {
                                 Some(LampState::On) | None => {
                                     let _ = to_lamp.send((id, false));
                                     status.write().lamp_state = Some(LampState::Off);
-                                    let _ = to_syslog.send(SysLog::new(format!("Lamp OFF @ {} (Set: {}", crate::ops::display::format_time(now), settings.lamp_off))).await;
+                                    let _ = to_syslog.send(SysLog::new(format!("Lamp OFF @ {} (Set: {}", crate::ops::display::format_time(now), settings.lamp_off)).source(&format!("light/{}", id))).await;
                                 }
                                 _ => {}
                             }
@@ -214,7 +245,7 @@ impl Runner {
                                 Some(LampState::Off) | None => {
                                     let _ = to_lamp.send((id, true));
                                     status.write().lamp_state = Some(LampState::On);
-                                    let _ = to_syslog.send(SysLog::new(format!("Lamp ON @ {} (Set: {}", crate::ops::display::format_time(now), settings.lamp_on))).await;
+                                    let _ = to_syslog.send(SysLog::new(format!("Lamp ON @ {} (Set: {}", crate::ops::display::format_time(now), settings.lamp_on)).source(&format!("light/{}", id))).await;
                                 }
                                 _ => {}
                             }
@@ -236,6 +267,7 @@ impl Runner {
                         //     }
                         // }
                     }
+                    _ = beat.tick() => heartbeat.beat(),
                     else => { break }
                 };
             }
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use super::{Zone, ZoneKind, ZoneView};
+use crate::error::GrowError;
+
+/// Zones in config order, indexed by kind and id
+#[derive(Debug, Default)]
+pub struct ZoneRegistry {
+    zones: Vec<Zone>,
+    index: HashMap<(ZoneKind, u8), usize>,
+    view: ZoneView,
+}
+impl ZoneRegistry {
+    pub fn new(zones: Vec<Zone>) -> Self {
+        let mut registry = Self::default();
+        for zone in zones {
+            registry.push(zone);
+        }
+        registry
+    }
+
+    /// Add a zone, replacing and returning a zone with the same kind and id
+    pub fn push(&mut self, zone: Zone) -> Option<Zone> {
+        self.view.insert(&zone);
+        match self.index.get(&zone.key()) {
+            Some(&i) => Some(core::mem::replace(&mut self.zones[i], zone)),
+            None => {
+                self.index.insert(zone.key(), self.zones.len());
+                self.zones.push(zone);
+                None
+            }
+        }
+    }
+
+    /// Remove a zone, keeping the order of the others
+    pub fn remove(&mut self, kind: ZoneKind, id: u8) -> Option<Zone> {
+        let i = self.index.remove(&(kind, id))?;
+        self.view.remove(kind, id);
+        let zone = self.zones.remove(i);
+        for pos in self.index.values_mut() {
+            if *pos > i {
+                *pos -= 1;
+            }
+        }
+        Some(zone)
+    }
+
+    /// Statuses of the zones, for reading without holding the registry
+    pub fn view(&self) -> ZoneView {
+        self.view.clone()
+    }
+
+    pub fn get(&self, kind: ZoneKind, id: u8) -> Option<&Zone> {
+        self.index.get(&(kind, id)).map(|&i| &self.zones[i])
+    }
+    pub fn get_mut(&mut self, kind: ZoneKind, id: u8) -> Option<&mut Zone> {
+        self.index.get(&(kind, id)).map(|&i| &mut self.zones[i])
+    }
+    /// As `get`, with `ZoneNotFound` for a missing zone
+    pub fn find(&self, kind: ZoneKind, id: u8) -> Result<&Zone, GrowError> {
+        self.get(kind, id).ok_or(GrowError::ZoneNotFound { kind, id })
+    }
+    pub fn find_mut(&mut self, kind: ZoneKind, id: u8) -> Result<&mut Zone, GrowError> {
+        self.get_mut(kind, id).ok_or(GrowError::ZoneNotFound { kind, id })
+    }
+    pub fn contains(&self, kind: ZoneKind, id: u8) -> bool {
+        self.index.contains_key(&(kind, id))
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, Zone> {
+        self.zones.iter()
+    }
+    pub fn iter_mut(&mut self) -> core::slice::IterMut<'_, Zone> {
+        self.zones.iter_mut()
+    }
+    pub fn len(&self) -> usize {
+        self.zones.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.zones.is_empty()
+    }
+}
+impl<'a> IntoIterator for &'a ZoneRegistry {
+    type Item = &'a Zone;
+    type IntoIter = core::slice::Iter<'a, Zone>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.zones.iter()
+    }
+}
+impl<'a> IntoIterator for &'a mut ZoneRegistry {
+    type Item = &'a mut Zone;
+    type IntoIter = core::slice::IterMut<'a, Zone>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.zones.iter_mut()
+    }
+}
@@ -0,0 +1,127 @@
+//! Zone statuses readable without the House lock, e.g. for the board during a watering cycle
+use alloc::collections::BTreeMap;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use super::{air, arm, auxiliary, light, pump, tank, water, Zone, ZoneDisplay, ZoneKind};
+use crate::ops::display::DisplayStatus;
+
+/// Status of one zone, shared with its runner
+#[derive(Clone, Debug)]
+pub enum ZoneStatus {
+    Air(Arc<RwLock<air::Status>>),
+    Aux(Arc<RwLock<auxiliary::Status>>),
+    Light(Arc<RwLock<light::Status>>),
+    Water(Arc<RwLock<water::Status>>),
+    Arm(Arc<RwLock<arm::Status>>),
+    Pump(Arc<RwLock<pump::Status>>),
+    Tank(Arc<RwLock<tank::Status>>),
+}
+impl ZoneStatus {
+    pub fn display_status(&self) -> DisplayStatus {
+        match self {
+            ZoneStatus::Air(status) => status.read().disp.clone(),
+            ZoneStatus::Aux(status) => status.read().disp.clone(),
+            ZoneStatus::Light(status) => status.read().disp.clone(),
+            ZoneStatus::Water(status) => status.read().disp.clone(),
+            ZoneStatus::Arm(status) => status.read().disp.clone(),
+            ZoneStatus::Pump(status) => status.read().disp.clone(),
+            ZoneStatus::Tank(status) => status.read().disp.clone(),
+        }
+    }
+    fn set_lease(&self, lease: Option<String>) {
+        match self {
+            ZoneStatus::Air(status) => status.write().disp.lease = lease,
+            ZoneStatus::Aux(status) => status.write().disp.lease = lease,
+            ZoneStatus::Light(status) => status.write().disp.lease = lease,
+            ZoneStatus::Water(status) => status.write().disp.lease = lease,
+            ZoneStatus::Arm(status) => status.write().disp.lease = lease,
+            ZoneStatus::Pump(status) => status.write().disp.lease = lease,
+            ZoneStatus::Tank(status) => status.write().disp.lease = lease,
+        }
+    }
+    fn zone_display(&self, id: u8) -> ZoneDisplay {
+        let info = self.display_status();
+        match self {
+            ZoneStatus::Air(_) => ZoneDisplay::Air { id, info },
+            ZoneStatus::Aux(_) => ZoneDisplay::Aux { id, info },
+            ZoneStatus::Light(_) => ZoneDisplay::Light { id, info },
+            ZoneStatus::Water(_) => ZoneDisplay::Water { id, info },
+            ZoneStatus::Arm(_) => ZoneDisplay::Arm { id, info },
+            ZoneStatus::Pump(_) => ZoneDisplay::Pump { id, info },
+            ZoneStatus::Tank(_) => ZoneDisplay::Tank { id, info },
+        }
+    }
+}
+impl From<&Zone> for ZoneStatus {
+    fn from(zone: &Zone) -> Self {
+        match zone {
+            Zone::Air { status, .. } => ZoneStatus::Air(status.clone()),
+            Zone::Aux { status, .. } => ZoneStatus::Aux(status.clone()),
+            Zone::Light { status, .. } => ZoneStatus::Light(status.clone()),
+            Zone::Water { status, .. } => ZoneStatus::Water(status.clone()),
+            Zone::Arm { status, .. } => ZoneStatus::Arm(status.clone()),
+            Zone::Pump { status, .. } => ZoneStatus::Pump(status.clone()),
+            Zone::Tank { status, .. } => ZoneStatus::Tank(status.clone()),
+        }
+    }
+}
+
+/// Statuses of the zones in the house, kept in step by `ZoneRegistry`
+#[derive(Clone, Debug, Default)]
+pub struct ZoneView {
+    zones: Arc<RwLock<BTreeMap<(ZoneKind, u8), ZoneStatus>>>,
+}
+impl ZoneView {
+    pub(crate) fn insert(&self, zone: &Zone) {
+        self.zones.write().insert(zone.key(), ZoneStatus::from(zone));
+    }
+    pub(crate) fn remove(&self, kind: ZoneKind, id: u8) {
+        self.zones.write().remove(&(kind, id));
+    }
+
+    pub fn status(&self, kind: ZoneKind, id: u8) -> Option<ZoneStatus> {
+        self.zones.read().get(&(kind, id)).cloned()
+    }
+    pub fn display_status(&self, kind: ZoneKind, id: u8) -> Option<DisplayStatus> {
+        self.zones.read().get(&(kind, id)).map(|status| status.display_status())
+    }
+    pub fn collect_display_status(&self) -> Vec<ZoneDisplay> {
+        self.zones
+            .read()
+            .iter()
+            .map(|((_, id), status)| status.zone_display(*id))
+            .collect()
+    }
+    /// Show the lease holder in the zone's display status, returns the display to publish
+    pub fn set_lease(&self, kind: ZoneKind, id: u8, lease: Option<String>) -> Option<ZoneDisplay> {
+        let status = self.status(kind, id)?;
+        status.set_lease(lease);
+        Some(status.zone_display(id))
+    }
+    pub fn tank_status(&self, id: u8) -> Option<Arc<RwLock<tank::Status>>> {
+        match self.status(ZoneKind::Tank, id) {
+            Some(ZoneStatus::Tank(status)) => Some(status),
+            _ => None,
+        }
+    }
+    pub fn air_status(&self, id: u8) -> Option<Arc<RwLock<air::Status>>> {
+        match self.status(ZoneKind::Air, id) {
+            Some(ZoneStatus::Air(status)) => Some(status),
+            _ => None,
+        }
+    }
+    pub fn water_status(&self, id: u8) -> Option<Arc<RwLock<water::Status>>> {
+        match self.status(ZoneKind::Water, id) {
+            Some(ZoneStatus::Water(status)) => Some(status),
+            _ => None,
+        }
+    }
+    pub fn light_status(&self, id: u8) -> Option<Arc<RwLock<light::Status>>> {
+        match self.status(ZoneKind::Light, id) {
+            Some(ZoneStatus::Light(status)) => Some(status),
+            _ => None,
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use super::Zone;
 use super::ZoneUpdate;
 use async_trait::async_trait;
-use core::error::Error;
+use crate::GrowError;
 use core::fmt::Debug;
 use core::time::Duration;
 use std::time::Instant;
@@ -17,6 +17,7 @@ use tokio::sync::broadcast;
 use serde::{Serialize, Deserialize};
 
 use crate::ops::display::{DisplayStatus, Indicator};
+use crate::ops::heartbeat::HEARTBEAT_INTERVAL;
 use crate::ops::OpsChannelsTx;
 use crate::ops::SysLog;
 use crate::zone::*;
@@ -32,8 +33,11 @@ pub fn new(id: u8, settings: Settings) -> super::Zone {
             indicator: Default::default(),
             msg: None,
             changed: OffsetDateTime::UNIX_EPOCH,
+            lease: None,
         },
         kind: None,
+        last_watering: None,
+        waterings_today: 0,
     };
     let status_mutex = Arc::new(RwLock::new(status));
     Zone::Water {
@@ -45,7 +49,7 @@ pub fn new(id: u8, settings: Settings) -> super::Zone {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Settings {
     pub moisture_low_red_alert: f32,
     pub moisture_low_yellow_warning: f32,
@@ -57,6 +61,40 @@ pub struct Settings {
     pub pump_time: Duration,
     pub settling_time: Duration,
     pub position: super::arm::Position,
+    /// Named positions on the arm, watered in sequence instead of `position`
+    #[serde(default)]
+    pub positions: Vec<String>,
+    /// Waypoints with their own pump times, used instead of `positions` when set
+    #[serde(default)]
+    pub route: Vec<Waypoint>,
+}
+
+/// Named position in the arm's library, or coordinates
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Target {
+    Named(String),
+    At(arm::Point),
+}
+
+/// One point of a watering route
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Waypoint {
+    pub at: Target,
+    /// Pump time when arrived, `None` uses the zone's `pump_time`
+    #[serde(default)]
+    pub pump_time: Option<Duration>,
+    /// Pump while moving in a straight line from the previous waypoint
+    #[serde(default)]
+    pub pump_on_way: bool,
+}
+
+/// Waypoint resolved to arm coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RouteStep {
+    pub position: arm::Position,
+    pub pump_time: Duration,
+    pub pump_on_way: bool,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -64,6 +102,27 @@ pub struct Status {
     pub moisture_level: Option<f32>,
     pub disp: DisplayStatus,
     kind: Option<WaterStatusKind>,
+    /// Last watering requested by the runner, kept over restarts
+    pub last_watering: Option<OffsetDateTime>,
+    pub waterings_today: u32,
+}
+impl Status {
+    fn watered(&mut self) {
+        let now = OffsetDateTime::now_utc().to_offset(crate::TIME_OFFSET);
+        if self.last_watering.map(|t| t.date()) != Some(now.date()) {
+            self.waterings_today = 0;
+        }
+        self.waterings_today += 1;
+        self.last_watering = Some(now);
+    }
+    /// Instant of the last watering for the settling time, now if none or in the future
+    fn previous_watering(&self) -> Instant {
+        let now = Instant::now();
+        self.last_watering
+            .and_then(|at| Duration::try_from(OffsetDateTime::now_utc() - at).ok())
+            .and_then(|ago| now.checked_sub(ago))
+            .unwrap_or(now)
+    }
 }
 
 #[async_trait]
@@ -72,8 +131,8 @@ pub trait MoistureSensor: Send {
     fn init(
         &mut self,
         tx_moist: tokio::sync::broadcast::Sender<(u8, Option<f32>)>,
-    ) -> Result<(), Box<dyn Error>>;
-    fn read(&self) -> Result<f32, Box<dyn Error + '_>>;
+    ) -> Result<(), GrowError>;
+    fn read(&self) -> Result<f32, GrowError>;
 }
 
 #[derive(Debug)]
@@ -108,6 +167,7 @@ impl Debug for dyn MoistureSensor {
 pub struct Runner {
     id: u8,
     tx_moisture: broadcast::Sender<(u8, Option<f32>)>,
+    tx_settings: broadcast::Sender<Settings>,
 
     pub task: tokio::task::JoinHandle<()>,
     status: Arc<RwLock<Status>>,
@@ -118,6 +178,7 @@ impl Runner {
             id,
             status,
             tx_moisture: broadcast::channel(168).0,
+            tx_settings: broadcast::channel(1).0,
             task: tokio::spawn(async move {}),
         }
     }
@@ -127,6 +188,16 @@ impl Runner {
     ) -> broadcast::Sender<(u8, Option<f32>)> {
         self.tx_moisture.clone()
     }
+    pub fn settings_sender(&self) -> broadcast::Sender<Settings> {
+        self.tx_settings.clone()
+    }
+
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+    pub fn task_mut(&mut self) -> &mut tokio::task::JoinHandle<()> {
+        &mut self.task
+    }
 
     pub fn run(
         &mut self,
@@ -140,7 +211,11 @@ impl Runner {
         let to_status_subscribers = zone_channels.zonestatus;
         let to_logger = zone_channels.zonelog;
         let to_syslog = ops_channels.syslog;
+        let heartbeat = ops_channels.heartbeats.register(&format!("water/{}", id));
+        let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
         let mut rx = self.tx_moisture.subscribe();
+        let mut rx_settings = self.tx_settings.subscribe();
+        let mut settings = settings;
         let status = self.status.clone();
         let mut interval = tokio::time::interval(settings.settling_time);
 
@@ -158,9 +233,15 @@ impl Runner {
                 Some(format!("Water running")),
             ));
 
-            let mut previous_watering = Instant::now();
+            let mut previous_watering = status.read().previous_watering();
             loop {
                 tokio::select! {
+                    Ok(new_settings) = rx_settings.recv() => {
+                        if new_settings.settling_time != settings.settling_time {
+                            interval = tokio::time::interval(new_settings.settling_time);
+                        }
+                        settings = new_settings;
+                    }
                     Ok(data) = rx.recv() => {                        
                         let mut o_ds: Option<DisplayStatus> = None;
                         status.write().moisture_level = data.1;
@@ -195,8 +276,9 @@ impl Runner {
 
                                 // Init watering if moisture changed to below limit & settling time expired
                                 if (moisture < settings.moisture_limit_water) & (previous_watering.elapsed() > settings.settling_time) {
-                                    let _ = to_manager.send(ZoneUpdate::Water{id, settings, status: status.clone()}).await;
+                                    let _ = to_manager.send(ZoneUpdate::Water{id, settings: settings.clone(), status: status.clone()}).await;
                                     previous_watering = Instant::now();
+                                    status.write().watered();
                                 }
                             },
                             _ => ()
@@ -213,10 +295,12 @@ impl Runner {
                         // println!("TICK Water {} moist:{:?} limit:{} elapsed:{:?} settling:{:?}", id, status.read().moisture_level, settings.moisture_limit_water, previous_watering.elapsed(), settings.settling_time);
                         
                         if (previous_watering.elapsed() > settings.settling_time) & (status.read().moisture_level.is_some_and(|m| m < settings.moisture_limit_water)) {
-                            let _ = to_manager.send(ZoneUpdate::Water{id, settings, status: status.clone()}).await;
+                            let _ = to_manager.send(ZoneUpdate::Water{id, settings: settings.clone(), status: status.clone()}).await;
                             previous_watering = Instant::now();
+                            status.write().watered();
                         }
                     }
+                    _ = beat.tick() => heartbeat.beat(),
                     else => { break }
                 };
             }
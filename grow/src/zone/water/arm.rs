@@ -1,8 +1,9 @@
 
 use async_trait::async_trait;
-use core::error::Error;
+use std::collections::{BTreeMap, VecDeque};
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time::Instant;
 
 // use tokio::sync::Mutex;
 use core::fmt::Debug;
@@ -13,13 +14,28 @@ use serde::{Serialize, Deserialize};
 
 use super::Zone;
 use super::*;
-use crate::ops::display::{DisplayStatus};
+use crate::GrowError;
+use crate::ops::display::{DisplayStatus, Indicator};
+use crate::ops::heartbeat::HEARTBEAT_INTERVAL;
+use crate::ops::{OpsChannelsTx, SysLog, LogLevel};
+use core::time::Duration;
 
 
 // use crate::TIME_OFFSET;
 pub type ControlFeedbackRx = broadcast::Receiver<ArmState>;
 pub type ControlFeedbackTx = broadcast::Sender<ArmState>;
 
+/// Longest time for one step of a planned move
+pub const STEP_TIMEOUT: Duration = Duration::from_secs(20);
+/// Longest time without axis movement before a move counts as stalled
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Acceptable deviation from target, in encoder units
+pub const POSITION_TOLERANCE: i32 = 5;
+/// Longest step of a line move, in encoder units
+pub const LINE_STEP: i32 = 50;
+/// Most steps of a move from `Settings::plan`: retract z, move x/y, lower z
+pub const PLAN_STEPS: u32 = 3;
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ArmCmd {
     Confirm, // Not implemented
@@ -31,6 +47,7 @@ pub enum ArmCmd {
     Goto { x: i32, y: i32 },
     GotoX { x: i32 },
     GotoY { y: i32 },
+    GotoZ { z: i32 },
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -46,16 +63,33 @@ pub enum ArmState {
     Busy,
 }
 
+/// Arm zone runner state
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ArmMode {
+    #[default]
+    Idle,
+    Homing,
+    Moving,
+    /// Kept in position, e.g. while watering
+    Holding,
+    /// Stalled or timed out, needs homing before moving again
+    Fault,
+}
+
 pub fn new(id: u8, settings: Settings) -> super::Zone {
     let status = Status {
         pos_x: 0,
         pos_y: 0,
         pos_z: 0,
+        mode: ArmMode::Idle,
+        target: None,
         disp: DisplayStatus {
             indicator: Default::default(),
             msg: None,
             changed: OffsetDateTime::UNIX_EPOCH,
+            lease: None,
         },
+        calibration: None,
     };
     let status_mutex = Arc::new(RwLock::new(status));
     Zone::Arm {
@@ -67,8 +101,212 @@ pub fn new(id: u8, settings: Settings) -> super::Zone {
     }
 }
 
+/// Soft limits and home position for one axis
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
-pub struct Settings {}
+pub struct Axis {
+    pub min: i32,
+    pub max: i32,
+    pub home: i32,
+}
+impl Default for Axis {
+    fn default() -> Self {
+        Self {
+            min: i32::MIN,
+            max: i32::MAX,
+            home: 0,
+        }
+    }
+}
+impl Axis {
+    pub fn contains(&self, pos: i32) -> bool {
+        (pos >= self.min) & (pos <= self.max)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub x: Axis,
+    pub y: Axis,
+    pub z: Axis,
+    /// Z position for moving in x/y without hitting plants
+    pub z_travel: i32,
+    /// Taught positions by name, e.g. "pot-3" or "park"
+    pub positions: BTreeMap<String, Point>,
+}
+impl Settings {
+    pub fn home(&self) -> (i32, i32, i32) {
+        (self.x.home, self.y.home, self.z.home)
+    }
+
+    pub fn position(&self, name: &str) -> Option<(i32, i32, i32)> {
+        self.positions.get(name).map(|p| p.xyz())
+    }
+
+    /// Store a position under `name`, replacing any position with the same name
+    pub fn teach(&mut self, name: &str, pos: (i32, i32, i32)) -> Result<(), GrowError> {
+        if name.trim().is_empty() {
+            return Err(GrowError::Config(String::from("Position name is empty")));
+        }
+        self.check(pos.0, pos.1, pos.2)?;
+        self.positions.insert(name.trim().to_string(), Point { x: pos.0, y: pos.1, z: pos.2 });
+        Ok(())
+    }
+
+    /// Reject targets outside the soft limits
+    pub fn check(&self, x: i32, y: i32, z: i32) -> Result<(), GrowError> {
+        for (name, axis, pos) in [("x", self.x, x), ("y", self.y, y), ("z", self.z, z)] {
+            if !axis.contains(pos) {
+                return Err(GrowError::Config(format!(
+                    "Arm {} target {} outside limits {}..{}",
+                    name, pos, axis.min, axis.max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Named position "park" if taught, otherwise home
+    pub fn park(&self) -> (i32, i32, i32) {
+        self.position("park").unwrap_or(self.home())
+    }
+
+    /// Split a straight x/y move at constant height into short steps, so the arm follows the line
+    pub fn plan_line(
+        &self,
+        from: (i32, i32, i32),
+        to: (i32, i32, i32),
+    ) -> Result<Vec<MoveStep>, GrowError> {
+        self.check(to.0, to.1, to.2)?;
+        if from.2 != to.2 {
+            return Err(GrowError::Config(format!(
+                "Arm line move needs constant z, from {} to {}",
+                from.2, to.2
+            )));
+        }
+        let count = line_steps(from, to);
+        Ok((1..=count)
+            .map(|i| {
+                MoveStep::XY(
+                    from.0 + (to.0 - from.0) * i / count,
+                    from.1 + (to.1 - from.1) * i / count,
+                )
+            })
+            .collect())
+    }
+
+    /// Split a move into steps: retract z, move x/y, lower z
+    pub fn plan(
+        &self,
+        from: (i32, i32, i32),
+        to: (i32, i32, i32),
+    ) -> Result<Vec<MoveStep>, GrowError> {
+        self.check(to.0, to.1, to.2)?;
+        if !self.z.contains(self.z_travel) {
+            return Err(GrowError::Config(format!(
+                "Arm travel height {} outside z limits {}..{}",
+                self.z_travel, self.z.min, self.z.max
+            )));
+        }
+        let mut steps: Vec<MoveStep> = Vec::new();
+        if (from.0, from.1) == (to.0, to.1) {
+            if from.2 != to.2 {
+                steps.push(MoveStep::Z(to.2));
+            }
+            return Ok(steps);
+        }
+        if from.2 != self.z_travel {
+            steps.push(MoveStep::Z(self.z_travel));
+        }
+        steps.push(MoveStep::XY(to.0, to.1));
+        if to.2 != self.z_travel {
+            steps.push(MoveStep::Z(to.2));
+        }
+        Ok(steps)
+    }
+}
+
+/// Steps of a line move from `plan_line`
+pub fn line_steps(from: (i32, i32, i32), to: (i32, i32, i32)) -> i32 {
+    let length = (to.0 - from.0).abs().max((to.1 - from.1).abs());
+    ((length + LINE_STEP - 1) / LINE_STEP).max(1)
+}
+
+/// One step of a planned arm move, run after the previous has finished
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MoveStep {
+    Z(i32),
+    XY(i32, i32),
+}
+impl MoveStep {
+    pub fn cmd(&self) -> ArmCmd {
+        match self {
+            MoveStep::Z(z) => ArmCmd::GotoZ { z: *z },
+            MoveStep::XY(x, y) => ArmCmd::Goto { x: *x, y: *y },
+        }
+    }
+}
+
+/// Planned move, run by the arm runner
+#[derive(Debug)]
+pub struct ArmMove {
+    pub steps: Vec<MoveStep>,
+    pub target: (i32, i32, i32),
+    arm: ArmHandle,
+}
+impl ArmMove {
+    pub fn new(steps: Vec<MoveStep>, target: (i32, i32, i32), arm: ArmHandle) -> Self {
+        Self { steps, target, arm }
+    }
+
+    /// Run steps one at a time, returns the position reached
+    pub async fn run(self, step_timeout: Duration) -> Result<(i32, i32, i32), GrowError> {
+        self.arm.run(self.steps, self.target, step_timeout).await
+    }
+}
+
+/// Homing taken out of the house, calibrating takes long and must not hold the house lock
+#[derive(Debug)]
+pub struct ArmHoming {
+    device: Arc<dyn Arm>,
+    settings: Settings,
+    arm: ArmHandle,
+}
+impl ArmHoming {
+    pub fn new(device: Arc<dyn Arm>, settings: Settings, arm: ArmHandle) -> Self {
+        Self { device, settings, arm }
+    }
+
+    /// Find zero-points and move to home position, returns the position reached
+    pub async fn run(self, step_timeout: Duration) -> Result<(i32, i32, i32), GrowError> {
+        let home = self.settings.home();
+        self.arm.homing().await;
+        let calibrated = match self.device.calibrate().await {
+            Ok(_) => self.device.calibrate_with_range().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = calibrated {
+            self.arm.fault(format!("Homing failed: {}", e)).await;
+            return Err(e);
+        }
+        let from = self.device.position()?;
+        let steps = self.settings.plan(from, home)?;
+        ArmMove::new(steps, home, self.arm).run(step_timeout).await
+    }
+}
+
+/// Named position in the arm's position library
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+impl Point {
+    pub fn xyz(&self) -> (i32, i32, i32) {
+        (self.x, self.y, self.z)
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Position {
@@ -79,7 +317,8 @@ pub struct Position {
 }
 #[derive(Debug)]
 pub struct Interface {
-    pub arm: Option<Box<dyn Arm>>,
+    /// Shared with homing, which runs without the house lock
+    pub arm: Option<Arc<dyn Arm>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -88,7 +327,11 @@ pub struct Status {
     pub pos_x: i32,
     pub pos_y: i32,
     pub pos_z: i32,
+    pub mode: ArmMode,
+    pub target: Option<(i32, i32, i32)>,
     pub disp: DisplayStatus,
+    /// Zero point found by the last calibration
+    pub calibration: Option<(i32, i32, i32)>,
 }
 
 #[async_trait]
@@ -101,21 +344,24 @@ pub trait Arm: Send + Sync {
         tx_axis_z: tokio::sync::broadcast::Sender<(i8, i32)>,
         tx_control: ControlFeedbackTx,
         rx_cmd: tokio::sync::broadcast::Receiver<ArmCmd>,
-    ) -> Result<(), Box<dyn Error>>;
-    async fn goto(&self, x: i32, y: i32, z: i32) -> Result<(), Box<dyn Error>>;
-    async fn goto_x(&self, x: i32) -> Result<(), Box<dyn Error>>;
-    async fn goto_y(&self, y: i32) -> Result<(), Box<dyn Error>>;
-    // async fn confirm(&self, x: i32, y: i32, z: i32, delta: u32) -> Result<bool, Box<dyn Error>>;
-    // fn confirm(&self, x: i32, y: i32, z:i32, acceptable_delta: u32) -> Result<( bool, ( i32, i32, i32 ) ), Box<dyn Error>>;
-    async fn stop(&self) -> Result<(), Box<dyn Error>>;
-    async fn start_x(&self, speed: i8) -> Result<(), Box<dyn Error>>;
-    async fn stop_x(&self) -> Result<(), Box<dyn Error>>;
-    async fn start_y(&self, speed: i8) -> Result<(), Box<dyn Error>>;
-    async fn stop_y(&self) -> Result<(), Box<dyn Error>>;
-    async fn update_pos(&self) -> Result<(), Box<dyn Error>>;
-    fn position(&self) -> Result<(i32, i32, i32), Box<dyn Error>>;
-    async fn calibrate(&self) -> Result<(i32, i32, i32), Box<dyn Error>>;
-    async fn calibrate_with_range(&self) -> Result<(), Box<dyn Error>>;
+    ) -> Result<(), GrowError>;
+    async fn goto(&self, x: i32, y: i32, z: i32) -> Result<(), GrowError>;
+    async fn goto_x(&self, x: i32) -> Result<(), GrowError>;
+    async fn goto_y(&self, y: i32) -> Result<(), GrowError>;
+    async fn goto_z(&self, _z: i32) -> Result<(), GrowError> {
+        Err(GrowError::InterfaceMissing { kind: ZoneKind::Arm, id: self.id() })
+    }
+    // async fn confirm(&self, x: i32, y: i32, z: i32, delta: u32) -> Result<bool, GrowError>;
+    // fn confirm(&self, x: i32, y: i32, z:i32, acceptable_delta: u32) -> Result<( bool, ( i32, i32, i32 ) ), GrowError>;
+    async fn stop(&self) -> Result<(), GrowError>;
+    async fn start_x(&self, speed: i8) -> Result<(), GrowError>;
+    async fn stop_x(&self) -> Result<(), GrowError>;
+    async fn start_y(&self, speed: i8) -> Result<(), GrowError>;
+    async fn stop_y(&self) -> Result<(), GrowError>;
+    async fn update_pos(&self) -> Result<(), GrowError>;
+    fn position(&self) -> Result<(i32, i32, i32), GrowError>;
+    async fn calibrate(&self) -> Result<(i32, i32, i32), GrowError>;
+    async fn calibrate_with_range(&self) -> Result<(), GrowError>;
 }
 
 impl Debug for dyn Arm {
@@ -124,22 +370,115 @@ impl Debug for dyn Arm {
     }
 }
 
+#[derive(Debug)]
+pub enum ArmRequest {
+    /// Zero-points are being found, followed by a move to home position
+    Homing,
+    Move {
+        steps: Vec<MoveStep>,
+        target: (i32, i32, i32),
+        step_timeout: Duration,
+        reply: oneshot::Sender<Result<(i32, i32, i32), GrowError>>,
+    },
+    Hold,
+    Release,
+    /// Stop the arm and drop the move in progress, e.g. after its caller timed out
+    Cancel,
+    Fault(String),
+}
+
+/// Access to the arm runner for other zones and the manager
+#[derive(Clone, Debug)]
+pub struct ArmHandle {
+    id: u8,
+    tx: mpsc::Sender<ArmRequest>,
+    status: Arc<RwLock<Status>>,
+}
+impl ArmHandle {
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+    pub fn mode(&self) -> ArmMode {
+        self.status.read().mode
+    }
+    pub fn position(&self) -> (i32, i32, i32) {
+        let status = self.status.read();
+        (status.pos_x, status.pos_y, status.pos_z)
+    }
+    pub async fn run(
+        &self,
+        steps: Vec<MoveStep>,
+        target: (i32, i32, i32),
+        step_timeout: Duration,
+    ) -> Result<(i32, i32, i32), GrowError> {
+        let (reply, response) = oneshot::channel();
+        self.tx
+            .send(ArmRequest::Move { steps, target, step_timeout, reply })
+            .await
+            .map_err(|_| GrowError::RunnerStopped { kind: ZoneKind::Arm, id: self.id })?;
+        response
+            .await
+            .map_err(|_| GrowError::RunnerStopped { kind: ZoneKind::Arm, id: self.id })?
+    }
+    pub async fn homing(&self) {
+        let _ = self.tx.send(ArmRequest::Homing).await;
+    }
+    pub async fn hold(&self) {
+        let _ = self.tx.send(ArmRequest::Hold).await;
+    }
+    pub async fn release(&self) {
+        let _ = self.tx.send(ArmRequest::Release).await;
+    }
+    pub async fn cancel(&self) {
+        let _ = self.tx.send(ArmRequest::Cancel).await;
+    }
+    pub async fn fault(&self, msg: String) {
+        let _ = self.tx.send(ArmRequest::Fault(msg)).await;
+    }
+}
+
+/// Move in progress
+struct Job {
+    steps: VecDeque<MoveStep>,
+    step: MoveStep,
+    target: (i32, i32, i32),
+    step_timeout: Duration,
+    deadline: Instant,
+    progress: Instant,
+    seen_busy: bool,
+    reply: oneshot::Sender<Result<(i32, i32, i32), GrowError>>,
+}
+impl Job {
+    fn reached(&self, pos: (i32, i32, i32)) -> bool {
+        match self.step {
+            MoveStep::XY(x, y) => {
+                ((pos.0 - x).abs() <= POSITION_TOLERANCE) & ((pos.1 - y).abs() <= POSITION_TOLERANCE)
+            }
+            MoveStep::Z(z) => (pos.2 - z).abs() <= POSITION_TOLERANCE,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Runner {
-    _id: u8,
+    id: u8,
     pub tx_axis_x: broadcast::Sender<(i8, i32)>,
     // axis_x: (watch::Sender<(i8, i32)>, watch::Receiver<(i8, i32)> ),
     pub tx_axis_y: broadcast::Sender<(i8, i32)>,
     pub tx_axis_z: broadcast::Sender<(i8, i32)>,
     pub tx_cmd: broadcast::Sender<ArmCmd>,
     pub tx_control: ControlFeedbackTx,
+    tx_request: mpsc::Sender<ArmRequest>,
+    /// Shared so a restarted runner takes over the requests
+    rx_request: Arc<tokio::sync::Mutex<mpsc::Receiver<ArmRequest>>>,
     pub task: tokio::task::JoinHandle<()>,
     status: Arc<RwLock<Status>>,
 }
 impl Runner {
-    pub fn new(_id: u8, status: Arc<RwLock<Status>>) -> Self {
+    pub fn new(id: u8, status: Arc<RwLock<Status>>) -> Self {
+        let (tx_request, rx_request) = mpsc::channel(8);
         Self {
-            _id,
+            id,
             status,
             tx_axis_x: broadcast::channel(64).0,
             // axis_x: tokio::sync::watch::channel((0, 0)),
@@ -147,6 +486,8 @@ impl Runner {
             tx_axis_z: broadcast::channel(64).0,
             tx_control: broadcast::channel(64).0,
             tx_cmd: broadcast::channel(8).0,
+            tx_request,
+            rx_request: Arc::new(tokio::sync::Mutex::new(rx_request)),
             task: tokio::spawn(async move {}),
         }
     }
@@ -159,6 +500,13 @@ impl Runner {
     pub fn control_feedback_sender(&self) -> ControlFeedbackTx {
         self.tx_control.clone()
     }
+    pub fn handle(&self) -> ArmHandle {
+        ArmHandle {
+            id: self.id,
+            tx: self.tx_request.clone(),
+            status: self.status.clone(),
+        }
+    }
     pub fn pos_feedback_sender(
         &self,
     ) -> (
@@ -173,19 +521,66 @@ impl Runner {
         )
     }
 
-    pub fn run(&mut self, _settings: Settings) {
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+    pub fn task_mut(&mut self) -> &mut tokio::task::JoinHandle<()> {
+        &mut self.task
+    }
+
+    pub fn run(
+        &mut self,
+        _settings: Settings,
+        zone_channels: ZoneChannelsTx,
+        ops_channels: OpsChannelsTx,
+    ) {
+        let id = self.id;
         let mut rx_axis_x = self.tx_axis_x.subscribe();
         let mut rx_axis_y = self.tx_axis_y.subscribe();
         let mut rx_axis_z = self.tx_axis_z.subscribe();
         let mut rx_control = self.tx_control.subscribe();
+        let rx_request = self.rx_request.clone();
         let to_arm = self.cmd_sender();
         let status = self.status.clone();
+        let to_manager = zone_channels.zoneupdate;
+        let to_status_subscribers = zone_channels.zonestatus;
+        let to_logger = zone_channels.zonelog;
+        let to_syslog = ops_channels.syslog;
+        let heartbeat = ops_channels.heartbeats.register(&format!("arm/{}", id));
+        let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let mut check = tokio::time::interval(Duration::from_millis(250));
+        let mut each_second = tokio::time::interval(Duration::from_secs(1));
         self.task = tokio::spawn(async move {
-            // println!("Spawned arm runner");
-            
+            let mut rx_request = rx_request.lock().await;
+            let _ = to_syslog
+                .send(SysLog::new(format!("Spawned arm runner id {}", &id)))
+                .await;
+            let position = || {
+                let s = status.read();
+                (s.pos_x, s.pos_y, s.pos_z)
+            };
+            // Set mode and display status, and tell subscribers
+            let publish = |mode: ArmMode, mut ds: DisplayStatus| {
+                let pos = position();
+                {
+                    let mut lock = status.write();
+                    lock.mode = mode;
+                    ds.lease = lock.disp.lease.clone();
+                    lock.disp = ds.clone();
+                }
+                let _ = to_status_subscribers.send(ZoneDisplay::Arm { id, info: ds.clone() });
+                let _ = to_logger.try_send(ZoneLog::Arm { id, x: pos.0, y: pos.1, z: pos.2, changed_status: Some(ds) });
+                let _ = to_manager.try_send(ZoneUpdate::Arm { id, state: mode, x: pos.0, y: pos.1, z: pos.2 });
+            };
+            publish(ArmMode::Idle, DisplayStatus::new(Indicator::Blue, Some(String::from("Arm running"))));
+
+            let mut job: Option<Job> = None;
+            let mut logged_pos = position();
             // let mut started = Started::new();
-            let mut started = (false, false, false);      
+            let mut started = (false, false, false);
             loop {
+                let mut moved = false;
+                let mut arm_idle = false;
                 tokio::select! {
                     // Ok(data) = rx_axis_x.recv() => {
                     //     if (data.0 == 0) & *started.x.read() { 
@@ -199,7 +594,7 @@ impl Runner {
                     //     println!("\tX:{:?} ", data);
                     // }
                     Ok(data) = rx_axis_x.recv() => {
-                        if (data.0 == 0) & started.0 { 
+                        if (data.0 == 0) & started.0 & job.is_none() { 
                             let _ = to_arm.send(ArmCmd::StopX);
                             started.0 = false; 
                             // println!("Stopped X"); 
@@ -208,11 +603,12 @@ impl Runner {
                             started.0 = true; 
                             // println!("Set X"); 
                         }
+                        moved = status.read().pos_x != data.1;
                         status.write().pos_x = data.1;
                         // println!("\tX:{:?} ", &data);
                     }
                     Ok(data) = rx_axis_y.recv() => {
-                        if (data.0 == 0) & started.1 { 
+                        if (data.0 == 0) & started.1 & job.is_none() { 
                             let _ = to_arm.send(ArmCmd::StopY);
                             started.1 = false; 
                             // println!("Stopped Y"); 
@@ -221,17 +617,153 @@ impl Runner {
                             started.1 = true; 
                             // println!("Set Y"); 
                         }
-                        status.write().pos_x = data.1;
+                        moved = status.read().pos_y != data.1;
+                        status.write().pos_y = data.1;
                         // println!("\tY:{:?} ", &data);
                     }
                     Ok(data) = rx_axis_z.recv() => {
+                        moved = status.read().pos_z != data.1;
                         status.write().pos_z = data.1;
                     }
-                    Ok(_data) = rx_control.recv() => {
-                        println!("\tControl:{:?} ", &_data);
+                    Ok(data) = rx_control.recv() => {
+                        match data {
+                            ArmState::Busy => {
+                                if let Some(job) = job.as_mut() {
+                                    job.seen_busy = true;
+                                }
+                            }
+                            ArmState::Idle => { arm_idle = true; }
+                        }
+                    }
+                    Some(request) = rx_request.recv() => {
+                        let mode = status.read().mode;
+                        match request {
+                            ArmRequest::Homing => {
+                                publish(ArmMode::Homing, DisplayStatus::new(Indicator::Blue, Some(String::from("Homing"))));
+                            }
+                            ArmRequest::Move { reply, .. } if job.is_some() | (mode == ArmMode::Holding) => {
+                                let _ = reply.send(Err(GrowError::Interlock { kind: ZoneKind::Arm, id, reason: String::from("Arm busy") }));
+                            }
+                            ArmRequest::Move { reply, .. } if mode == ArmMode::Fault => {
+                                let _ = reply.send(Err(GrowError::Interlock { kind: ZoneKind::Arm, id, reason: String::from("Arm in fault, home before moving") }));
+                            }
+                            ArmRequest::Move { steps, target, step_timeout, reply } => {
+                                let mut steps: VecDeque<MoveStep> = steps.into();
+                                match steps.pop_front() {
+                                    None => { let _ = reply.send(Ok(position())); }
+                                    Some(step) => {
+                                        status.write().target = Some(target);
+                                        if mode != ArmMode::Homing {
+                                            publish(ArmMode::Moving, DisplayStatus::new(Indicator::Green, Some(format!("Moving to {}, {}, {}", target.0, target.1, target.2))));
+                                        }
+                                        let _ = to_arm.send(step.cmd());
+                                        job = Some(Job {
+                                            steps,
+                                            step,
+                                            target,
+                                            step_timeout,
+                                            deadline: Instant::now() + step_timeout,
+                                            progress: Instant::now(),
+                                            seen_busy: false,
+                                            reply,
+                                        });
+                                    }
+                                }
+                            }
+                            ArmRequest::Hold if job.is_none() & (mode != ArmMode::Fault) => {
+                                let pos = position();
+                                publish(ArmMode::Holding, DisplayStatus::new(Indicator::Green, Some(format!("Holding at {}, {}, {}", pos.0, pos.1, pos.2))));
+                            }
+                            ArmRequest::Release if mode == ArmMode::Holding => {
+                                let pos = position();
+                                publish(ArmMode::Idle, DisplayStatus::new(Indicator::Green, Some(format!("Idle at {}, {}, {}", pos.0, pos.1, pos.2))));
+                            }
+                            ArmRequest::Hold | ArmRequest::Release => {}
+                            ArmRequest::Cancel => {
+                                let _ = to_arm.send(ArmCmd::Stop);
+                                if let Some(cancelled) = job.take() {
+                                    let _ = cancelled.reply.send(Err(GrowError::Interlock { kind: ZoneKind::Arm, id, reason: String::from("Move cancelled") }));
+                                }
+                                status.write().target = None;
+                                // A fault stays until homed
+                                if mode != ArmMode::Fault {
+                                    let pos = position();
+                                    publish(ArmMode::Idle, DisplayStatus::new(Indicator::Yellow, Some(format!("Stopped at {}, {}, {}", pos.0, pos.1, pos.2))));
+                                }
+                            }
+                            ArmRequest::Fault(msg) => {
+                                let _ = to_arm.send(ArmCmd::Stop);
+                                if let Some(job) = job.take() {
+                                    let _ = job.reply.send(Err(GrowError::hardware_msg(&msg)));
+                                }
+                                publish(ArmMode::Fault, DisplayStatus::new(Indicator::Red, Some(msg.clone())));
+                                let _ = to_syslog.send(SysLog::with_level(LogLevel::Error, format!("Arm {} fault: {}", id, msg)).source(&format!("arm/{}", id))).await;
+                            }
+                        }
+                    }
+                    _ = check.tick() => {}
+                    _ = each_second.tick() => {
+                        let pos = position();
+                        if pos != logged_pos {
+                            logged_pos = pos;
+                            let _ = to_logger.send(ZoneLog::Arm { id, x: pos.0, y: pos.1, z: pos.2, changed_status: None }).await;
+                        }
                     }
+                    _ = beat.tick() => heartbeat.beat(),
                     else => { break }
                 };
+
+                // Follow up on move in progress
+                let pos = position();
+                let mut fault: Option<GrowError> = None;
+                let mut done: Option<Result<(i32, i32, i32), GrowError>> = None;
+                if let Some(current) = job.as_mut() {
+                    let now = Instant::now();
+                    if moved {
+                        current.progress = now;
+                    }
+                    if current.reached(pos) {
+                        match current.steps.pop_front() {
+                            Some(step) => {
+                                current.step = step;
+                                current.deadline = now + current.step_timeout;
+                                current.progress = now;
+                                current.seen_busy = false;
+                                let _ = to_arm.send(step.cmd());
+                            }
+                            None => done = Some(Ok(pos)),
+                        }
+                    }
+                    else if arm_idle & current.seen_busy {
+                        done = Some(Err(GrowError::PositionNotConfirmed { arm_id: id, target: current.target, position: pos }));
+                    }
+                    else if now > current.deadline {
+                        fault = Some(GrowError::Timeout(format!("Arm move at {:?}, step {:?}", pos, current.step)));
+                    }
+                    else if now.duration_since(current.progress) > STALL_TIMEOUT {
+                        fault = Some(GrowError::hardware_msg(format!("Arm move stalled at {:?}, step {:?}", pos, current.step)));
+                    }
+                }
+                if let Some(result) = done {
+                    if let Some(finished) = job.take() {
+                        let ds = match &result {
+                            Ok(pos) => DisplayStatus::new(Indicator::Green, Some(format!("Idle at {}, {}, {}", pos.0, pos.1, pos.2))),
+                            Err(e) => DisplayStatus::new(Indicator::Yellow, Some(format!("{}", e))),
+                        };
+                        status.write().target = None;
+                        publish(ArmMode::Idle, ds);
+                        let _ = finished.reply.send(result);
+                    }
+                }
+                if let Some(e) = fault {
+                    let _ = to_arm.send(ArmCmd::Stop);
+                    let msg = e.to_string();
+                    if let Some(failed) = job.take() {
+                        let _ = failed.reply.send(Err(e));
+                    }
+                    publish(ArmMode::Fault, DisplayStatus::new(Indicator::Red, Some(msg.clone())));
+                    let _ = to_syslog.send(SysLog::with_level(LogLevel::Error, format!("Arm {} fault: {}", id, msg)).source(&format!("arm/{}", id))).await;
+                }
             }
         });
     }
@@ -264,4 +796,75 @@ impl Runner {
 //         }
 //         else {println!("Set X skipped") }
 //     }
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::heartbeat::Heartbeats;
+
+    /// Runner with no driver, the test plays the arm through the command and feedback channels
+    fn runner() -> (Runner, ZoneChannelsTx, OpsChannelsTx) {
+        let status = match new(1, Settings::default()) {
+            Zone::Arm { status, .. } => status,
+            _ => unreachable!(),
+        };
+        let zone_channels = ZoneChannelsTx {
+            zoneupdate: mpsc::channel(64).0,
+            zonestatus: broadcast::channel(64).0,
+            zonelog: mpsc::channel(64).0,
+        };
+        let ops_channels = OpsChannelsTx { syslog: mpsc::channel(64).0, heartbeats: Heartbeats::new() };
+        (Runner::new(1, status), zone_channels, ops_channels)
+    }
+
+    #[test]
+    fn plan_retracts_for_xy() {
+        let settings = Settings {
+            x: Axis { min: 0, max: 1000, home: 0 },
+            y: Axis { min: 0, max: 1000, home: 0 },
+            z: Axis { min: 0, max: 100, home: 100 },
+            z_travel: 100,
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.plan((0, 0, 20), (500, 500, 30)).unwrap(),
+            vec![MoveStep::Z(100), MoveStep::XY(500, 500), MoveStep::Z(30)]
+        );
+        assert_eq!(settings.plan((500, 500, 20), (500, 500, 30)).unwrap(), vec![MoveStep::Z(30)]);
+        assert!(settings.plan((0, 0, 20), (2000, 0, 30)).is_err());
+        assert_eq!(settings.plan_line((0, 0, 50), (120, 0, 50)).unwrap().len(), line_steps((0, 0, 50), (120, 0, 50)) as usize);
+    }
+
+    #[tokio::test]
+    async fn park_after_cancelled_move() {
+        let (mut runner, zone_channels, ops_channels) = runner();
+        let mut from_runner = runner.cmd_receiver();
+        runner.run(Settings::default(), zone_channels, ops_channels);
+        let arm = runner.handle();
+
+        // The arm never reaches x 500, the caller gives up first
+        let stuck = tokio::time::timeout(
+            Duration::from_millis(100),
+            arm.run(vec![MoveStep::XY(500, 0)], (500, 0, 0), Duration::from_secs(60)),
+        )
+        .await;
+        assert!(stuck.is_err());
+        assert_eq!(from_runner.recv().await.unwrap(), ArmCmd::Goto { x: 500, y: 0 });
+        assert_eq!(arm.mode(), ArmMode::Moving);
+        let refused = arm.run(vec![MoveStep::Z(0)], (0, 0, 0), Duration::from_secs(1)).await;
+        assert!(matches!(refused, Err(GrowError::Interlock { .. })));
+
+        arm.cancel().await;
+        assert_eq!(from_runner.recv().await.unwrap(), ArmCmd::Stop);
+        let parked = tokio::time::timeout(
+            Duration::from_secs(2),
+            arm.run(vec![MoveStep::Z(0)], (0, 0, 0), Duration::from_secs(1)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(parked.unwrap(), (0, 0, 0));
+        assert_eq!(arm.mode(), ArmMode::Idle);
+        runner.abort();
+    }
+}
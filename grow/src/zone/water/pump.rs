@@ -1,7 +1,9 @@
 
 use async_trait::async_trait;
-use core::error::Error;
+use core::time::Duration;
 use parking_lot::RwLock;
+use std::collections::VecDeque;
+use std::time::Instant;
 use std::{sync::Arc};
 use tokio::sync::broadcast;
 
@@ -13,7 +15,13 @@ use serde::{Serialize, Deserialize};
 
 use super::Zone;
 use super::*;
-use crate::ops::display::{DisplayStatus};
+use super::tank::TankLevel;
+use crate::ops::display::{DisplayStatus, Indicator};
+use crate::ops::heartbeat::HEARTBEAT_INTERVAL;
+use crate::ops::{OpsChannelsTx, SysLog, LogLevel};
+use crate::GrowError;
+
+const HOUR: Duration = Duration::from_secs(3600);
 
 
 // use crate::TIME_OFFSET;
@@ -30,30 +38,335 @@ pub fn new(id: u8, settings: Settings) -> super::Zone {
             indicator: Default::default(),
             msg: None,
             changed: OffsetDateTime::UNIX_EPOCH,
+            lease: None,
         },
+        state: PumpState::Stopped,
+        speed: settings.speed,
+        feedback: None,
+        running: None,
+        history: VecDeque::new(),
+        runtime_total: Duration::ZERO,
+        interlock: None,
     };
     let status_mutex = Arc::new(RwLock::new(status));
     Zone::Pump {
         id,
         settings,
+        runner: Runner::new(id, status_mutex.clone()),
         status: status_mutex,
         interface: Interface { pump: None },
-        runner: Runner::new(),
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
+    /// Tank feeding the pump, no running while it is empty or unread
+    pub tank_id: Option<u8>,
+    /// Longest continuous run
+    pub max_runtime: Duration,
+    /// Longest total run time within any hour
+    pub max_per_hour: Duration,
+    /// Motor speed in percent, -100..100
+    pub speed: i8,
+    /// Feedback speed below this while running means the pump is stalled
+    pub stall_speed: i8,
+    /// Feedback speed at or above this while running means no load, i.e. pumping air; 0 disables
+    pub no_load_speed: i8,
+    /// How long feedback must stay out of range before stopping, also allowed for spin-up
+    pub feedback_time: Duration,
+    /// Litres per minute at `speed`, for tank forecasts
+    pub flow_rate: f32,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tank_id: None,
+            max_runtime: Duration::from_secs(30),
+            max_per_hour: Duration::from_secs(300),
+            speed: 50,
+            stall_speed: 5,
+            no_load_speed: 0,
+            feedback_time: Duration::from_secs(2),
+            flow_rate: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PumpState {
+    #[default]
+    Stopped,
+    Running,
+    /// Motor released after a timed run, coasting
+    Float,
+}
+
+/// Safety stop or refusal to run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interlock {
+    MaxRuntime,
+    DutyLimit,
+    TankEmpty,
+    TankNoData,
+    /// Motor feedback too slow while running
+    Stall,
+    /// Motor feedback too fast while running, no water to pump
+    DryRun,
+}
+impl Interlock {
+    pub fn indicator(&self) -> Indicator {
+        match self {
+            Interlock::TankEmpty | Interlock::TankNoData | Interlock::Stall | Interlock::DryRun => Indicator::Red,
+            Interlock::MaxRuntime | Interlock::DutyLimit => Indicator::Yellow,
+        }
+    }
+}
+impl core::fmt::Display for Interlock {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Interlock::MaxRuntime => write!(f, "max runtime reached"),
+            Interlock::DutyLimit => write!(f, "hourly run time used up"),
+            Interlock::TankEmpty => write!(f, "tank empty"),
+            Interlock::TankNoData => write!(f, "no tank level"),
+            Interlock::Stall => write!(f, "motor stalled"),
+            Interlock::DryRun => write!(f, "running dry"),
+        }
+    }
+}
+
+/// Pump run in progress, `until` is set for timed runs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Run {
+    pub started: Instant,
+    pub until: Option<Instant>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Status {
     pub disp: DisplayStatus,
+    pub state: PumpState,
+    /// Commanded speed
+    pub speed: i8,
+    /// Last motor speed feedback
+    pub feedback: Option<i8>,
+    pub running: Option<Run>,
+    /// Finished runs within the last hour: start and length
+    pub history: VecDeque<(Instant, Duration)>,
+    /// Run time of finished runs since start
+    pub runtime_total: Duration,
+    /// Last safety stop or refusal, cleared by the next run
+    pub interlock: Option<Interlock>,
+}
+impl Status {
+    /// Run time within the last hour, including a run in progress
+    pub fn run_time_last_hour(&self, now: Instant) -> Duration {
+        let hour_ago = now.checked_sub(HOUR);
+        let within = |start: Instant, length: Duration| match hour_ago {
+            Some(hour_ago) if start < hour_ago => (start + length).saturating_duration_since(hour_ago),
+            _ => length,
+        };
+        let finished: Duration = self.history.iter().map(|(start, length)| within(*start, *length)).sum();
+        let current = self
+            .running
+            .map(|run| within(run.started, now.saturating_duration_since(run.started)))
+            .unwrap_or_default();
+        finished + current
+    }
+
+    /// Run time since start, including a run in progress
+    pub fn total_runtime(&self, now: Instant) -> Duration {
+        let current = self
+            .running
+            .map(|run| run.until.map(|u| u.min(now)).unwrap_or(now).saturating_duration_since(run.started))
+            .unwrap_or_default();
+        self.runtime_total + current
+    }
+
+    /// Interlocks for a new run of `requested` length, `None` runs until stopped
+    pub fn check(
+        &self,
+        settings: &Settings,
+        tank: Option<TankLevel>,
+        requested: Option<Duration>,
+        now: Instant,
+    ) -> Result<(), Interlock> {
+        if settings.tank_id.is_some() {
+            match tank {
+                Some(TankLevel::Empty) => return Err(Interlock::TankEmpty),
+                Some(TankLevel::NoData) | None => return Err(Interlock::TankNoData),
+                _ => {}
+            }
+        }
+        if requested.is_some_and(|r| r > settings.max_runtime) {
+            return Err(Interlock::MaxRuntime);
+        }
+        let left = settings.max_per_hour.saturating_sub(self.run_time_last_hour(now));
+        if left.is_zero() | requested.is_some_and(|r| r > left) {
+            return Err(Interlock::DutyLimit);
+        }
+        Ok(())
+    }
+
+    pub fn start(&mut self, now: Instant, requested: Option<Duration>) {
+        self.stop(now);
+        self.running = Some(Run {
+            started: now,
+            until: requested.map(|r| now + r),
+        });
+        self.state = PumpState::Running;
+        self.interlock = None;
+    }
+
+    /// End a run in progress, returns its length
+    pub fn stop(&mut self, now: Instant) -> Option<Duration> {
+        let run = self.running.take()?;
+        self.state = PumpState::Stopped;
+        let end = run.until.map(|u| u.min(now)).unwrap_or(now);
+        let length = end.saturating_duration_since(run.started);
+        self.runtime_total += length;
+        self.history.push_back((run.started, length));
+        while self.history.front().is_some_and(|(start, length)| *start + *length + HOUR < now) {
+            self.history.pop_front();
+        }
+        Some(length)
+    }
+
+    /// Interlock tripped by a run in progress
+    fn tripped(&self, settings: &Settings, tank: Option<TankLevel>, now: Instant) -> Option<Interlock> {
+        let run = self.running?;
+        if settings.tank_id.is_some() {
+            match tank {
+                Some(TankLevel::Empty) => return Some(Interlock::TankEmpty),
+                Some(TankLevel::NoData) | None => return Some(Interlock::TankNoData),
+                _ => {}
+            }
+        }
+        if now.saturating_duration_since(run.started) > settings.max_runtime {
+            return Some(Interlock::MaxRuntime);
+        }
+        if self.run_time_last_hour(now) > settings.max_per_hour {
+            return Some(Interlock::DutyLimit);
+        }
+        None
+    }
+}
+
+/// Set display status and tell subscribers
+pub fn publish(id: u8, status: &Arc<RwLock<Status>>, mut ds: DisplayStatus, zone_channels: &ZoneChannelsTx) {
+    let (state, speed) = {
+        let mut lock = status.write();
+        // The lease is set by `Leases`, not by the runner
+        ds.lease = lock.disp.lease.clone();
+        lock.disp = ds.clone();
+        (lock.state, lock.feedback)
+    };
+    let _ = zone_channels.zonestatus.send(ZoneDisplay::Pump { id, info: ds.clone() });
+    let _ = zone_channels.zonelog.try_send(ZoneLog::Pump { id, state, speed, changed_status: Some(ds) });
+}
+
+/// Display status for a run started at `speed`
+pub fn running_status(speed: i8) -> DisplayStatus {
+    DisplayStatus::new(Indicator::Green, Some(format!("Running at {}%", speed)))
+}
+
+/// Display status for a refused or stopped run
+pub fn interlock_status(interlock: Interlock) -> DisplayStatus {
+    DisplayStatus::new(interlock.indicator(), Some(format!("Interlock: {}", interlock)))
+}
+
+/// End a run in progress and show the pump stopped
+pub fn stopped(id: u8, status: &Arc<RwLock<Status>>, zone_channels: &ZoneChannelsTx) {
+    if status.write().stop(Instant::now()).is_some() {
+        publish(id, status, DisplayStatus::new(Indicator::Green, Some(String::from("Stopped"))), zone_channels);
+    }
+}
+
+/// Runs the pump without the House lock, settings are those when the handle was made
+#[derive(Clone, Debug)]
+pub struct PumpHandle {
+    id: u8,
+    settings: Settings,
+    status: Arc<RwLock<Status>>,
+    tank: Option<Arc<RwLock<tank::Status>>>,
+    pump: Arc<dyn Pump>,
+    zone_channels: ZoneChannelsTx,
+}
+impl PumpHandle {
+    pub fn new(
+        id: u8,
+        settings: Settings,
+        status: Arc<RwLock<Status>>,
+        tank: Option<Arc<RwLock<tank::Status>>>,
+        pump: Arc<dyn Pump>,
+        zone_channels: ZoneChannelsTx,
+    ) -> Self {
+        Self { id, settings, status, tank, pump, zone_channels }
+    }
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+    fn interlock(&self, requested: Option<Duration>, now: Instant) -> Result<(), Interlock> {
+        let level = self.tank.as_ref().and_then(|t| t.read().level);
+        self.status.read().check(&self.settings, level, requested, now)
+    }
+
+    /// Check interlocks without running, e.g. before moving the arm for watering
+    pub fn check(&self, requested: Option<Duration>) -> Result<(), GrowError> {
+        self.interlock(requested, Instant::now()).map_err(|interlock| GrowError::Interlock {
+            kind: ZoneKind::Pump,
+            id: self.id,
+            reason: interlock.to_string(),
+        })
+    }
+    /// Check interlocks and mark the run started, refusals are shown in the pump status
+    fn start(&self, requested: Option<Duration>) -> Result<(), GrowError> {
+        let now = Instant::now();
+        match self.interlock(requested, now) {
+            Ok(()) => {
+                let speed = {
+                    let mut lock = self.status.write();
+                    lock.start(now, requested);
+                    lock.speed
+                };
+                publish(self.id, &self.status, running_status(speed), &self.zone_channels);
+                Ok(())
+            }
+            Err(interlock) => {
+                self.status.write().interlock = Some(interlock);
+                publish(self.id, &self.status, interlock_status(interlock), &self.zone_channels);
+                Err(GrowError::Interlock { kind: ZoneKind::Pump, id: self.id, reason: interlock.to_string() })
+            }
+        }
+    }
+
+    /// Run until stopped, or until the pump's max runtime
+    pub async fn run(&self) -> Result<(), GrowError> {
+        self.start(None)?;
+        let result = self.pump.run().await;
+        if result.is_err() {
+            stopped(self.id, &self.status, &self.zone_channels);
+        }
+        result
+    }
+    pub async fn run_for_secs(&self, secs: u16) -> Result<(), GrowError> {
+        self.start(Some(Duration::from_secs(secs as u64)))?;
+        let result = self.pump.run_for_secs(secs).await;
+        if result.is_err() {
+            stopped(self.id, &self.status, &self.zone_channels);
+        }
+        result
+    }
+    pub async fn stop(&self) -> Result<(), GrowError> {
+        stopped(self.id, &self.status, &self.zone_channels);
+        self.pump.stop().await
+    }
 }
 
 #[derive(Debug)]
 pub struct Interface {
-    pub pump: Option<Box<dyn Pump>>,
+    /// Shared with the pump handles, drivers are initialised before any is made
+    pub pump: Option<Arc<dyn Pump>>,
 }
 
 #[async_trait]
@@ -63,11 +376,13 @@ pub trait Pump: Send + Sync {
         &mut self,
         rx_pump: tokio::sync::broadcast::Receiver<(u8, PumpCmd)>,
         tx_pump: tokio::sync::broadcast::Sender<(u8, (i8, i32))>,
-    ) -> Result<(), Box<dyn Error>>;
-    async fn run_for_secs(&self, secs: u16) -> Result<(), Box<dyn Error>>;
-    async fn run(&self) -> Result<(), Box<dyn Error>>;
-    async fn stop(&self) -> Result<(), Box<dyn Error>>;
-    async fn float(&self) -> Result<(), Box<dyn Error>>;
+    ) -> Result<(), GrowError>;
+    async fn run_for_secs(&self, secs: u16) -> Result<(), GrowError>;
+    async fn run(&self) -> Result<(), GrowError>;
+    /// Speed for following runs, in percent -100..100
+    async fn set_speed(&self, speed: i8) -> Result<(), GrowError>;
+    async fn stop(&self) -> Result<(), GrowError>;
+    async fn float(&self) -> Result<(), GrowError>;
 }
 impl Debug for dyn Pump {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -77,15 +392,21 @@ impl Debug for dyn Pump {
 
 #[derive(Debug)]
 pub struct Runner {
+    id: u8,
+    status: Arc<RwLock<Status>>,
     tx_feedback: broadcast::Sender<(u8, (i8, i32))>,
     tx_pumpcmd: broadcast::Sender<(u8, PumpCmd)>,
+    tx_settings: broadcast::Sender<Settings>,
     task: tokio::task::JoinHandle<()>,
 }
 impl Runner {
-    pub fn new() -> Self {
+    pub fn new(id: u8, status: Arc<RwLock<Status>>) -> Self {
         Self {
+            id,
+            status,
             tx_feedback: broadcast::channel(8).0,
             tx_pumpcmd: broadcast::channel(8).0,
+            tx_settings: broadcast::channel(1).0,
             task: tokio::spawn(async move {}),
         }
     }
@@ -99,20 +420,231 @@ impl Runner {
     pub fn feedback_sender(&self) -> broadcast::Sender<(u8, (i8, i32))> {
         self.tx_feedback.clone()
     }
+    pub fn settings_sender(&self) -> broadcast::Sender<Settings> {
+        self.tx_settings.clone()
+    }
 
-    pub fn run(&mut self, _settings: Settings) {
+    /// Watch runs in progress and stop the pump when an interlock trips
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+    pub fn task_mut(&mut self) -> &mut tokio::task::JoinHandle<()> {
+        &mut self.task
+    }
+
+    pub fn run(
+        &mut self,
+        settings: Settings,
+        tank: Option<Arc<RwLock<tank::Status>>>,
+        zone_channels: ZoneChannelsTx,
+        ops_channels: OpsChannelsTx,
+    ) {
+        let id = self.id;
+        let status = self.status.clone();
         let mut rx_feedback = self.tx_feedback.subscribe();
-        let _tx_pumpcmd = self.tx_pumpcmd.clone();
+        let mut rx_settings = self.tx_settings.subscribe();
+        let mut settings = settings;
+        let to_pump = self.tx_pumpcmd.clone();
+        let to_syslog = ops_channels.syslog;
+        let heartbeat = ops_channels.heartbeats.register(&format!("pump/{}", id));
+        let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let mut check = tokio::time::interval(Duration::from_millis(250));
+        let mut each_second = tokio::time::interval(Duration::from_secs(1));
         self.task = tokio::spawn(async move {
-            println!("Spawned pump runner");
+            let _ = to_syslog
+                .send(SysLog::new(format!("Spawned pump runner id {}", &id)))
+                .await;
+            // A restarted runner may find a run in progress
+            let (running, speed) = {
+                let lock = status.read();
+                (lock.running.is_some(), lock.speed)
+            };
+            let ds = match running {
+                true => running_status(speed),
+                false => DisplayStatus::new(Indicator::Blue, Some(String::from("Pump idle"))),
+            };
+            publish(id, &status, ds, &zone_channels);
+            // Since when feedback has been too slow or too fast during a run
+            let mut slow_since: Option<Instant> = None;
+            let mut fast_since: Option<Instant> = None;
             loop {
                 tokio::select! {
-                    Ok(_data) = rx_feedback.recv() => {
-                        // println!("\tPump speed: {:?}", data);
+                    Ok(new_settings) = rx_settings.recv() => {
+                        settings = new_settings;
+                    }
+                    Ok((_id, (speed, _))) = rx_feedback.recv() => {
+                        let now = Instant::now();
+                        let run = {
+                            let mut lock = status.write();
+                            lock.feedback = Some(speed);
+                            lock.running
+                        };
+                        match run {
+                            // Spin-up is not judged
+                            Some(run) if now.saturating_duration_since(run.started) > settings.feedback_time => {
+                                let abs = speed.unsigned_abs();
+                                if abs < settings.stall_speed.unsigned_abs() {
+                                    slow_since.get_or_insert(now);
+                                } else {
+                                    slow_since = None;
+                                }
+                                if (settings.no_load_speed != 0) & (abs >= settings.no_load_speed.unsigned_abs()) {
+                                    fast_since.get_or_insert(now);
+                                } else {
+                                    fast_since = None;
+                                }
+                            }
+                            _ => {
+                                slow_since = None;
+                                fast_since = None;
+                            }
+                        }
+                    }
+                    _ = check.tick() => {
+                        let now = Instant::now();
+                        let level = tank.as_ref().and_then(|t| t.read().level);
+                        let run = status.read().running;
+                        match run {
+                            Some(Run { until: Some(until), .. }) if now >= until => {
+                                // Timed run finished by itself, motor left floating
+                                {
+                                    let mut lock = status.write();
+                                    lock.stop(now);
+                                    lock.state = PumpState::Float;
+                                }
+                                publish(id, &status, DisplayStatus::new(Indicator::Green, Some(String::from("Stopped"))), &zone_channels);
+                            }
+                            Some(_) => {
+                                let out_of_range = |since: Option<Instant>| since.is_some_and(|t| now.saturating_duration_since(t) > settings.feedback_time);
+                                let tripped = status
+                                    .read()
+                                    .tripped(&settings, level, now)
+                                    .or(if out_of_range(slow_since) { Some(Interlock::Stall) } else { None })
+                                    .or(if out_of_range(fast_since) { Some(Interlock::DryRun) } else { None });
+                                if let Some(interlock) = tripped {
+                                    let _ = to_pump.send((id, PumpCmd::Stop));
+                                    {
+                                        let mut lock = status.write();
+                                        lock.stop(now);
+                                        lock.interlock = Some(interlock);
+                                    }
+                                    slow_since = None;
+                                    fast_since = None;
+                                    publish(id, &status, interlock_status(interlock), &zone_channels);
+                                    let _ = to_syslog
+                                        .send(SysLog::with_level(LogLevel::Warning, format!("Pump {} stopped: {}", id, interlock)).source(&format!("pump/{}", id)))
+                                        .await;
+                                }
+                            }
+                            None => {}
+                        }
                     }
+                    _ = each_second.tick() => {
+                        let (state, speed) = {
+                            let lock = status.read();
+                            (lock.state, lock.feedback)
+                        };
+                        if state == PumpState::Running {
+                            let _ = zone_channels.zonelog.send(ZoneLog::Pump { id, state, speed, changed_status: None }).await;
+                        }
+                    }
+                    _ = beat.tick() => heartbeat.beat(),
                     else => { break }
                 };
             }
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status() -> Status {
+        Status {
+            disp: DisplayStatus::new(Indicator::Blue, None),
+            state: PumpState::Stopped,
+            speed: 50,
+            feedback: None,
+            running: None,
+            history: VecDeque::new(),
+            runtime_total: Duration::ZERO,
+            interlock: None,
+        }
+    }
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn check_max_runtime() {
+        let settings = Settings::default();
+        let now = Instant::now();
+        assert_eq!(status().check(&settings, None, Some(settings.max_runtime), now), Ok(()));
+        assert_eq!(status().check(&settings, None, Some(settings.max_runtime + secs(1)), now), Err(Interlock::MaxRuntime));
+        // Runs until stopped are limited while running, see `tripped`
+        assert_eq!(status().check(&settings, None, None, now), Ok(()));
+    }
+
+    #[test]
+    fn check_tank() {
+        let settings = Settings { tank_id: Some(1), ..Default::default() };
+        let now = Instant::now();
+        let check = |tank| status().check(&settings, tank, Some(secs(5)), now);
+        assert_eq!(check(Some(TankLevel::Ok)), Ok(()));
+        assert_eq!(check(Some(TankLevel::Low)), Ok(()));
+        assert_eq!(check(Some(TankLevel::Empty)), Err(Interlock::TankEmpty));
+        assert_eq!(check(Some(TankLevel::NoData)), Err(Interlock::TankNoData));
+        assert_eq!(check(None), Err(Interlock::TankNoData));
+        // Without a tank the level is not looked at
+        assert_eq!(status().check(&Settings::default(), Some(TankLevel::Empty), Some(secs(5)), now), Ok(()));
+    }
+
+    #[test]
+    fn check_hourly_cooldown() {
+        let settings = Settings { max_runtime: secs(60), max_per_hour: secs(90), ..Default::default() };
+        let start = Instant::now();
+        let mut status = status();
+        status.start(start, Some(secs(60)));
+        let now = start + secs(70);
+        assert_eq!(status.stop(now), Some(secs(60)));
+        assert_eq!(status.check(&settings, None, Some(secs(30)), now), Ok(()));
+        assert_eq!(status.check(&settings, None, Some(secs(31)), now), Err(Interlock::DutyLimit));
+        status.start(now, Some(secs(30)));
+        let later = now + secs(30);
+        status.stop(later);
+        assert_eq!(status.check(&settings, None, None, later), Err(Interlock::DutyLimit));
+        // Runs older than an hour no longer count
+        let next_hour = start + HOUR + secs(60);
+        assert_eq!(status.check(&settings, None, Some(secs(60)), next_hour), Ok(()));
+    }
+
+    #[test]
+    fn tripped_while_running() {
+        let settings = Settings { tank_id: Some(1), ..Default::default() };
+        let start = Instant::now();
+        let mut status = status();
+        assert_eq!(status.tripped(&settings, Some(TankLevel::Empty), start), None);
+        status.start(start, None);
+        assert_eq!(status.tripped(&settings, Some(TankLevel::Ok), start + secs(1)), None);
+        assert_eq!(status.tripped(&settings, Some(TankLevel::Empty), start + secs(1)), Some(Interlock::TankEmpty));
+        assert_eq!(status.tripped(&settings, Some(TankLevel::NoData), start + secs(1)), Some(Interlock::TankNoData));
+        assert_eq!(
+            status.tripped(&settings, Some(TankLevel::Ok), start + settings.max_runtime + secs(1)),
+            Some(Interlock::MaxRuntime)
+        );
+    }
+
+    #[test]
+    fn tripped_by_hourly_limit() {
+        let settings = Settings { max_runtime: secs(60), max_per_hour: secs(90), ..Default::default() };
+        let start = Instant::now();
+        let mut status = status();
+        status.start(start, None);
+        status.stop(start + secs(60));
+        let now = start + secs(120);
+        status.start(now, None);
+        assert_eq!(status.tripped(&settings, None, now + secs(30)), None);
+        assert_eq!(status.tripped(&settings, None, now + secs(31)), Some(Interlock::DutyLimit));
+    }
+}
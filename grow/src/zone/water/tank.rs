@@ -1,8 +1,11 @@
 
 use async_trait::async_trait;
-use core::error::Error;
+use crate::GrowError;
+use core::time::Duration;
 use parking_lot::RwLock;
+use std::collections::VecDeque;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::broadcast;
 // use tokio::sync::Mutex;
 use core::fmt::Debug;
@@ -13,11 +16,13 @@ use serde::{Serialize, Deserialize};
 use super::Zone;
 use super::*;
 use crate::ops::display::{DisplayStatus, Indicator};
+use crate::ops::heartbeat::HEARTBEAT_INTERVAL;
 use crate::ops::OpsChannelsTx;
-use crate::ops::SysLog;
+use crate::ops::{LogLevel, SysLog};
+use super::pump;
 // use crate::TIME_OFFSET;
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Ord, PartialOrd, Hash)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum TankLevel {
     Ok,
     Low,
@@ -25,6 +30,18 @@ pub enum TankLevel {
     Overfill,
     NoData
 }
+impl TankLevel {
+    /// Numeric level for graphs: Empty 0, Low 1, Ok 2, Overfill 3
+    pub fn value(&self) -> Option<f64> {
+        match self {
+            TankLevel::Empty => Some(0.0),
+            TankLevel::Low => Some(1.0),
+            TankLevel::Ok => Some(2.0),
+            TankLevel::Overfill => Some(3.0),
+            TankLevel::NoData => None,
+        }
+    }
+}
 
 pub fn new(id: u8, settings: Settings) -> super::Zone {
     let status = Status {
@@ -32,7 +49,14 @@ pub fn new(id: u8, settings: Settings) -> super::Zone {
             indicator: Default::default(),
             msg: None,
             changed: OffsetDateTime::UNIX_EPOCH,
+            lease: None,
         },
+        level: None,
+        percent: None,
+        volume: None,
+        days_left: None,
+        refilling: None,
+        refill_fault: None,
     };
     let status_mutex = Arc::new(RwLock::new(status));
     Zone::Tank {
@@ -40,21 +64,202 @@ pub fn new(id: u8, settings: Settings) -> super::Zone {
         settings,
         runner: Runner::new(id, status_mutex.clone()),
         status: status_mutex,
-        interface: Interface { tank_sensor: None },
+        interface: Interface { tank_sensor: None, refill: None },
     }
 }
 
+/// Continuous level reading
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelReading {
+    /// Ultrasonic, distance from the sensor down to the surface in mm
+    Distance(f32),
+    /// Analog float, fraction of full 0.0..1.0
+    Fraction(f32),
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
-pub struct Settings {}
+#[serde(default)]
+pub struct Settings {
+    /// Volume when full, litres
+    pub capacity: f32,
+    /// Ultrasonic distance to the surface when empty, mm
+    pub empty_distance: f32,
+    /// Ultrasonic distance to the surface when full, mm
+    pub full_distance: f32,
+    pub low_percent: f32,
+    pub empty_percent: f32,
+    pub overfill_percent: f32,
+    /// Warn when the forecast runs out within this many days
+    pub warning_days: f32,
+    /// Pump use over this period makes the forecast
+    pub forecast_window: Duration,
+    /// Refill automatically when the level is low
+    pub refill: bool,
+    /// Stop refilling at this level, continuous sensors only
+    pub fill_percent: f32,
+    /// Longest a refill may take
+    pub max_refill: Duration,
+    /// Level must rise by `min_rise_percent` within this time while refilling
+    pub leak_check: Duration,
+    pub min_rise_percent: f32,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            capacity: 10.0,
+            empty_distance: 300.0,
+            full_distance: 50.0,
+            low_percent: 25.0,
+            empty_percent: 5.0,
+            overfill_percent: 105.0,
+            warning_days: 2.0,
+            forecast_window: Duration::from_secs(3 * 24 * 3600),
+            refill: false,
+            fill_percent: 90.0,
+            max_refill: Duration::from_secs(300),
+            leak_check: Duration::from_secs(60),
+            min_rise_percent: 2.0,
+        }
+    }
+}
+impl Settings {
+    /// Percent of full, above 100 when overfilled
+    pub fn percent(&self, reading: LevelReading) -> f32 {
+        let percent = match reading {
+            LevelReading::Distance(d) => {
+                (self.empty_distance - d) / (self.empty_distance - self.full_distance) * 100.0
+            }
+            LevelReading::Fraction(f) => f * 100.0,
+        };
+        percent.max(0.0)
+    }
+    pub fn volume(&self, percent: f32) -> f32 {
+        self.capacity * percent.min(100.0) / 100.0
+    }
+    pub fn level(&self, percent: f32) -> TankLevel {
+        if percent >= self.overfill_percent {
+            TankLevel::Overfill
+        } else if percent <= self.empty_percent {
+            TankLevel::Empty
+        } else if percent <= self.low_percent {
+            TankLevel::Low
+        } else {
+            TankLevel::Ok
+        }
+    }
+    /// Refill is done at `fill_percent`, or at level Ok without a continuous sensor
+    pub fn filled(&self, level: Option<TankLevel>, percent: Option<f32>) -> bool {
+        match percent {
+            Some(percent) => percent >= self.fill_percent,
+            None => level == Some(TankLevel::Ok),
+        }
+    }
+}
+
+/// Why a refill was stopped, refilling stays off until reset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefillFault {
+    Timeout,
+    Leak,
+    Overfill,
+    /// Level or percent lost while refilling, the refill can not be watched
+    NoData,
+}
+impl core::fmt::Display for RefillFault {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RefillFault::Timeout => write!(f, "Refill took too long"),
+            RefillFault::Leak => write!(f, "Level not rising while refilling, leak?"),
+            RefillFault::Overfill => write!(f, "Overfill while refilling"),
+            RefillFault::NoData => write!(f, "No level data while refilling"),
+        }
+    }
+}
+
+/// A refill in progress
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Refill {
+    pub started: Instant,
+    /// Start of the current leak check period, and the percent then
+    pub checked: Instant,
+    pub checked_percent: Option<f32>,
+}
+impl Refill {
+    fn new(now: Instant, percent: Option<f32>) -> Self {
+        Self { started: now, checked: now, checked_percent: percent }
+    }
+    /// Fault if the refill runs too long, the level does not rise, or the sensor stops reporting
+    fn check(&mut self, settings: &Settings, level: Option<TankLevel>, percent: Option<f32>, now: Instant) -> Option<RefillFault> {
+        match level {
+            Some(TankLevel::Overfill) => return Some(RefillFault::Overfill),
+            None | Some(TankLevel::NoData) => return Some(RefillFault::NoData),
+            _ => {}
+        }
+        // Started with a continuous sensor, without it the leak check would be skipped
+        if self.checked_percent.is_some() & percent.is_none() {
+            return Some(RefillFault::NoData);
+        }
+        if now.saturating_duration_since(self.started) > settings.max_refill {
+            return Some(RefillFault::Timeout);
+        }
+        if now.saturating_duration_since(self.checked) >= settings.leak_check {
+            if let (Some(before), Some(after)) = (self.checked_percent, percent) {
+                if after - before < settings.min_rise_percent {
+                    return Some(RefillFault::Leak);
+                }
+            }
+            self.checked = now;
+            self.checked_percent = percent;
+        }
+        None
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Status {
     pub disp: DisplayStatus,
+    /// Last reading, `None` before the first one
+    pub level: Option<TankLevel>,
+    /// From continuous level sensors
+    pub percent: Option<f32>,
+    /// Litres, from continuous level sensors
+    pub volume: Option<f32>,
+    /// Forecast from recent pump use
+    pub days_left: Option<f32>,
+    pub refilling: Option<Refill>,
+    pub refill_fault: Option<RefillFault>,
+}
+impl Status {
+    /// Display status for the current level and forecast
+    pub fn describe(&self, settings: &Settings) -> DisplayStatus {
+        let (indicator, text) = match (self.refill_fault, self.level) {
+            (Some(fault), _) => (Indicator::Red, format!("{}", fault)),
+            (_, Some(TankLevel::Low | TankLevel::Empty | TankLevel::Ok)) if self.refilling.is_some() => {
+                (Indicator::Blue, String::from("Tank refilling"))
+            }
+            (_, None | Some(TankLevel::NoData)) => (Indicator::Red, String::from("No data from tank sensor")),
+            (_, Some(TankLevel::Empty)) => (Indicator::Red, String::from("Tank empty")),
+            (_, Some(TankLevel::Overfill)) => (Indicator::Red, String::from("Tank overfill")),
+            (_, Some(TankLevel::Low)) => (Indicator::Yellow, String::from("Tank low")),
+            (_, Some(TankLevel::Ok)) => match self.days_left {
+                Some(days) if days < settings.warning_days => {
+                    (Indicator::Yellow, format!("Tank empty in {:.1} days", days))
+                }
+                _ => (Indicator::Green, String::from("Tank ok")),
+            },
+        };
+        match self.percent {
+            Some(percent) => DisplayStatus::new(indicator, Some(format!("{}, {:.0}%", text, percent))),
+            None => DisplayStatus::new(indicator, Some(text)),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Interface {
     pub tank_sensor: Option<Box<dyn TankSensor>>,
+    /// Valve or helper pump filling the tank
+    pub refill: Option<Box<dyn RefillValve>>,
 }
 
 #[async_trait]
@@ -63,8 +268,19 @@ pub trait TankSensor: Send + Sync {
     async fn init(
         &mut self,
         tx_tank: tokio::sync::broadcast::Sender<(u8, Option<TankLevel>)>,
-    ) -> Result<(), Box<dyn Error>>;
-    fn read(&self) -> Result<TankLevel, Box<dyn Error>>;
+    ) -> Result<(), GrowError>;
+    fn read(&self) -> Result<TankLevel, GrowError>;
+    /// Start continuous level readings, for ultrasonic or analog float sensors
+    fn init_level(
+        &mut self,
+        _tx_level: tokio::sync::broadcast::Sender<(u8, Option<LevelReading>)>,
+    ) -> Result<(), GrowError> {
+        Err(GrowError::InterfaceMissing { kind: ZoneKind::Tank, id: self.id() })
+    }
+    /// Continuous level, distance or fraction of full
+    fn read_level(&self) -> Result<LevelReading, GrowError> {
+        Err(GrowError::InterfaceMissing { kind: ZoneKind::Tank, id: self.id() })
+    }
 }
 impl Debug for dyn TankSensor {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -72,10 +288,29 @@ impl Debug for dyn TankSensor {
     }
 }
 
+pub trait RefillValve: Send + Sync {
+    fn id(&self) -> u8;
+    /// Follow open (true) and close (false) commands from the runner
+    fn init(
+        &mut self,
+        rx_refill: tokio::sync::broadcast::Receiver<(u8, bool)>,
+    ) -> Result<(), GrowError>;
+    fn set_open(&self, open: bool) -> Result<(), GrowError>;
+    fn is_open(&self) -> Result<bool, GrowError>;
+}
+impl Debug for dyn RefillValve {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "RefillValve {{{}}}", self.id())
+    }
+}
+
 #[derive(Debug)]
 pub struct Runner {
     id: u8,
     tx_tank: broadcast::Sender<(u8, Option<TankLevel>)>,
+    tx_level: broadcast::Sender<(u8, Option<LevelReading>)>,
+    tx_refill: broadcast::Sender<(u8, bool)>,
+    tx_refill_request: broadcast::Sender<bool>,
     task: tokio::task::JoinHandle<()>,
     status: Arc<RwLock<Status>>,
 }
@@ -85,6 +320,9 @@ impl Runner {
             id,
             status,
             tx_tank: broadcast::channel(64).0,
+            tx_level: broadcast::channel(64).0,
+            tx_refill: broadcast::channel(8).0,
+            tx_refill_request: broadcast::channel(8).0,
             task: tokio::spawn(async move {}),
         }
     }
@@ -94,10 +332,31 @@ impl Runner {
     ) -> broadcast::Sender<(u8, Option<TankLevel>)> {
         self.tx_tank.clone()
     }
+    pub fn level_feedback_sender(
+        &self,
+    ) -> broadcast::Sender<(u8, Option<LevelReading>)> {
+        self.tx_level.clone()
+    }
+    pub fn refill_cmd_receiver(&self) -> broadcast::Receiver<(u8, bool)> {
+        self.tx_refill.subscribe()
+    }
+    /// Start (true) or stop (false) a refill by hand, starting clears a refill fault
+    pub fn refill_request_sender(&self) -> broadcast::Sender<bool> {
+        self.tx_refill_request.clone()
+    }
+
+    /// `pumps` are the pumps drawing from this tank, with their flow rate in litres per minute
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+    pub fn task_mut(&mut self) -> &mut tokio::task::JoinHandle<()> {
+        &mut self.task
+    }
 
     pub fn run(
         &mut self,
-        _settings: Settings,
+        settings: Settings,
+        pumps: Vec<(Arc<RwLock<pump::Status>>, f32)>,
         zone_channels: ZoneChannelsTx,
         ops_channels: OpsChannelsTx,
     ) {
@@ -106,8 +365,15 @@ impl Runner {
         let to_status_subscribers = zone_channels.zonestatus;
         let to_logger = zone_channels.zonelog;
         let to_syslog = ops_channels.syslog;
+        let heartbeat = ops_channels.heartbeats.register(&format!("tank/{}", id));
+        let mut beat = tokio::time::interval(HEARTBEAT_INTERVAL);
         let mut rx = self.tx_tank.subscribe();
+        let mut rx_level = self.tx_level.subscribe();
+        let mut rx_refill_request = self.tx_refill_request.subscribe();
+        let to_valve = self.tx_refill.clone();
         let status = self.status.clone();
+        let mut each_minute = tokio::time::interval(Duration::from_secs(60));
+        let mut each_second = tokio::time::interval(Duration::from_secs(1));
 
         self.task = tokio::spawn(async move {
             let _ = to_syslog
@@ -121,37 +387,204 @@ impl Runner {
                 Indicator::Blue,
                 Some(format!("Tank running")),
             ));
+            // Litres pumped since start, sampled each minute
+            let mut pumped: VecDeque<(Instant, f32)> = VecDeque::new();
             loop {
-                tokio::select! {
+                let refill_event = tokio::select! {
                     Ok(data) = rx.recv() => {
-                        let mut o_ds: Option<DisplayStatus> = None;
-                        match data {
-                            (_id, None) => {
-                                o_ds = Some(DisplayStatus::new(Indicator::Red, Some( format!("No data from tank sensor") )) );
-                            },
-                            (_id, Some(TankLevel::Ok)) => {
-                                o_ds = Some(DisplayStatus::new(Indicator::Green, Some( format!("Tank ok") )) );
-                            },
-                            (_id, Some(TankLevel::Low)) => {
-                                o_ds = Some(DisplayStatus::new(Indicator::Yellow, Some( format!("Tank low") )) );
-                            },
-                            (_id, Some(TankLevel::Empty)) => {
-                                o_ds = Some(DisplayStatus::new(Indicator::Red, Some( format!("Tank empty") )) );
-                            },
-                            (_id, Some(TankLevel::Overfill)) => {
-                                o_ds = Some(DisplayStatus::new(Indicator::Red, Some( format!("Tank overfill") )) );
-                            },
-                            _ => ()
-                        }
-                        let _ = to_logger.send(ZoneLog::Tank {id: data.0, changed_status: o_ds.clone() }).await;
+                        let o_ds = {
+                            let mut lock = status.write();
+                            lock.level = Some(data.1.unwrap_or(TankLevel::NoData));
+                            Some(lock.describe(&settings))
+                        };
+                        let (percent, days_left) = {
+                            let lock = status.read();
+                            (lock.percent, lock.days_left)
+                        };
+                        let refilling = status.read().refilling.is_some();
+                        let _ = to_logger.send(ZoneLog::Tank {id: data.0, level: data.1, percent, days_left, refilling, changed_status: o_ds.clone() }).await;
                         match o_ds {
                             Some(ds) => { set_and_send(ds); }
                             None => {}
                         }
+                        None
+                    }
+                    Ok((_id, reading)) = rx_level.recv() => {
+                        // Frequent readings, status only sent on indicator change
+                        let (level, percent, days_left, refilling, ds) = {
+                            let mut lock = status.write();
+                            let percent = reading.map(|r| settings.percent(r));
+                            lock.percent = percent;
+                            lock.volume = percent.map(|p| settings.volume(p));
+                            lock.level = Some(percent.map(|p| settings.level(p)).unwrap_or(TankLevel::NoData));
+                            (lock.level, lock.percent, lock.days_left, lock.refilling.is_some(), lock.describe(&settings))
+                        };
+                        let changed = status.read().disp.indicator != ds.indicator;
+                        let o_ds = if changed { Some(ds) } else { None };
+                        let _ = to_logger.send(ZoneLog::Tank {id, level, percent, days_left, refilling, changed_status: o_ds.clone() }).await;
+                        if let Some(ds) = o_ds {
+                            set_and_send(ds);
+                        }
+                        None
+                    }
+                    _ = each_minute.tick() => {
+                        let now = Instant::now();
+                        let litres: f32 = pumps
+                            .iter()
+                            .map(|(pump, flow_rate)| pump.read().total_runtime(now).as_secs_f32() / 60.0 * flow_rate)
+                            .sum();
+                        pumped.push_back((now, litres));
+                        while pumped.front().is_some_and(|(t, _)| now.saturating_duration_since(*t) > settings.forecast_window) {
+                            pumped.pop_front();
+                        }
+                        let days_left = forecast(&pumped, status.read().volume);
+                        let ds = {
+                            let mut lock = status.write();
+                            lock.days_left = days_left;
+                            lock.describe(&settings)
+                        };
+                        if status.read().disp.indicator != ds.indicator {
+                            let (level, percent, refilling) = {
+                                let lock = status.read();
+                                (lock.level, lock.percent, lock.refilling.is_some())
+                            };
+                            let _ = to_logger.send(ZoneLog::Tank {id, level, percent, days_left, refilling, changed_status: Some(ds.clone()) }).await;
+                            set_and_send(ds);
+                        }
+                        None
+                    }
+                    Ok(start) = rx_refill_request.recv() => {
+                        let mut lock = status.write();
+                        if start {
+                            lock.refill_fault = None;
+                            match lock.refilling {
+                                Some(_) => None,
+                                None => {
+                                    lock.refilling = Some(Refill::new(Instant::now(), lock.percent));
+                                    Some(RefillEvent::Started)
+                                }
+                            }
+                        } else {
+                            lock.refilling.take().map(|_| RefillEvent::Stopped)
+                        }
+                    }
+                    _ = each_second.tick() => {
+                        let now = Instant::now();
+                        let mut lock = status.write();
+                        let (level, percent) = (lock.level, lock.percent);
+                        let fault = lock.refilling.as_mut().and_then(|refill| refill.check(&settings, level, percent, now));
+                        match (lock.refilling, fault) {
+                            (Some(_), Some(fault)) => {
+                                lock.refilling = None;
+                                lock.refill_fault = Some(fault);
+                                Some(RefillEvent::Fault(fault))
+                            }
+                            (Some(_), None) if settings.filled(level, percent) => {
+                                lock.refilling = None;
+                                Some(RefillEvent::Done)
+                            }
+                            (None, _) if settings.refill
+                                && lock.refill_fault.is_none()
+                                && matches!(level, Some(TankLevel::Low | TankLevel::Empty)) => {
+                                lock.refilling = Some(Refill::new(now, percent));
+                                Some(RefillEvent::Started)
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ = beat.tick() => {
+                        heartbeat.beat();
+                        None
                     }
                     else => { break }
                 };
+                if let Some(event) = refill_event {
+                    let _ = to_valve.send((id, event == RefillEvent::Started));
+                    let (log_level, msg) = match event {
+                        RefillEvent::Started => (LogLevel::Info, String::from("Refill started")),
+                        RefillEvent::Done => (LogLevel::Info, String::from("Refill done")),
+                        RefillEvent::Stopped => (LogLevel::Info, String::from("Refill stopped")),
+                        RefillEvent::Fault(fault) => (LogLevel::Warning, format!("Refill stopped: {}", fault)),
+                    };
+                    let _ = to_syslog
+                        .send(SysLog::with_level(log_level, msg).source(&format!("tank/{}", id)))
+                        .await;
+                    let (level, percent, days_left, refilling, ds) = {
+                        let lock = status.read();
+                        (lock.level, lock.percent, lock.days_left, lock.refilling.is_some(), lock.describe(&settings))
+                    };
+                    let _ = to_logger.send(ZoneLog::Tank {id, level, percent, days_left, refilling, changed_status: Some(ds.clone()) }).await;
+                    set_and_send(ds);
+                }
             }
         });
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RefillEvent {
+    Started,
+    Done,
+    Stopped,
+    Fault(RefillFault),
+}
+
+/// Days until `volume` is used up at the rate pumped over the samples, needs an hour of samples
+fn forecast(pumped: &VecDeque<(Instant, f32)>, volume: Option<f32>) -> Option<f32> {
+    let (first, last) = (pumped.front()?, pumped.back()?);
+    let elapsed = last.0.saturating_duration_since(first.0);
+    if elapsed < Duration::from_secs(3600) {
+        return None;
+    }
+    let per_day = (last.1 - first.1) / elapsed.as_secs_f32() * 86400.0;
+    if per_day <= 0.0 {
+        return None;
+    }
+    Some(volume? / per_day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            max_refill: Duration::from_secs(300),
+            leak_check: Duration::from_secs(60),
+            min_rise_percent: 2.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn refill_rising_passes_leak_check() {
+        let start = Instant::now();
+        let mut refill = Refill::new(start, Some(20.0));
+        let at = |secs| start + Duration::from_secs(secs);
+        assert_eq!(refill.check(&settings(), Some(TankLevel::Low), Some(21.0), at(30)), None);
+        assert_eq!(refill.check(&settings(), Some(TankLevel::Low), Some(23.0), at(60)), None);
+        assert_eq!(refill.checked_percent, Some(23.0));
+        assert_eq!(refill.check(&settings(), Some(TankLevel::Ok), Some(24.0), at(120)), Some(RefillFault::Leak));
+    }
+
+    #[test]
+    fn refill_faults() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut refill = Refill::new(start, None);
+        assert_eq!(refill.check(&settings(), Some(TankLevel::Low), None, at(60)), None);
+        assert_eq!(refill.check(&settings(), Some(TankLevel::Low), None, at(301)), Some(RefillFault::Timeout));
+        assert_eq!(refill.check(&settings(), Some(TankLevel::Overfill), None, at(1)), Some(RefillFault::Overfill));
+        assert_eq!(refill.check(&settings(), Some(TankLevel::NoData), None, at(1)), Some(RefillFault::NoData));
+        assert_eq!(refill.check(&settings(), None, None, at(1)), Some(RefillFault::NoData));
+    }
+
+    #[test]
+    fn refill_faults_when_percent_lost() {
+        let start = Instant::now();
+        let mut refill = Refill::new(start, Some(20.0));
+        let fault = refill.check(&settings(), Some(TankLevel::Low), None, start + Duration::from_secs(60));
+        assert_eq!(fault, Some(RefillFault::NoData));
+        assert_eq!(refill.checked_percent, Some(20.0));
+    }
+}
@@ -0,0 +1,52 @@
+{
+  "enabled": true,
+  "rules": [
+    {
+      "kind": "Air",
+      "id": null,
+      "indicator": "Red",
+      "hold": { "secs": 0, "nanos": 0 },
+      "repeat": { "secs": 1800, "nanos": 0 },
+      "escalate_after": { "secs": 3600, "nanos": 0 },
+      "notify": ["mail"],
+      "escalate_to": ["webhook"]
+    },
+    {
+      "kind": "Tank",
+      "id": 1,
+      "indicator": "Red",
+      "hold": { "secs": 600, "nanos": 0 },
+      "repeat": { "secs": 3600, "nanos": 0 },
+      "escalate_after": null,
+      "notify": ["mail", "beep"],
+      "escalate_to": []
+    }
+  ],
+  "notifiers": [
+    {
+      "Smtp": {
+        "name": "mail",
+        "host": "127.0.0.1",
+        "port": 25,
+        "helo": "greenhouse",
+        "from": "greenhouse@localhost",
+        "to": ["root@localhost"]
+      }
+    },
+    {
+      "Webhook": {
+        "name": "webhook",
+        "host": "127.0.0.1",
+        "port": 8080,
+        "path": "/alert"
+      }
+    },
+    {
+      "Command": {
+        "name": "beep",
+        "program": "/usr/local/bin/grow-alert",
+        "args": []
+      }
+    }
+  ]
+}
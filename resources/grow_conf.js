@@ -7,8 +7,13 @@
           "temp_high_red_alert": 40.0,
           "temp_fan_low": 25.0,
           "temp_fan_high": 30.0,
-          "fan_rpm_low_red_alert": 10.0
-        }
+          "fan_rpm_low_red_alert": 10.0,
+          "safe_duty_cycle": 0.0
+        },
+        "hardware": [
+          { "slot": "Fan", "driver": "pwmfan" },
+          { "slot": "Thermo", "driver": "pcf8591-thermistor" }
+        ]
       }
     },
     {
@@ -19,8 +24,12 @@
           "temp_high_red_alert": 40.0,
           "temp_fan_low": 25.0,
           "temp_fan_high": 30.0,
-          "fan_rpm_low_red_alert": 10.0
-        }
+          "fan_rpm_low_red_alert": 10.0,
+          "safe_duty_cycle": 0.0
+        },
+        "hardware": [
+          { "slot": "Thermo", "driver": "lpu-temp" }
+        ]
       }
     },
     {
@@ -48,7 +57,10 @@
             "y": 4254,
             "z": 0
           }
-        }
+        },
+        "hardware": [
+          { "slot": "Moist", "driver": "pcf8591-moisture" }
+        ]
       }
     },
     {
@@ -75,8 +87,19 @@
             "x": 231,
             "y": 1923,
             "z": 0
-          }
-        }
+          },
+          "route": [
+            { "at": "pot-2" },
+            {
+              "at": { "x": 231, "y": 2500, "z": 0 },
+              "pump_time": { "secs": 0, "nanos": 0 },
+              "pump_on_way": true
+            }
+          ]
+        },
+        "hardware": [
+          { "slot": "Moist", "driver": "pcf8591-moisture" }
+        ]
       }
     },
     {
@@ -103,8 +126,12 @@
             "x": 87,
             "y": 4254,
             "z": 0
-          }
-        }
+          },
+          "positions": ["pot-1", "pot-3"]
+        },
+        "hardware": [
+          { "slot": "Moist", "driver": "dummy-moisture" }
+        ]
       }
     },
     {
@@ -125,31 +152,100 @@
             0,
             0
           ]
-        }
+        },
+        "hardware": [
+          { "slot": "Lightmeter", "driver": "pcf8591-photoresistor" },
+          { "slot": "Lamp", "driver": "pcf8591-led" }
+        ]
       }
     },
     {
       "Arm": {
         "id": 1,
-        "settings": {}
+        "settings": {
+          "x": { "min": -50, "max": 400, "home": 0 },
+          "y": { "min": 0, "max": 4500, "home": 0 },
+          "z": { "min": 0, "max": 0, "home": 0 },
+          "z_travel": 0,
+          "positions": {
+            "park": { "x": 0, "y": 0, "z": 0 },
+            "pot-1": { "x": 87, "y": 4254, "z": 0 },
+            "pot-2": { "x": 231, "y": 1923, "z": 0 },
+            "pot-3": { "x": 231, "y": 3100, "z": 0 }
+          }
+        },
+        "hardware": [
+          { "slot": "Arm", "driver": "lpu-arm" }
+        ]
       }
     },
     {
       "Pump": {
         "id": 1,
-        "settings": {}
+        "settings": {
+          "tank_id": 1,
+          "max_runtime": {
+            "secs": 30,
+            "nanos": 0
+          },
+          "max_per_hour": {
+            "secs": 300,
+            "nanos": 0
+          },
+          "speed": 50,
+          "stall_speed": 5,
+          "no_load_speed": 0,
+          "feedback_time": {
+            "secs": 2,
+            "nanos": 0
+          },
+          "flow_rate": 0.5
+        },
+        "hardware": [
+          { "slot": "Pump", "driver": "lpu-pump" }
+        ]
       }
     },
     {
       "Tank": {
         "id": 1,
-        "settings": {}
+        "settings": {
+          "capacity": 10.0,
+          "empty_distance": 300.0,
+          "full_distance": 50.0,
+          "low_percent": 25.0,
+          "empty_percent": 5.0,
+          "overfill_percent": 105.0,
+          "warning_days": 2.0,
+          "forecast_window": {
+            "secs": 259200,
+            "nanos": 0
+          },
+          "refill": true,
+          "fill_percent": 90.0,
+          "max_refill": {
+            "secs": 300,
+            "nanos": 0
+          },
+          "leak_check": {
+            "secs": 60,
+            "nanos": 0
+          },
+          "min_rise_percent": 2.0
+        },
+        "hardware": [
+          { "slot": "TankSensor", "driver": "lpu-vision" },
+          { "slot": "Refill", "driver": "gpio-valve", "params": { "pin": 17 } }
+        ]
       }
     },
     {
       "Aux": {
         "id": 1,
-        "settings": {}
+        "settings": {},
+        "hardware": [
+          { "slot": "Aux", "driver": "lpu-hub" }
+        ]
       }
     }
   ]
@@ -0,0 +1,7 @@
+{
+    "enabled": true,
+    "check_interval": { "secs": 5, "nanos": 0 },
+    "missed_after": { "secs": 60, "nanos": 0 },
+    "lock_threshold": { "secs": 30, "nanos": 0 },
+    "watchdog": { "File": "/tmp/grow-watchdog" }
+}
@@ -0,0 +1,17 @@
+{
+  "filter": {
+    "level": "Info",
+    "sources": {
+      "zonelog": null,
+      "zonestatus": null,
+      "water": "Debug",
+      "xymon": "Warning"
+    }
+  },
+  "sinks": [
+    { "Console": { "level": "Trace", "color": true } },
+    { "File": { "level": "Info", "path": "grow.log", "max_bytes": 1048576, "keep": 5 } },
+    { "JsonLines": { "level": "Debug", "path": "grow-log.jsonl", "max_bytes": 4194304, "keep": 3 } },
+    { "Syslog": { "level": "Notice", "socket": "/dev/log", "facility": 1, "app_name": "grow" } }
+  ]
+}
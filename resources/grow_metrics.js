@@ -0,0 +1,4 @@
+{
+    "enabled": true,
+    "bind": "0.0.0.0:9184"
+}
@@ -0,0 +1,45 @@
+{
+  "profiles": [
+    {
+      "name": "seedling",
+      "light": {
+        "1": { "lamp_on": [5, 0, 0, 0], "lamp_off": [23, 0, 0, 0] }
+      },
+      "water": {
+        "1": { "moisture_limit_water": 45.0, "pump_time": { "secs": 3, "nanos": 0 } }
+      },
+      "air": {
+        "1": { "temp_fan_low": 24.0, "temp_fan_high": 27.0 }
+      }
+    },
+    {
+      "name": "vegetative",
+      "light": {
+        "1": { "lamp_on": [6, 0, 0, 0], "lamp_off": [23, 59, 0, 0] }
+      },
+      "water": {
+        "1": { "moisture_limit_water": 35.0 }
+      }
+    },
+    {
+      "name": "flowering",
+      "light": {
+        "1": { "lamp_on": [7, 0, 0, 0], "lamp_off": [19, 0, 0, 0] }
+      },
+      "water": {
+        "1": { "moisture_limit_water": 30.0, "pump_time": { "secs": 6, "nanos": 0 } }
+      },
+      "air": {
+        "1": { "temp_high_yellow_warning": 28.0, "temp_fan_high": 26.0 }
+      }
+    }
+  ],
+  "calendar": {
+    "start": "2024-03-01",
+    "stages": [
+      { "profile": "seedling", "days": 14 },
+      { "profile": "vegetative", "days": 28 },
+      { "profile": "flowering", "days": 56 }
+    ]
+  }
+}
@@ -0,0 +1,33 @@
+{
+  "enabled": true,
+  "check_interval": { "secs": 30, "nanos": 0 },
+  "rules": [
+    {
+      "name": "heatwave",
+      "when": { "TempAbove": { "id": 1, "value": 32.0 } },
+      "then": [
+        { "PostponeWatering": { "id": null } },
+        { "ForceFan": { "id": 1, "setting": "High" } }
+      ]
+    },
+    {
+      "name": "tank-low",
+      "when": { "TankLevel": { "id": 1, "level": "Low" } },
+      "then": [
+        { "ScalePumpTime": { "id": null, "factor": 0.7 } }
+      ]
+    },
+    {
+      "name": "night-fan",
+      "when": {
+        "All": [
+          { "TimeBetween": { "from": [22, 0, 0, 0], "to": [6, 0, 0, 0] } },
+          { "Not": { "TempAbove": { "id": 1, "value": 26.0 } } }
+        ]
+      },
+      "then": [
+        { "ForceFan": { "id": 1, "setting": "Low" } }
+      ]
+    }
+  ]
+}
@@ -1,5 +1,11 @@
 {
+    "enabled": true,
     "port": 1984,
     "host": "192.168.1.81",
-    "client": "greenhouse"
-}
\ No newline at end of file
+    "client": "greenhouse",
+    "lifetime": "1h",
+    "report_interval": { "secs": 300, "nanos": 0 },
+    "flush_interval": { "secs": 5, "nanos": 0 },
+    "max_backoff": { "secs": 600, "nanos": 0 },
+    "data_test": "grow"
+}